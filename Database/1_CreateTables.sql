@@ -27,3 +27,336 @@ CREATE INDEX IF NOT EXISTS IX_Notes_UserId ON Notes(UserId);
 CREATE INDEX IF NOT EXISTS IX_Notes_Title ON Notes(Title);
 CREATE INDEX IF NOT EXISTS IX_Users_Email ON Users(Email);
 CREATE INDEX IF NOT EXISTS IX_Users_Username ON Users(Username);
+
+-- Create RefreshTokens table
+-- Tokens are stored hashed; every rotation keeps the FamilyId of the token it replaces
+CREATE TABLE IF NOT EXISTS RefreshTokens (
+    Id SERIAL PRIMARY KEY,
+    UserId INT NOT NULL,
+    FamilyId UUID NOT NULL,
+    TokenHash VARCHAR(64) NOT NULL UNIQUE,
+    ExpiresAt TIMESTAMPTZ NOT NULL,
+    RevokedAt TIMESTAMPTZ,
+    CreatedAt TIMESTAMPTZ DEFAULT (CURRENT_TIMESTAMP AT TIME ZONE 'Asia/Bangkok'),
+    CONSTRAINT fk_refresh_token_user FOREIGN KEY(UserId) REFERENCES Users(Id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS IX_RefreshTokens_UserId ON RefreshTokens(UserId);
+CREATE INDEX IF NOT EXISTS IX_RefreshTokens_FamilyId ON RefreshTokens(FamilyId);
+
+-- Token version counter: bumping it invalidates every access token issued before
+ALTER TABLE Users ADD COLUMN IF NOT EXISTS TokenVersion INT NOT NULL DEFAULT 0;
+
+-- Create RevokedTokens table
+-- Holds the IDs (jti) of access tokens revoked before they expired
+CREATE TABLE IF NOT EXISTS RevokedTokens (
+    Jti VARCHAR(64) PRIMARY KEY,
+    UserId INT NOT NULL,
+    ExpiresAt TIMESTAMPTZ NOT NULL,
+    RevokedAt TIMESTAMPTZ DEFAULT (CURRENT_TIMESTAMP AT TIME ZONE 'Asia/Bangkok'),
+    CONSTRAINT fk_revoked_token_user FOREIGN KEY(UserId) REFERENCES Users(Id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS IX_RevokedTokens_ExpiresAt ON RevokedTokens(ExpiresAt);
+
+-- Create PasswordResetTokens table
+-- Tokens are stored hashed and can be used once
+CREATE TABLE IF NOT EXISTS PasswordResetTokens (
+    Id SERIAL PRIMARY KEY,
+    UserId INT NOT NULL,
+    TokenHash VARCHAR(64) NOT NULL UNIQUE,
+    ExpiresAt TIMESTAMPTZ NOT NULL,
+    UsedAt TIMESTAMPTZ,
+    CreatedAt TIMESTAMPTZ DEFAULT (CURRENT_TIMESTAMP AT TIME ZONE 'Asia/Bangkok'),
+    CONSTRAINT fk_password_reset_token_user FOREIGN KEY(UserId) REFERENCES Users(Id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS IX_PasswordResetTokens_UserId ON PasswordResetTokens(UserId);
+
+-- Email verification flag; new accounts start unverified
+ALTER TABLE Users ADD COLUMN IF NOT EXISTS EmailVerified BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Create EmailVerificationTokens table
+-- Email is the address the token proves ownership of
+CREATE TABLE IF NOT EXISTS EmailVerificationTokens (
+    Id SERIAL PRIMARY KEY,
+    UserId INT NOT NULL,
+    Email VARCHAR(255) NOT NULL,
+    TokenHash VARCHAR(64) NOT NULL UNIQUE,
+    ExpiresAt TIMESTAMPTZ NOT NULL,
+    UsedAt TIMESTAMPTZ,
+    CreatedAt TIMESTAMPTZ DEFAULT (CURRENT_TIMESTAMP AT TIME ZONE 'Asia/Bangkok'),
+    CONSTRAINT fk_email_verification_token_user FOREIGN KEY(UserId) REFERENCES Users(Id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS IX_EmailVerificationTokens_UserId ON EmailVerificationTokens(UserId);
+
+-- TOTP two-factor authentication; a secret only takes effect once TotpEnabled is set.
+-- TotpLastUsedStep is the last accepted time step, so a code cannot be replayed.
+ALTER TABLE Users ADD COLUMN IF NOT EXISTS TotpSecret VARCHAR(64);
+ALTER TABLE Users ADD COLUMN IF NOT EXISTS TotpEnabled BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE Users ADD COLUMN IF NOT EXISTS TotpLastUsedStep BIGINT;
+
+-- Create TotpRecoveryCodes table
+-- Single-use recovery codes, stored hashed
+CREATE TABLE IF NOT EXISTS TotpRecoveryCodes (
+    Id SERIAL PRIMARY KEY,
+    UserId INT NOT NULL,
+    CodeHash VARCHAR(64) NOT NULL,
+    UsedAt TIMESTAMPTZ,
+    CreatedAt TIMESTAMPTZ DEFAULT (CURRENT_TIMESTAMP AT TIME ZONE 'Asia/Bangkok'),
+    CONSTRAINT fk_totp_recovery_code_user FOREIGN KEY(UserId) REFERENCES Users(Id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS IX_TotpRecoveryCodes_UserId ON TotpRecoveryCodes(UserId);
+
+-- Create LoginAttempts table
+-- Failed login counters keyed by account (email:<address>), client IP (ip:<address>)
+-- or two-factor challenge (2fa:<user id>)
+CREATE TABLE IF NOT EXISTS LoginAttempts (
+    AttemptKey VARCHAR(320) PRIMARY KEY,
+    FailedCount INT NOT NULL DEFAULT 0,
+    LockedUntil TIMESTAMPTZ,
+    LastFailedAt TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS IX_LoginAttempts_LastFailedAt ON LoginAttempts(LastFailedAt);
+
+-- Create PersonalAccessTokens table
+-- Long-lived, scoped tokens for scripts and CI; only the hash is stored,
+-- TokenPrefix is kept so users can tell their tokens apart
+CREATE TABLE IF NOT EXISTS PersonalAccessTokens (
+    Id SERIAL PRIMARY KEY,
+    UserId INT NOT NULL,
+    Name VARCHAR(100) NOT NULL,
+    TokenHash VARCHAR(64) NOT NULL UNIQUE,
+    TokenPrefix VARCHAR(16) NOT NULL,
+    Scopes TEXT[] NOT NULL,
+    ExpiresAt TIMESTAMPTZ,
+    LastUsedAt TIMESTAMPTZ,
+    RevokedAt TIMESTAMPTZ,
+    CreatedAt TIMESTAMPTZ DEFAULT (CURRENT_TIMESTAMP AT TIME ZONE 'Asia/Bangkok'),
+    CONSTRAINT fk_personal_access_token_user FOREIGN KEY(UserId) REFERENCES Users(Id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS IX_PersonalAccessTokens_UserId ON PersonalAccessTokens(UserId);
+
+-- Create OAuthStates table
+-- Pending OpenID Connect logins: the PKCE code verifier and nonce, keyed by the hashed state parameter
+CREATE TABLE IF NOT EXISTS OAuthStates (
+    StateHash VARCHAR(64) PRIMARY KEY,
+    Provider VARCHAR(50) NOT NULL,
+    CodeVerifier VARCHAR(128) NOT NULL,
+    Nonce VARCHAR(64) NOT NULL,
+    ExpiresAt TIMESTAMPTZ NOT NULL,
+    CreatedAt TIMESTAMPTZ DEFAULT (CURRENT_TIMESTAMP AT TIME ZONE 'Asia/Bangkok')
+);
+
+-- Create UserIdentities table
+-- External OpenID Connect identities (provider + subject) linked to local users
+CREATE TABLE IF NOT EXISTS UserIdentities (
+    Id SERIAL PRIMARY KEY,
+    UserId INT NOT NULL,
+    Provider VARCHAR(50) NOT NULL,
+    Subject VARCHAR(255) NOT NULL,
+    Email VARCHAR(255),
+    CreatedAt TIMESTAMPTZ DEFAULT (CURRENT_TIMESTAMP AT TIME ZONE 'Asia/Bangkok'),
+    CONSTRAINT uq_user_identity UNIQUE (Provider, Subject),
+    CONSTRAINT fk_user_identity_user FOREIGN KEY(UserId) REFERENCES Users(Id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS IX_UserIdentities_UserId ON UserIdentities(UserId);
+
+-- Create Sessions table
+-- One row per login; the session ID is also the FamilyId of its refresh tokens
+CREATE TABLE IF NOT EXISTS Sessions (
+    Id UUID PRIMARY KEY,
+    UserId INT NOT NULL,
+    DeviceName VARCHAR(100) NOT NULL DEFAULT 'Unknown device',
+    UserAgent VARCHAR(500),
+    IpAddress INET,
+    CreatedAt TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+    LastSeenAt TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+    RevokedAt TIMESTAMPTZ,
+    CONSTRAINT fk_session_user FOREIGN KEY(UserId) REFERENCES Users(Id) ON DELETE CASCADE
+);
+
+-- Older databases stamped CreatedAt with Bangkok wall-clock time while LastSeenAt used UTC
+ALTER TABLE Sessions ALTER COLUMN CreatedAt SET DEFAULT CURRENT_TIMESTAMP;
+
+CREATE INDEX IF NOT EXISTS IX_Sessions_UserId ON Sessions(UserId);
+
+-- Create Roles, Permissions, RolePermissions and UserRoles tables
+-- Users get permissions through their roles; both are embedded in access tokens
+CREATE TABLE IF NOT EXISTS Roles (
+    Id SERIAL PRIMARY KEY,
+    Name VARCHAR(50) NOT NULL UNIQUE,
+    Description VARCHAR(255),
+    CreatedAt TIMESTAMPTZ DEFAULT (CURRENT_TIMESTAMP AT TIME ZONE 'Asia/Bangkok')
+);
+
+CREATE TABLE IF NOT EXISTS Permissions (
+    Id SERIAL PRIMARY KEY,
+    Name VARCHAR(100) NOT NULL UNIQUE,
+    Description VARCHAR(255)
+);
+
+CREATE TABLE IF NOT EXISTS RolePermissions (
+    RoleId INT NOT NULL,
+    PermissionId INT NOT NULL,
+    PRIMARY KEY (RoleId, PermissionId),
+    CONSTRAINT fk_role_permission_role FOREIGN KEY(RoleId) REFERENCES Roles(Id) ON DELETE CASCADE,
+    CONSTRAINT fk_role_permission_permission FOREIGN KEY(PermissionId) REFERENCES Permissions(Id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS UserRoles (
+    UserId INT NOT NULL,
+    RoleId INT NOT NULL,
+    AssignedAt TIMESTAMPTZ DEFAULT (CURRENT_TIMESTAMP AT TIME ZONE 'Asia/Bangkok'),
+    PRIMARY KEY (UserId, RoleId),
+    CONSTRAINT fk_user_role_user FOREIGN KEY(UserId) REFERENCES Users(Id) ON DELETE CASCADE,
+    CONSTRAINT fk_user_role_role FOREIGN KEY(RoleId) REFERENCES Roles(Id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS IX_UserRoles_RoleId ON UserRoles(RoleId);
+
+-- Built-in permissions and the admin role, which holds all of them
+INSERT INTO Permissions (Name, Description) VALUES
+    ('users:read', 'Read the profile of any user'),
+    ('users:admin', 'Manage user accounts'),
+    ('roles:read', 'List roles and their permissions'),
+    ('roles:assign', 'Grant and revoke roles')
+ON CONFLICT (Name) DO NOTHING;
+
+INSERT INTO Roles (Name, Description) VALUES
+    ('admin', 'Full administrative access')
+ON CONFLICT (Name) DO NOTHING;
+
+INSERT INTO RolePermissions (RoleId, PermissionId)
+SELECT r.Id, p.Id
+FROM Roles r
+CROSS JOIN Permissions p
+WHERE r.Name = 'admin'
+ON CONFLICT DO NOTHING;
+
+-- Account suspension; suspended users cannot log in and their tokens are rejected
+ALTER TABLE Users ADD COLUMN IF NOT EXISTS SuspendedAt TIMESTAMPTZ;
+ALTER TABLE Users ADD COLUMN IF NOT EXISTS SuspensionReason VARCHAR(500);
+
+-- Append-only log of security-relevant events (logins, token refreshes, password and 2FA changes, ...).
+-- UserId is NULL when the account is unknown, e.g. a failed login for an email nobody registered;
+-- Email is the address that was tried. There is no foreign key on UserId: the history outlives
+-- the account, but once it is deleted its events are anonymized (see sp_anonymize_auth_events).
+CREATE TABLE IF NOT EXISTS AuthEvents (
+    Id BIGSERIAL PRIMARY KEY,
+    UserId INT,
+    Email VARCHAR(255),
+    EventType VARCHAR(50) NOT NULL,
+    Success BOOLEAN NOT NULL,
+    Reason VARCHAR(500),
+    IpAddress INET,
+    UserAgent VARCHAR(500),
+    CreatedAt TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP
+);
+
+ALTER TABLE AuthEvents DROP CONSTRAINT IF EXISTS fk_auth_event_user;
+
+CREATE INDEX IF NOT EXISTS IX_AuthEvents_UserId ON AuthEvents(UserId, CreatedAt DESC);
+CREATE INDEX IF NOT EXISTS IX_AuthEvents_CreatedAt ON AuthEvents(CreatedAt DESC);
+
+-- The only change allowed is erasing the personal details of an event
+CREATE OR REPLACE FUNCTION fn_auth_events_append_only()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF NEW.Id = OLD.Id
+       AND NEW.UserId IS NOT DISTINCT FROM OLD.UserId
+       AND NEW.EventType = OLD.EventType
+       AND NEW.Success = OLD.Success
+       AND NEW.CreatedAt IS NOT DISTINCT FROM OLD.CreatedAt
+       AND (NEW.Reason IS NULL OR NEW.Reason = OLD.Reason)
+       AND NEW.Email IS NULL AND NEW.IpAddress IS NULL AND NEW.UserAgent IS NULL THEN
+        RETURN NEW;
+    END IF;
+
+    RAISE EXCEPTION 'AuthEvents is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS tr_auth_events_append_only ON AuthEvents;
+CREATE TRIGGER tr_auth_events_append_only
+BEFORE UPDATE ON AuthEvents
+FOR EACH ROW EXECUTE FUNCTION fn_auth_events_append_only();
+
+-- Self-service account deletion; the account and everything it owns is purged once this time passes
+ALTER TABLE Users ADD COLUMN IF NOT EXISTS DeletionScheduledAt TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS IX_Users_DeletionScheduledAt ON Users(DeletionScheduledAt) WHERE DeletionScheduledAt IS NOT NULL;
+
+-- Profile details the user can edit; NULL when not set
+ALTER TABLE Users ADD COLUMN IF NOT EXISTS DisplayName VARCHAR(100);
+ALTER TABLE Users ADD COLUMN IF NOT EXISTS Bio VARCHAR(500);
+ALTER TABLE Users ADD COLUMN IF NOT EXISTS Locale VARCHAR(35);
+ALTER TABLE Users ADD COLUMN IF NOT EXISTS Timezone VARCHAR(64);
+
+-- Current avatar; its thumbnails are stored under avatars/<user id>/<version>/ in the storage backend
+ALTER TABLE Users ADD COLUMN IF NOT EXISTS AvatarVersion UUID;
+
+-- Keyset pagination walks a user's notes by (UpdatedAt, Id), newest first
+CREATE INDEX IF NOT EXISTS IX_Notes_UserId_UpdatedAt_Id ON Notes(UserId, UpdatedAt DESC, Id DESC);
+
+-- Tags belong to a user; names are unique per user regardless of case
+CREATE TABLE IF NOT EXISTS Tags (
+    Id SERIAL PRIMARY KEY,
+    UserId INT NOT NULL,
+    Name VARCHAR(50) NOT NULL,
+    CreatedAt TIMESTAMPTZ DEFAULT (CURRENT_TIMESTAMP AT TIME ZONE 'Asia/Bangkok'),
+    CONSTRAINT fk_tags_user FOREIGN KEY(UserId) REFERENCES Users(Id) ON DELETE CASCADE
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS UX_Tags_UserId_Name ON Tags(UserId, LOWER(Name));
+
+CREATE TABLE IF NOT EXISTS NoteTags (
+    NoteId INT NOT NULL,
+    TagId INT NOT NULL,
+    PRIMARY KEY (NoteId, TagId),
+    CONSTRAINT fk_note_tags_note FOREIGN KEY(NoteId) REFERENCES Notes(Id) ON DELETE CASCADE,
+    CONSTRAINT fk_note_tags_tag FOREIGN KEY(TagId) REFERENCES Tags(Id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS IX_NoteTags_TagId ON NoteTags(TagId);
+
+-- Notebooks nest as a tree per user; top-level notebooks have no parent
+CREATE TABLE IF NOT EXISTS Notebooks (
+    Id SERIAL PRIMARY KEY,
+    UserId INT NOT NULL,
+    ParentId INT,
+    Name VARCHAR(100) NOT NULL,
+    CreatedAt TIMESTAMPTZ DEFAULT (CURRENT_TIMESTAMP AT TIME ZONE 'Asia/Bangkok'),
+    UpdatedAt TIMESTAMPTZ DEFAULT (CURRENT_TIMESTAMP AT TIME ZONE 'Asia/Bangkok'),
+    CONSTRAINT fk_notebooks_user FOREIGN KEY(UserId) REFERENCES Users(Id) ON DELETE CASCADE,
+    CONSTRAINT fk_notebooks_parent FOREIGN KEY(ParentId) REFERENCES Notebooks(Id) ON DELETE CASCADE,
+    CONSTRAINT ck_notebooks_not_own_parent CHECK (ParentId <> Id)
+);
+
+CREATE INDEX IF NOT EXISTS IX_Notebooks_UserId ON Notebooks(UserId);
+CREATE INDEX IF NOT EXISTS IX_Notebooks_ParentId ON Notebooks(ParentId);
+
+-- Notes outside of any notebook have no NotebookId
+ALTER TABLE Notes ADD COLUMN IF NOT EXISTS NotebookId INT
+    CONSTRAINT fk_notes_notebook REFERENCES Notebooks(Id) ON DELETE SET NULL;
+
+CREATE INDEX IF NOT EXISTS IX_Notes_NotebookId ON Notes(NotebookId);
+
+-- Grants another user read or write access to a note; the note's owner keeps full control
+CREATE TABLE IF NOT EXISTS NoteShares (
+    NoteId INT NOT NULL,
+    UserId INT NOT NULL,
+    Permission VARCHAR(10) NOT NULL,
+    CreatedAt TIMESTAMPTZ DEFAULT (CURRENT_TIMESTAMP AT TIME ZONE 'Asia/Bangkok'),
+    PRIMARY KEY (NoteId, UserId),
+    CONSTRAINT fk_note_shares_note FOREIGN KEY(NoteId) REFERENCES Notes(Id) ON DELETE CASCADE,
+    CONSTRAINT fk_note_shares_user FOREIGN KEY(UserId) REFERENCES Users(Id) ON DELETE CASCADE,
+    CONSTRAINT ck_note_shares_permission CHECK (Permission IN ('read', 'write'))
+);
+
+CREATE INDEX IF NOT EXISTS IX_NoteShares_UserId ON NoteShares(UserId);
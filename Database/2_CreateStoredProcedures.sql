@@ -19,24 +19,53 @@ END;
 $$ LANGUAGE plpgsql;
 
 -- User Login Function
+DROP FUNCTION IF EXISTS sp_login_user(VARCHAR);
 CREATE OR REPLACE FUNCTION sp_login_user(p_email VARCHAR)
-RETURNS TABLE (Id INT, Username VARCHAR, Email VARCHAR, PasswordHash VARCHAR, CreatedAt TIMESTAMPTZ) AS $$
+RETURNS TABLE (Id INT, Username VARCHAR, Email VARCHAR, PasswordHash VARCHAR, CreatedAt TIMESTAMPTZ, TokenVersion INT, EmailVerified BOOLEAN, TotpEnabled BOOLEAN, Suspended BOOLEAN) AS $$
 BEGIN
     RETURN QUERY
-    SELECT Id, Username, Email, PasswordHash, CreatedAt
-    FROM Users
-    WHERE Email = p_email;
+    SELECT u.Id, u.Username, u.Email, u.PasswordHash, u.CreatedAt, u.TokenVersion, u.EmailVerified, u.TotpEnabled,
+           u.SuspendedAt IS NOT NULL
+    FROM Users u
+    WHERE u.Email = p_email;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Get User Credentials by ID (same columns as sp_login_user)
+DROP FUNCTION IF EXISTS sp_get_user_credentials(INT);
+CREATE OR REPLACE FUNCTION sp_get_user_credentials(p_user_id INT)
+RETURNS TABLE (Id INT, Username VARCHAR, Email VARCHAR, PasswordHash VARCHAR, CreatedAt TIMESTAMPTZ, TokenVersion INT, EmailVerified BOOLEAN, TotpEnabled BOOLEAN, Suspended BOOLEAN) AS $$
+BEGIN
+    RETURN QUERY
+    SELECT u.Id, u.Username, u.Email, u.PasswordHash, u.CreatedAt, u.TokenVersion, u.EmailVerified, u.TotpEnabled,
+           u.SuspendedAt IS NOT NULL
+    FROM Users u
+    WHERE u.Id = p_user_id;
 END;
 $$ LANGUAGE plpgsql;
 
 -- Get User by ID
+DROP FUNCTION IF EXISTS sp_get_user_by_id(INT);
 CREATE OR REPLACE FUNCTION sp_get_user_by_id(p_user_id INT)
-RETURNS TABLE (Id INT, Username VARCHAR, Email VARCHAR, CreatedAt TIMESTAMPTZ) AS $$
+RETURNS TABLE (
+    Id INT,
+    Username VARCHAR,
+    Email VARCHAR,
+    EmailVerified BOOLEAN,
+    DisplayName VARCHAR,
+    Bio VARCHAR,
+    Locale VARCHAR,
+    Timezone VARCHAR,
+    AvatarVersion UUID,
+    CreatedAt TIMESTAMPTZ,
+    UpdatedAt TIMESTAMPTZ
+) AS $$
 BEGIN
     RETURN QUERY
-    SELECT Id, Username, Email, CreatedAt
-    FROM Users
-    WHERE Id = p_user_id;
+    SELECT u.Id, u.Username, u.Email, u.EmailVerified, u.DisplayName, u.Bio, u.Locale, u.Timezone,
+           u.AvatarVersion, u.CreatedAt, u.UpdatedAt
+    FROM Users u
+    WHERE u.Id = p_user_id;
 END;
 $$ LANGUAGE plpgsql;
 
@@ -60,75 +89,284 @@ BEGIN
 END;
 $$ LANGUAGE plpgsql;
 
+-- Get Notebook Tree
+-- IDs of the user's notebook and every notebook nested below it; empty when the user has no such notebook
+CREATE OR REPLACE FUNCTION sp_get_notebook_tree(p_notebook_id INT, p_user_id INT)
+RETURNS INT[] AS $$
+BEGIN
+    RETURN ARRAY(
+        WITH RECURSIVE tree AS (
+            SELECT nb.Id
+            FROM Notebooks nb
+            WHERE nb.Id = p_notebook_id AND nb.UserId = p_user_id
+            UNION ALL
+            SELECT child.Id
+            FROM Notebooks child
+            JOIN tree ON child.ParentId = tree.Id
+        )
+        SELECT tree.Id FROM tree
+    );
+END;
+$$ LANGUAGE plpgsql;
+
+-- Set Note Tags
+-- Replaces the note's tags with p_tags, creating the user's tags that do not exist yet.
+-- Names match existing tags regardless of case. Callers check that the note belongs to the user.
+CREATE OR REPLACE FUNCTION sp_set_note_tags(p_note_id INT, p_user_id INT, p_tags VARCHAR[])
+RETURNS VOID AS $$
+BEGIN
+    INSERT INTO Tags (UserId, Name)
+    SELECT p_user_id, tag.Name
+    FROM unnest(p_tags) AS tag(Name)
+    ON CONFLICT (UserId, LOWER(Name)) DO NOTHING;
+
+    DELETE FROM NoteTags nt WHERE nt.NoteId = p_note_id;
+
+    INSERT INTO NoteTags (NoteId, TagId)
+    SELECT p_note_id, t.Id
+    FROM Tags t
+    WHERE t.UserId = p_user_id
+    AND LOWER(t.Name) IN (SELECT LOWER(tag.Name) FROM unnest(p_tags) AS tag(Name))
+    ON CONFLICT DO NOTHING;
+END;
+$$ LANGUAGE plpgsql;
+
 -- Create or Update Note
+-- Operation is one of: created, updated, not_found, notebook_not_found.
+-- A NULL p_tags leaves the note's tags as they are; a NULL p_notebook_id puts the note in no notebook.
+DROP FUNCTION IF EXISTS sp_create_or_update_note(INT, VARCHAR, TEXT, INT);
+DROP FUNCTION IF EXISTS sp_create_or_update_note(INT, VARCHAR, TEXT, INT, VARCHAR[]);
 CREATE OR REPLACE FUNCTION sp_create_or_update_note(
     p_note_id INT,
     p_title VARCHAR,
     p_content TEXT,
-    p_user_id INT
+    p_user_id INT,
+    p_tags VARCHAR[] DEFAULT NULL,
+    p_notebook_id INT DEFAULT NULL
 )
 RETURNS TABLE (NoteId INT, Operation TEXT) AS $$
+DECLARE
+    v_note_id INT;
 BEGIN
+    IF p_notebook_id IS NOT NULL AND NOT EXISTS (
+        SELECT 1 FROM Notebooks nb WHERE nb.Id = p_notebook_id AND nb.UserId = p_user_id
+    ) THEN
+        RETURN QUERY SELECT NULL::INT, 'notebook_not_found'::TEXT;
+        RETURN;
+    END IF;
+
     IF p_note_id IS NULL OR p_note_id = 0 THEN
-        INSERT INTO Notes (Title, Content, UserId, CreatedAt)
-        VALUES (p_title, p_content, p_user_id,
+        INSERT INTO Notes (Title, Content, UserId, NotebookId, CreatedAt)
+        VALUES (p_title, p_content, p_user_id, p_notebook_id,
                 CURRENT_TIMESTAMP AT TIME ZONE 'Asia/Bangkok')
-        RETURNING Id AS NoteId, 'created'::TEXT AS Operation;
+        RETURNING Id INTO v_note_id;
+
+        IF p_tags IS NOT NULL THEN
+            PERFORM sp_set_note_tags(v_note_id, p_user_id, p_tags);
+        END IF;
+
+        RETURN QUERY SELECT v_note_id, 'created'::TEXT;
     ELSE
-        UPDATE Notes
+        UPDATE Notes n
         SET Title = p_title,
             Content = p_content,
+            NotebookId = p_notebook_id,
             UpdatedAt = CURRENT_TIMESTAMP AT TIME ZONE 'Asia/Bangkok'
-        WHERE Id = p_note_id AND UserId = p_user_id;
+        WHERE n.Id = p_note_id AND n.UserId = p_user_id;
 
         IF FOUND THEN
-            RETURN QUERY SELECT p_note_id, 'updated';
+            IF p_tags IS NOT NULL THEN
+                PERFORM sp_set_note_tags(p_note_id, p_user_id, p_tags);
+            END IF;
+
+            RETURN QUERY SELECT p_note_id, 'updated'::TEXT;
         ELSE
-            RETURN QUERY SELECT NULL::INT, 'not_found';
+            RETURN QUERY SELECT NULL::INT, 'not_found'::TEXT;
         END IF;
     END IF;
 END;
 $$ LANGUAGE plpgsql;
 
 -- Get User Notes
-CREATE OR REPLACE FUNCTION sp_get_user_notes(p_user_id INT)
-RETURNS TABLE (Id INT, Title VARCHAR, Content TEXT, CreatedAt TIMESTAMPTZ, UpdatedAt TIMESTAMPTZ) AS $$
+-- Sorted by title, CreatedAt or UpdatedAt (p_sort: title, created_at or updated_at), ties broken by Id.
+-- Pages continue after the sort value and Id of the previous page's last note; a NULL limit
+-- returns every remaining note. Date ranges include their start and exclude their end, and
+-- content lengths count characters. p_tags holds lowercase tag names; with p_match_all a note
+-- needs every one of them, otherwise any one. p_notebook_id limits the notes to one notebook,
+-- and with p_include_sub_notebooks also to the notebooks nested below it.
+DROP FUNCTION IF EXISTS sp_get_user_notes(INT);
+DROP FUNCTION IF EXISTS sp_get_user_notes(INT, INT, TIMESTAMPTZ, INT);
+DROP FUNCTION IF EXISTS sp_get_user_notes(INT, INT, VARCHAR, BOOLEAN, TEXT, INT, TIMESTAMPTZ, TIMESTAMPTZ, TIMESTAMPTZ, TIMESTAMPTZ, INT, INT);
+DROP FUNCTION IF EXISTS sp_get_user_notes(INT, INT, VARCHAR, BOOLEAN, TEXT, INT, TIMESTAMPTZ, TIMESTAMPTZ, TIMESTAMPTZ, TIMESTAMPTZ, INT, INT, VARCHAR[], BOOLEAN);
+DROP FUNCTION IF EXISTS sp_get_user_notes(INT, INT, VARCHAR, BOOLEAN, TEXT, INT, TIMESTAMPTZ, TIMESTAMPTZ, TIMESTAMPTZ, TIMESTAMPTZ, INT, INT, VARCHAR[], BOOLEAN, INT, BOOLEAN);
+CREATE OR REPLACE FUNCTION sp_get_user_notes(
+    p_user_id INT,
+    p_limit INT DEFAULT NULL,
+    p_sort VARCHAR DEFAULT 'updated_at',
+    p_descending BOOLEAN DEFAULT TRUE,
+    p_after_value TEXT DEFAULT NULL,
+    p_after_id INT DEFAULT NULL,
+    p_created_from TIMESTAMPTZ DEFAULT NULL,
+    p_created_to TIMESTAMPTZ DEFAULT NULL,
+    p_updated_from TIMESTAMPTZ DEFAULT NULL,
+    p_updated_to TIMESTAMPTZ DEFAULT NULL,
+    p_min_length INT DEFAULT NULL,
+    p_max_length INT DEFAULT NULL,
+    p_tags VARCHAR[] DEFAULT NULL,
+    p_match_all BOOLEAN DEFAULT TRUE,
+    p_notebook_id INT DEFAULT NULL,
+    p_include_sub_notebooks BOOLEAN DEFAULT FALSE
+)
+RETURNS TABLE (
+    Id INT, Title VARCHAR, Content TEXT, CreatedAt TIMESTAMPTZ, UpdatedAt TIMESTAMPTZ, Tags VARCHAR[],
+    NotebookId INT, OwnerId INT, Permission VARCHAR
+) AS $$
+DECLARE
+    v_sort_column TEXT;
+    v_sort_type TEXT;
+    v_notebook_ids INT[];
 BEGIN
-    RETURN QUERY
-    SELECT Id, Title, Content, CreatedAt, UpdatedAt
-    FROM Notes
-    WHERE UserId = p_user_id
-    ORDER BY UpdatedAt DESC;
+    -- Notes are stamped with Asia/Bangkok wall-clock time, so move the bounds onto the same clock
+    p_created_from := (p_created_from AT TIME ZONE 'Asia/Bangkok')::TIMESTAMPTZ;
+    p_created_to := (p_created_to AT TIME ZONE 'Asia/Bangkok')::TIMESTAMPTZ;
+    p_updated_from := (p_updated_from AT TIME ZONE 'Asia/Bangkok')::TIMESTAMPTZ;
+    p_updated_to := (p_updated_to AT TIME ZONE 'Asia/Bangkok')::TIMESTAMPTZ;
+
+    IF p_notebook_id IS NOT NULL THEN
+        v_notebook_ids := CASE WHEN p_include_sub_notebooks
+                               THEN sp_get_notebook_tree(p_notebook_id, p_user_id)
+                               ELSE ARRAY[p_notebook_id] END;
+    END IF;
+
+    -- Only these fixed column names are ever spliced into the query; every value is a parameter
+    CASE p_sort
+        WHEN 'title' THEN v_sort_column := 'n.Title'; v_sort_type := 'VARCHAR';
+        WHEN 'created_at' THEN v_sort_column := 'n.CreatedAt'; v_sort_type := 'TIMESTAMPTZ';
+        WHEN 'updated_at' THEN v_sort_column := 'n.UpdatedAt'; v_sort_type := 'TIMESTAMPTZ';
+        ELSE RAISE EXCEPTION 'Unknown note sort: %', p_sort;
+    END CASE;
+
+    RETURN QUERY EXECUTE format(
+        'SELECT n.Id, n.Title, n.Content, n.CreatedAt, n.UpdatedAt,
+                ARRAY(SELECT t.Name FROM NoteTags nt JOIN Tags t ON t.Id = nt.TagId
+                      WHERE nt.NoteId = n.Id ORDER BY t.Name)::VARCHAR[],
+                n.NotebookId, n.UserId, ''owner''::VARCHAR
+         FROM Notes n
+         WHERE n.UserId = $1
+         AND ($2::TIMESTAMPTZ IS NULL OR n.CreatedAt >= $2)
+         AND ($3::TIMESTAMPTZ IS NULL OR n.CreatedAt < $3)
+         AND ($4::TIMESTAMPTZ IS NULL OR n.UpdatedAt >= $4)
+         AND ($5::TIMESTAMPTZ IS NULL OR n.UpdatedAt < $5)
+         AND ($6::INT IS NULL OR char_length(COALESCE(n.Content, '''')) >= $6)
+         AND ($7::INT IS NULL OR char_length(COALESCE(n.Content, '''')) <= $7)
+         AND ($9::INT IS NULL OR (%1$s, n.Id) %2$s ($8::%3$s, $9))
+         AND ($11::VARCHAR[] IS NULL OR (
+             SELECT COUNT(*) FROM NoteTags nt JOIN Tags t ON t.Id = nt.TagId
+             WHERE nt.NoteId = n.Id AND LOWER(t.Name) = ANY($11)
+         ) >= CASE WHEN $12 THEN cardinality($11) ELSE 1 END)
+         AND ($13::INT[] IS NULL OR n.NotebookId = ANY($13))
+         ORDER BY %1$s %4$s, n.Id %4$s
+         LIMIT $10',
+        v_sort_column,
+        CASE WHEN p_descending THEN '<' ELSE '>' END,
+        v_sort_type,
+        CASE WHEN p_descending THEN 'DESC' ELSE 'ASC' END
+    )
+    USING p_user_id, p_created_from, p_created_to, p_updated_from, p_updated_to,
+          p_min_length, p_max_length, p_after_value, p_after_id, p_limit, p_tags, p_match_all,
+          v_notebook_ids;
 END;
 $$ LANGUAGE plpgsql;
 
 -- Get Note by ID
+-- The note if the user owns it or it was shared with them. Permission is one of: owner, write,
+-- read. Notebooks are private to the owner, so collaborators see no NotebookId.
+DROP FUNCTION IF EXISTS sp_get_note_by_id(INT, INT);
 CREATE OR REPLACE FUNCTION sp_get_note_by_id(p_note_id INT, p_user_id INT)
-RETURNS TABLE (Id INT, Title VARCHAR, Content TEXT, CreatedAt TIMESTAMPTZ, UpdatedAt TIMESTAMPTZ) AS $$
+RETURNS TABLE (
+    Id INT, Title VARCHAR, Content TEXT, CreatedAt TIMESTAMPTZ, UpdatedAt TIMESTAMPTZ, Tags VARCHAR[],
+    NotebookId INT, OwnerId INT, Permission VARCHAR
+) AS $$
 BEGIN
     RETURN QUERY
-    SELECT Id, Title, Content, CreatedAt, UpdatedAt
-    FROM Notes
-    WHERE Id = p_note_id AND UserId = p_user_id;
+    SELECT n.Id, n.Title, n.Content, n.CreatedAt, n.UpdatedAt,
+           ARRAY(SELECT t.Name FROM NoteTags nt JOIN Tags t ON t.Id = nt.TagId
+                 WHERE nt.NoteId = n.Id ORDER BY t.Name)::VARCHAR[],
+           CASE WHEN n.UserId = p_user_id THEN n.NotebookId END,
+           n.UserId,
+           CASE WHEN n.UserId = p_user_id THEN 'owner' ELSE ns.Permission END::VARCHAR
+    FROM Notes n
+    LEFT JOIN NoteShares ns ON ns.NoteId = n.Id AND ns.UserId = p_user_id
+    WHERE n.Id = p_note_id
+    AND (n.UserId = p_user_id OR ns.UserId IS NOT NULL);
 END;
 $$ LANGUAGE plpgsql;
 
 -- Update Note
+-- Returns 1 if updated, 0 if the note was not found, -1 if the notebook was not found, -2 if
+-- the note was only shared with the user for reading and -3 if a collaborator tried to change
+-- the tags or notebook.
+-- A NULL p_tags leaves the note's tags as they are. The note moves to p_notebook_id only with
+-- p_set_notebook, where a NULL p_notebook_id takes it out of its notebook; otherwise it stays put.
+-- Collaborators with write access may change only the title and content; tags and notebook are the owner's.
+DROP FUNCTION IF EXISTS sp_update_note(INT, VARCHAR, TEXT, INT);
+DROP FUNCTION IF EXISTS sp_update_note(INT, VARCHAR, TEXT, INT, VARCHAR[]);
+DROP FUNCTION IF EXISTS sp_update_note(INT, VARCHAR, TEXT, INT, VARCHAR[], INT);
 CREATE OR REPLACE FUNCTION sp_update_note(
     p_note_id INT,
     p_title VARCHAR,
     p_content TEXT,
-    p_user_id INT
+    p_user_id INT,
+    p_tags VARCHAR[] DEFAULT NULL,
+    p_notebook_id INT DEFAULT NULL,
+    p_set_notebook BOOLEAN DEFAULT FALSE
 )
 RETURNS INTEGER AS $$
+DECLARE
+    v_permission VARCHAR;
 BEGIN
-    UPDATE Notes
+    SELECT ns.Permission INTO v_permission
+    FROM NoteShares ns
+    WHERE ns.NoteId = p_note_id AND ns.UserId = p_user_id;
+
+    IF v_permission = 'read' THEN
+        RETURN -2;
+    ELSIF v_permission = 'write' THEN
+        IF p_tags IS NOT NULL OR p_set_notebook THEN
+            RETURN -3;
+        END IF;
+
+        UPDATE Notes n
+        SET Title = p_title,
+            Content = p_content,
+            UpdatedAt = CURRENT_TIMESTAMP AT TIME ZONE 'Asia/Bangkok'
+        WHERE n.Id = p_note_id;
+
+        RETURN FOUND::INT;
+    END IF;
+
+    IF p_set_notebook AND p_notebook_id IS NOT NULL AND NOT EXISTS (
+        SELECT 1 FROM Notebooks nb WHERE nb.Id = p_notebook_id AND nb.UserId = p_user_id
+    ) THEN
+        RETURN -1;
+    END IF;
+
+    UPDATE Notes n
     SET Title = p_title,
         Content = p_content,
+        NotebookId = CASE WHEN p_set_notebook THEN p_notebook_id ELSE n.NotebookId END,
         UpdatedAt = CURRENT_TIMESTAMP AT TIME ZONE 'Asia/Bangkok'
-    WHERE Id = p_note_id AND UserId = p_user_id;
+    WHERE n.Id = p_note_id AND n.UserId = p_user_id;
+
+    IF NOT FOUND THEN
+        RETURN 0;
+    END IF;
 
-    RETURN FOUND::INT;  -- 1 if updated, 0 if not
+    IF p_tags IS NOT NULL THEN
+        PERFORM sp_set_note_tags(p_note_id, p_user_id, p_tags);
+    END IF;
+
+    RETURN 1;
 END;
 $$ LANGUAGE plpgsql;
 
@@ -144,18 +382,1812 @@ END;
 $$ LANGUAGE plpgsql;
 
 -- Search Notes
-CREATE OR REPLACE FUNCTION sp_search_notes(p_user_id INT, p_search_term VARCHAR DEFAULT NULL)
-RETURNS TABLE (Id INT, Title VARCHAR, Content TEXT, CreatedAt TIMESTAMPTZ, UpdatedAt TIMESTAMPTZ) AS $$
+-- Searches the user's own notes and the notes shared with them, paged like sp_get_user_notes.
+-- A notebook scope only covers the user's own notes.
+DROP FUNCTION IF EXISTS sp_search_notes(INT, VARCHAR);
+DROP FUNCTION IF EXISTS sp_search_notes(INT, VARCHAR, INT, TIMESTAMPTZ, INT);
+DROP FUNCTION IF EXISTS sp_search_notes(INT, VARCHAR, INT, TIMESTAMPTZ, INT, INT, BOOLEAN);
+CREATE OR REPLACE FUNCTION sp_search_notes(
+    p_user_id INT,
+    p_search_term VARCHAR DEFAULT NULL,
+    p_limit INT DEFAULT NULL,
+    p_after_updated_at TIMESTAMPTZ DEFAULT NULL,
+    p_after_id INT DEFAULT NULL,
+    p_notebook_id INT DEFAULT NULL,
+    p_include_sub_notebooks BOOLEAN DEFAULT FALSE
+)
+RETURNS TABLE (
+    Id INT, Title VARCHAR, Content TEXT, CreatedAt TIMESTAMPTZ, UpdatedAt TIMESTAMPTZ, Tags VARCHAR[],
+    NotebookId INT, OwnerId INT, Permission VARCHAR
+) AS $$
+DECLARE
+    v_notebook_ids INT[];
 BEGIN
+    IF p_notebook_id IS NOT NULL THEN
+        v_notebook_ids := CASE WHEN p_include_sub_notebooks
+                               THEN sp_get_notebook_tree(p_notebook_id, p_user_id)
+                               ELSE ARRAY[p_notebook_id] END;
+    END IF;
+
     RETURN QUERY
-    SELECT Id, Title, Content, CreatedAt, UpdatedAt
-    FROM Notes
-    WHERE UserId = p_user_id
+    SELECT n.Id, n.Title, n.Content, n.CreatedAt, n.UpdatedAt,
+           ARRAY(SELECT t.Name FROM NoteTags nt JOIN Tags t ON t.Id = nt.TagId
+                 WHERE nt.NoteId = n.Id ORDER BY t.Name)::VARCHAR[],
+           CASE WHEN n.UserId = p_user_id THEN n.NotebookId END,
+           n.UserId,
+           CASE WHEN n.UserId = p_user_id THEN 'owner' ELSE ns.Permission END::VARCHAR
+    FROM Notes n
+    LEFT JOIN NoteShares ns ON ns.NoteId = n.Id AND ns.UserId = p_user_id
+    WHERE (n.UserId = p_user_id OR ns.UserId IS NOT NULL)
     AND (
         p_search_term IS NULL OR
-        Title ILIKE '%' || p_search_term || '%' OR
-        Content ILIKE '%' || p_search_term || '%'
+        n.Title ILIKE '%' || p_search_term || '%' OR
+        n.Content ILIKE '%' || p_search_term || '%'
     )
-    ORDER BY UpdatedAt DESC;
+    AND (p_after_id IS NULL OR (n.UpdatedAt, n.Id) < (p_after_updated_at, p_after_id))
+    AND (v_notebook_ids IS NULL OR (n.UserId = p_user_id AND n.NotebookId = ANY(v_notebook_ids)))
+    ORDER BY n.UpdatedAt DESC, n.Id DESC
+    LIMIT p_limit;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Count User Notes
+-- Number of notes across all pages of sp_get_user_notes or sp_search_notes with the same filters;
+-- p_include_shared also counts notes shared with the user, as search does
+DROP FUNCTION IF EXISTS sp_count_user_notes(INT, VARCHAR);
+DROP FUNCTION IF EXISTS sp_count_user_notes(INT, VARCHAR, TIMESTAMPTZ, TIMESTAMPTZ, TIMESTAMPTZ, TIMESTAMPTZ, INT, INT);
+DROP FUNCTION IF EXISTS sp_count_user_notes(INT, VARCHAR, TIMESTAMPTZ, TIMESTAMPTZ, TIMESTAMPTZ, TIMESTAMPTZ, INT, INT, VARCHAR[], BOOLEAN);
+DROP FUNCTION IF EXISTS sp_count_user_notes(INT, VARCHAR, TIMESTAMPTZ, TIMESTAMPTZ, TIMESTAMPTZ, TIMESTAMPTZ, INT, INT, VARCHAR[], BOOLEAN, INT, BOOLEAN);
+CREATE OR REPLACE FUNCTION sp_count_user_notes(
+    p_user_id INT,
+    p_search_term VARCHAR DEFAULT NULL,
+    p_created_from TIMESTAMPTZ DEFAULT NULL,
+    p_created_to TIMESTAMPTZ DEFAULT NULL,
+    p_updated_from TIMESTAMPTZ DEFAULT NULL,
+    p_updated_to TIMESTAMPTZ DEFAULT NULL,
+    p_min_length INT DEFAULT NULL,
+    p_max_length INT DEFAULT NULL,
+    p_tags VARCHAR[] DEFAULT NULL,
+    p_match_all BOOLEAN DEFAULT TRUE,
+    p_notebook_id INT DEFAULT NULL,
+    p_include_sub_notebooks BOOLEAN DEFAULT FALSE,
+    p_include_shared BOOLEAN DEFAULT FALSE
+)
+RETURNS BIGINT AS $$
+DECLARE
+    v_notebook_ids INT[];
+BEGIN
+    -- Notes are stamped with Asia/Bangkok wall-clock time, so move the bounds onto the same clock
+    p_created_from := (p_created_from AT TIME ZONE 'Asia/Bangkok')::TIMESTAMPTZ;
+    p_created_to := (p_created_to AT TIME ZONE 'Asia/Bangkok')::TIMESTAMPTZ;
+    p_updated_from := (p_updated_from AT TIME ZONE 'Asia/Bangkok')::TIMESTAMPTZ;
+    p_updated_to := (p_updated_to AT TIME ZONE 'Asia/Bangkok')::TIMESTAMPTZ;
+
+    IF p_notebook_id IS NOT NULL THEN
+        v_notebook_ids := CASE WHEN p_include_sub_notebooks
+                               THEN sp_get_notebook_tree(p_notebook_id, p_user_id)
+                               ELSE ARRAY[p_notebook_id] END;
+    END IF;
+
+    RETURN (
+        SELECT COUNT(*)
+        FROM Notes n
+        WHERE (n.UserId = p_user_id OR (p_include_shared AND EXISTS (
+            SELECT 1 FROM NoteShares ns WHERE ns.NoteId = n.Id AND ns.UserId = p_user_id
+        )))
+        AND (
+            p_search_term IS NULL OR
+            n.Title ILIKE '%' || p_search_term || '%' OR
+            n.Content ILIKE '%' || p_search_term || '%'
+        )
+        AND (p_created_from IS NULL OR n.CreatedAt >= p_created_from)
+        AND (p_created_to IS NULL OR n.CreatedAt < p_created_to)
+        AND (p_updated_from IS NULL OR n.UpdatedAt >= p_updated_from)
+        AND (p_updated_to IS NULL OR n.UpdatedAt < p_updated_to)
+        AND (p_min_length IS NULL OR char_length(COALESCE(n.Content, '')) >= p_min_length)
+        AND (p_max_length IS NULL OR char_length(COALESCE(n.Content, '')) <= p_max_length)
+        AND (p_tags IS NULL OR (
+            SELECT COUNT(*) FROM NoteTags nt JOIN Tags t ON t.Id = nt.TagId
+            WHERE nt.NoteId = n.Id AND LOWER(t.Name) = ANY(p_tags)
+        ) >= CASE WHEN p_match_all THEN cardinality(p_tags) ELSE 1 END)
+        AND (v_notebook_ids IS NULL OR (n.UserId = p_user_id AND n.NotebookId = ANY(v_notebook_ids)))
+    );
+END;
+$$ LANGUAGE plpgsql;
+
+-- Refresh token families are started by sp_create_session
+DROP FUNCTION IF EXISTS sp_create_refresh_token(INT, VARCHAR, INT);
+
+-- Rotate Refresh Token
+-- Status is one of: rotated, expired, reused, revoked (session signed out), suspended, invalid.
+-- Presenting a token that was already rotated or revoked revokes its whole family.
+-- A successful rotation records the client address and activity on the session.
+DROP FUNCTION IF EXISTS sp_rotate_refresh_token(VARCHAR, VARCHAR, INT);
+CREATE OR REPLACE FUNCTION sp_rotate_refresh_token(
+    p_token_hash VARCHAR,
+    p_new_token_hash VARCHAR,
+    p_ttl_seconds INT,
+    p_ip_address INET
+)
+RETURNS TABLE (UserId INT, TokenVersion INT, SessionId UUID, Status TEXT) AS $$
+DECLARE
+    v_token RefreshTokens%ROWTYPE;
+    v_token_version INT;
+BEGIN
+    SELECT rt.* INTO v_token
+    FROM RefreshTokens rt
+    WHERE rt.TokenHash = p_token_hash
+    FOR UPDATE;
+
+    IF NOT FOUND THEN
+        RETURN QUERY SELECT NULL::INT, NULL::INT, NULL::UUID, 'invalid'::TEXT;
+        RETURN;
+    END IF;
+
+    IF EXISTS (SELECT 1 FROM Sessions s WHERE s.Id = v_token.FamilyId AND s.RevokedAt IS NOT NULL) THEN
+        RETURN QUERY SELECT v_token.UserId, NULL::INT, NULL::UUID, 'revoked'::TEXT;
+        RETURN;
+    END IF;
+
+    IF EXISTS (SELECT 1 FROM Users u WHERE u.Id = v_token.UserId AND u.SuspendedAt IS NOT NULL) THEN
+        RETURN QUERY SELECT v_token.UserId, NULL::INT, NULL::UUID, 'suspended'::TEXT;
+        RETURN;
+    END IF;
+
+    IF v_token.RevokedAt IS NOT NULL THEN
+        UPDATE RefreshTokens rt
+        SET RevokedAt = CURRENT_TIMESTAMP
+        WHERE rt.FamilyId = v_token.FamilyId AND rt.RevokedAt IS NULL;
+
+        RETURN QUERY SELECT v_token.UserId, NULL::INT, NULL::UUID, 'reused'::TEXT;
+        RETURN;
+    END IF;
+
+    IF v_token.ExpiresAt <= CURRENT_TIMESTAMP THEN
+        RETURN QUERY SELECT v_token.UserId, NULL::INT, NULL::UUID, 'expired'::TEXT;
+        RETURN;
+    END IF;
+
+    UPDATE RefreshTokens rt
+    SET RevokedAt = CURRENT_TIMESTAMP
+    WHERE rt.Id = v_token.Id;
+
+    INSERT INTO RefreshTokens (UserId, FamilyId, TokenHash, ExpiresAt)
+    VALUES (v_token.UserId, v_token.FamilyId, p_new_token_hash,
+            CURRENT_TIMESTAMP + make_interval(secs => p_ttl_seconds));
+
+    -- Token families issued before sessions existed get their session on the next rotation
+    INSERT INTO Sessions (Id, UserId, IpAddress)
+    VALUES (v_token.FamilyId, v_token.UserId, p_ip_address)
+    ON CONFLICT (Id) DO UPDATE
+    SET IpAddress = EXCLUDED.IpAddress,
+        LastSeenAt = CURRENT_TIMESTAMP;
+
+    SELECT u.TokenVersion INTO v_token_version
+    FROM Users u
+    WHERE u.Id = v_token.UserId;
+
+    RETURN QUERY SELECT v_token.UserId, v_token_version, v_token.FamilyId, 'rotated'::TEXT;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Revoke Refresh Token (revokes the whole family the token belongs to)
+CREATE OR REPLACE FUNCTION sp_revoke_refresh_token(p_token_hash VARCHAR, p_user_id INT)
+RETURNS INTEGER AS $$
+BEGIN
+    UPDATE RefreshTokens rt
+    SET RevokedAt = CURRENT_TIMESTAMP
+    WHERE rt.UserId = p_user_id
+    AND rt.RevokedAt IS NULL
+    AND rt.FamilyId = (
+        SELECT r.FamilyId FROM RefreshTokens r WHERE r.TokenHash = p_token_hash
+    );
+
+    RETURN FOUND::INT;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Revoke Access Token (by jti); expired entries are purged along the way
+CREATE OR REPLACE FUNCTION sp_revoke_access_token(
+    p_jti VARCHAR,
+    p_user_id INT,
+    p_expires_at TIMESTAMPTZ
+)
+RETURNS INTEGER AS $$
+BEGIN
+    DELETE FROM RevokedTokens rt
+    WHERE rt.ExpiresAt < CURRENT_TIMESTAMP;
+
+    INSERT INTO RevokedTokens (Jti, UserId, ExpiresAt)
+    VALUES (p_jti, p_user_id, p_expires_at)
+    ON CONFLICT (Jti) DO NOTHING;
+
+    RETURN 1;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Revoke every outstanding token of a user
+-- Bumps the token version (invalidating all access tokens) and revokes all refresh tokens, sessions
+-- and personal access tokens
+CREATE OR REPLACE FUNCTION sp_revoke_user_tokens(p_user_id INT)
+RETURNS INTEGER AS $$
+DECLARE
+    new_token_version INTEGER;
+BEGIN
+    UPDATE Users u
+    SET TokenVersion = u.TokenVersion + 1,
+        UpdatedAt = CURRENT_TIMESTAMP AT TIME ZONE 'Asia/Bangkok'
+    WHERE u.Id = p_user_id
+    RETURNING u.TokenVersion INTO new_token_version;
+
+    UPDATE RefreshTokens rt
+    SET RevokedAt = CURRENT_TIMESTAMP
+    WHERE rt.UserId = p_user_id AND rt.RevokedAt IS NULL;
+
+    UPDATE Sessions s
+    SET RevokedAt = CURRENT_TIMESTAMP
+    WHERE s.UserId = p_user_id AND s.RevokedAt IS NULL;
+
+    UPDATE PersonalAccessTokens pat
+    SET RevokedAt = CURRENT_TIMESTAMP
+    WHERE pat.UserId = p_user_id AND pat.RevokedAt IS NULL;
+
+    RETURN new_token_version;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Get Access Token Status
+-- Status is one of: valid, revoked, session_revoked, stale (token version bumped), suspended, unknown_user.
+-- Valid tokens refresh the last-seen time of their session (at most once a minute).
+DROP FUNCTION IF EXISTS sp_get_access_token_status(VARCHAR, INT, INT);
+CREATE OR REPLACE FUNCTION sp_get_access_token_status(
+    p_jti VARCHAR,
+    p_user_id INT,
+    p_token_version INT,
+    p_session_id UUID
+)
+RETURNS TABLE (Status TEXT, EmailVerified BOOLEAN) AS $$
+DECLARE
+    v_token_version INTEGER;
+    v_email_verified BOOLEAN;
+    v_suspended_at TIMESTAMPTZ;
+BEGIN
+    SELECT u.TokenVersion, u.EmailVerified, u.SuspendedAt INTO v_token_version, v_email_verified, v_suspended_at
+    FROM Users u
+    WHERE u.Id = p_user_id;
+
+    IF NOT FOUND THEN
+        RETURN QUERY SELECT 'unknown_user'::TEXT, NULL::BOOLEAN;
+        RETURN;
+    END IF;
+
+    IF v_suspended_at IS NOT NULL THEN
+        RETURN QUERY SELECT 'suspended'::TEXT, v_email_verified;
+        RETURN;
+    END IF;
+
+    IF v_token_version <> p_token_version THEN
+        RETURN QUERY SELECT 'stale'::TEXT, v_email_verified;
+        RETURN;
+    END IF;
+
+    IF EXISTS (SELECT 1 FROM RevokedTokens rt WHERE rt.Jti = p_jti) THEN
+        RETURN QUERY SELECT 'revoked'::TEXT, v_email_verified;
+        RETURN;
+    END IF;
+
+    IF EXISTS (SELECT 1 FROM Sessions s WHERE s.Id = p_session_id AND s.RevokedAt IS NOT NULL) THEN
+        RETURN QUERY SELECT 'session_revoked'::TEXT, v_email_verified;
+        RETURN;
+    END IF;
+
+    UPDATE Sessions s
+    SET LastSeenAt = CURRENT_TIMESTAMP
+    WHERE s.Id = p_session_id
+    AND s.LastSeenAt < CURRENT_TIMESTAMP - INTERVAL '1 minute';
+
+    RETURN QUERY SELECT 'valid'::TEXT, v_email_verified;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Create Password Reset Token
+-- Returns no row when the email is unknown; earlier unused tokens of the user are discarded
+CREATE OR REPLACE FUNCTION sp_create_password_reset_token(
+    p_email VARCHAR,
+    p_token_hash VARCHAR,
+    p_ttl_seconds INT
+)
+RETURNS TABLE (UserId INT, Username VARCHAR) AS $$
+DECLARE
+    v_user_id INTEGER;
+    v_username VARCHAR;
+BEGIN
+    SELECT u.Id, u.Username INTO v_user_id, v_username
+    FROM Users u
+    WHERE u.Email = p_email;
+
+    IF NOT FOUND THEN
+        RETURN;
+    END IF;
+
+    DELETE FROM PasswordResetTokens prt
+    WHERE prt.UserId = v_user_id AND prt.UsedAt IS NULL;
+
+    INSERT INTO PasswordResetTokens (UserId, TokenHash, ExpiresAt)
+    VALUES (v_user_id, p_token_hash,
+            CURRENT_TIMESTAMP + make_interval(secs => p_ttl_seconds));
+
+    RETURN QUERY SELECT v_user_id, v_username;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Reset Password
+-- Consumes a valid reset token, stores the new password hash and revokes every
+-- outstanding token of the user. Returns the user ID, or NULL for an invalid token.
+CREATE OR REPLACE FUNCTION sp_reset_password(p_token_hash VARCHAR, p_password_hash VARCHAR)
+RETURNS INTEGER AS $$
+DECLARE
+    v_user_id INTEGER;
+BEGIN
+    UPDATE PasswordResetTokens prt
+    SET UsedAt = CURRENT_TIMESTAMP
+    WHERE prt.TokenHash = p_token_hash
+    AND prt.UsedAt IS NULL
+    AND prt.ExpiresAt > CURRENT_TIMESTAMP
+    RETURNING prt.UserId INTO v_user_id;
+
+    IF v_user_id IS NULL THEN
+        RETURN NULL;
+    END IF;
+
+    UPDATE Users u
+    SET PasswordHash = p_password_hash,
+        UpdatedAt = CURRENT_TIMESTAMP AT TIME ZONE 'Asia/Bangkok'
+    WHERE u.Id = v_user_id;
+
+    PERFORM sp_revoke_user_tokens(v_user_id);
+
+    RETURN v_user_id;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Create Email Verification Token
+-- Email is the address being verified. A token for the current address (registration) replaces
+-- the user's earlier unused registration tokens, one for another address (email change) their
+-- earlier unused email change tokens, so resending one never cancels the other.
+CREATE OR REPLACE FUNCTION sp_create_email_verification_token(
+    p_user_id INT,
+    p_email VARCHAR,
+    p_token_hash VARCHAR,
+    p_ttl_seconds INT
+)
+RETURNS INTEGER AS $$
+DECLARE
+    new_token_id INTEGER;
+BEGIN
+    DELETE FROM EmailVerificationTokens evt
+    USING Users u
+    WHERE u.Id = p_user_id
+    AND evt.UserId = p_user_id
+    AND evt.UsedAt IS NULL
+    AND (evt.Email = u.Email) = (p_email = u.Email);
+
+    INSERT INTO EmailVerificationTokens (UserId, Email, TokenHash, ExpiresAt)
+    VALUES (p_user_id, p_email, p_token_hash,
+            CURRENT_TIMESTAMP + make_interval(secs => p_ttl_seconds))
+    RETURNING Id INTO new_token_id;
+
+    RETURN new_token_id;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Verify Email
+-- Consumes a valid verification token and marks the address as verified. A token for an
+-- address other than the current one comes from an email change, which is applied here.
+-- Returns the user ID, or NULL for an invalid token or a new address taken in the meantime.
+DROP FUNCTION IF EXISTS sp_verify_email(VARCHAR);
+CREATE OR REPLACE FUNCTION sp_verify_email(p_token_hash VARCHAR, p_ip_address INET, p_user_agent VARCHAR)
+RETURNS INTEGER AS $$
+DECLARE
+    v_user_id INTEGER;
+    v_email VARCHAR;
+    v_current_email VARCHAR;
+BEGIN
+    UPDATE EmailVerificationTokens evt
+    SET UsedAt = CURRENT_TIMESTAMP
+    WHERE evt.TokenHash = p_token_hash
+    AND evt.UsedAt IS NULL
+    AND evt.ExpiresAt > CURRENT_TIMESTAMP
+    RETURNING evt.UserId, evt.Email INTO v_user_id, v_email;
+
+    IF v_user_id IS NULL THEN
+        RETURN NULL;
+    END IF;
+
+    SELECT u.Email INTO v_current_email
+    FROM Users u
+    WHERE u.Id = v_user_id
+    FOR UPDATE;
+
+    IF v_current_email IS DISTINCT FROM v_email THEN
+        IF EXISTS (SELECT 1 FROM Users u WHERE u.Email = v_email) THEN
+            RETURN NULL;
+        END IF;
+
+        PERFORM sp_record_auth_event(v_user_id, v_email, 'email_changed', TRUE,
+                                     v_current_email || ' -> ' || v_email, p_ip_address, p_user_agent);
+    ELSE
+        PERFORM sp_record_auth_event(v_user_id, v_email, 'email_verified', TRUE, NULL, p_ip_address, p_user_agent);
+    END IF;
+
+    UPDATE Users u
+    SET Email = v_email,
+        EmailVerified = TRUE,
+        UpdatedAt = CURRENT_TIMESTAMP AT TIME ZONE 'Asia/Bangkok'
+    WHERE u.Id = v_user_id;
+
+    RETURN v_user_id;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Begin TOTP Enrollment
+-- Stores a new, not yet enabled secret. Returns no row when the user is unknown or
+-- already has two-factor authentication enabled.
+CREATE OR REPLACE FUNCTION sp_begin_totp_enrollment(p_user_id INT, p_secret VARCHAR)
+RETURNS TABLE (Username VARCHAR, Email VARCHAR) AS $$
+BEGIN
+    RETURN QUERY
+    UPDATE Users u
+    SET TotpSecret = p_secret,
+        TotpLastUsedStep = NULL,
+        UpdatedAt = CURRENT_TIMESTAMP AT TIME ZONE 'Asia/Bangkok'
+    WHERE u.Id = p_user_id AND u.TotpEnabled = FALSE
+    RETURNING u.Username, u.Email;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Get TOTP State
+CREATE OR REPLACE FUNCTION sp_get_totp_state(p_user_id INT)
+RETURNS TABLE (TotpSecret VARCHAR, TotpEnabled BOOLEAN) AS $$
+BEGIN
+    RETURN QUERY
+    SELECT u.TotpSecret, u.TotpEnabled
+    FROM Users u
+    WHERE u.Id = p_user_id;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Record TOTP Step
+-- Accepts a time step only if it is newer than the last accepted one, so every code is single-use.
+-- Returns FALSE for a replayed code.
+CREATE OR REPLACE FUNCTION sp_record_totp_step(p_user_id INT, p_step BIGINT)
+RETURNS BOOLEAN AS $$
+BEGIN
+    UPDATE Users u
+    SET TotpLastUsedStep = p_step
+    WHERE u.Id = p_user_id
+    AND u.TotpEnabled = TRUE
+    AND (u.TotpLastUsedStep IS NULL OR u.TotpLastUsedStep < p_step);
+
+    RETURN FOUND;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Enable TOTP
+-- Turns on a pending enrollment once its first code was verified and stores a fresh set of recovery codes.
+-- Returns FALSE when there is no pending enrollment.
+CREATE OR REPLACE FUNCTION sp_enable_totp(p_user_id INT, p_step BIGINT, p_code_hashes VARCHAR[])
+RETURNS BOOLEAN AS $$
+BEGIN
+    UPDATE Users u
+    SET TotpEnabled = TRUE,
+        TotpLastUsedStep = p_step,
+        UpdatedAt = CURRENT_TIMESTAMP AT TIME ZONE 'Asia/Bangkok'
+    WHERE u.Id = p_user_id
+    AND u.TotpEnabled = FALSE
+    AND u.TotpSecret IS NOT NULL;
+
+    IF NOT FOUND THEN
+        RETURN FALSE;
+    END IF;
+
+    PERFORM sp_replace_recovery_codes(p_user_id, p_code_hashes);
+
+    RETURN TRUE;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Disable TOTP (removes the secret and every recovery code)
+CREATE OR REPLACE FUNCTION sp_disable_totp(p_user_id INT)
+RETURNS INTEGER AS $$
+BEGIN
+    UPDATE Users u
+    SET TotpSecret = NULL,
+        TotpEnabled = FALSE,
+        TotpLastUsedStep = NULL,
+        UpdatedAt = CURRENT_TIMESTAMP AT TIME ZONE 'Asia/Bangkok'
+    WHERE u.Id = p_user_id;
+
+    DELETE FROM TotpRecoveryCodes trc
+    WHERE trc.UserId = p_user_id;
+
+    RETURN FOUND::INT;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Replace Recovery Codes (invalidates every earlier code of the user)
+CREATE OR REPLACE FUNCTION sp_replace_recovery_codes(p_user_id INT, p_code_hashes VARCHAR[])
+RETURNS INTEGER AS $$
+BEGIN
+    DELETE FROM TotpRecoveryCodes trc
+    WHERE trc.UserId = p_user_id;
+
+    INSERT INTO TotpRecoveryCodes (UserId, CodeHash)
+    SELECT p_user_id, code_hash
+    FROM unnest(p_code_hashes) AS code_hash;
+
+    RETURN array_length(p_code_hashes, 1);
+END;
+$$ LANGUAGE plpgsql;
+
+-- Use Recovery Code
+-- Consumes an unused recovery code. Returns FALSE when the code is unknown or already used.
+CREATE OR REPLACE FUNCTION sp_use_recovery_code(p_user_id INT, p_code_hash VARCHAR)
+RETURNS BOOLEAN AS $$
+BEGIN
+    UPDATE TotpRecoveryCodes trc
+    SET UsedAt = CURRENT_TIMESTAMP
+    WHERE trc.Id = (
+        SELECT r.Id FROM TotpRecoveryCodes r
+        WHERE r.UserId = p_user_id AND r.CodeHash = p_code_hash AND r.UsedAt IS NULL
+        LIMIT 1
+        FOR UPDATE
+    );
+
+    RETURN FOUND;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Get Login Lockout
+-- Returns the seconds until the longest active lockout among p_keys ends, or NULL when none is locked
+CREATE OR REPLACE FUNCTION sp_get_login_lockout(p_keys VARCHAR[])
+RETURNS INTEGER AS $$
+DECLARE
+    v_locked_until TIMESTAMPTZ;
+BEGIN
+    SELECT MAX(la.LockedUntil) INTO v_locked_until
+    FROM LoginAttempts la
+    WHERE la.AttemptKey = ANY(p_keys)
+    AND la.LockedUntil > CURRENT_TIMESTAMP;
+
+    IF v_locked_until IS NULL THEN
+        RETURN NULL;
+    END IF;
+
+    RETURN CEIL(EXTRACT(EPOCH FROM v_locked_until - CURRENT_TIMESTAMP))::INT;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Record Login Failure
+-- Counts a failed attempt for p_key. From p_max_attempts failures on, the key is locked for
+-- p_base_lockout_seconds, doubling with every further failure up to p_max_lockout_seconds.
+-- Counters are forgotten once they saw no failure for p_max_lockout_seconds and are not locked.
+-- Returns the failure count.
+CREATE OR REPLACE FUNCTION sp_record_login_failure(
+    p_key VARCHAR,
+    p_max_attempts INT,
+    p_base_lockout_seconds INT,
+    p_max_lockout_seconds INT
+)
+RETURNS INTEGER AS $$
+DECLARE
+    v_failed_count INTEGER;
+BEGIN
+    DELETE FROM LoginAttempts la
+    WHERE la.LastFailedAt < CURRENT_TIMESTAMP - make_interval(secs => p_max_lockout_seconds)
+    AND (la.LockedUntil IS NULL OR la.LockedUntil < CURRENT_TIMESTAMP);
+
+    INSERT INTO LoginAttempts AS la (AttemptKey, FailedCount, LastFailedAt)
+    VALUES (p_key, 1, CURRENT_TIMESTAMP)
+    ON CONFLICT (AttemptKey) DO UPDATE
+    SET FailedCount = la.FailedCount + 1,
+        LastFailedAt = CURRENT_TIMESTAMP
+    RETURNING la.FailedCount INTO v_failed_count;
+
+    IF v_failed_count >= p_max_attempts THEN
+        UPDATE LoginAttempts la
+        SET LockedUntil = CURRENT_TIMESTAMP + make_interval(secs => LEAST(
+            p_base_lockout_seconds * power(2, LEAST(v_failed_count - p_max_attempts, 20)),
+            p_max_lockout_seconds
+        ))
+        WHERE la.AttemptKey = p_key;
+    END IF;
+
+    RETURN v_failed_count;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Clear Login Failures (after a successful login)
+CREATE OR REPLACE FUNCTION sp_clear_login_failures(p_key VARCHAR)
+RETURNS INTEGER AS $$
+BEGIN
+    DELETE FROM LoginAttempts la
+    WHERE la.AttemptKey = p_key;
+
+    RETURN FOUND::INT;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Update Password Hash
+-- Swaps in an upgraded hash of the same password, but only while p_old_hash is still current
+CREATE OR REPLACE FUNCTION sp_update_password_hash(p_user_id INT, p_old_hash VARCHAR, p_new_hash VARCHAR)
+RETURNS INTEGER AS $$
+BEGIN
+    UPDATE Users u
+    SET PasswordHash = p_new_hash
+    WHERE u.Id = p_user_id AND u.PasswordHash = p_old_hash;
+
+    RETURN FOUND::INT;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Get Password Reset User
+-- Returns the account a valid, unused reset token belongs to, without consuming the token
+CREATE OR REPLACE FUNCTION sp_get_password_reset_user(p_token_hash VARCHAR)
+RETURNS TABLE (UserId INT, Username VARCHAR, Email VARCHAR) AS $$
+BEGIN
+    RETURN QUERY
+    SELECT u.Id, u.Username, u.Email
+    FROM PasswordResetTokens prt
+    JOIN Users u ON u.Id = prt.UserId
+    WHERE prt.TokenHash = p_token_hash
+    AND prt.UsedAt IS NULL
+    AND prt.ExpiresAt > CURRENT_TIMESTAMP;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Create Personal Access Token
+-- p_ttl_seconds NULL creates a token that never expires
+CREATE OR REPLACE FUNCTION sp_create_personal_access_token(
+    p_user_id INT,
+    p_name VARCHAR,
+    p_token_hash VARCHAR,
+    p_token_prefix VARCHAR,
+    p_scopes TEXT[],
+    p_ttl_seconds INT
+)
+RETURNS TABLE (Id INT, Name VARCHAR, TokenPrefix VARCHAR, Scopes TEXT[], ExpiresAt TIMESTAMPTZ, LastUsedAt TIMESTAMPTZ, CreatedAt TIMESTAMPTZ) AS $$
+BEGIN
+    RETURN QUERY
+    INSERT INTO PersonalAccessTokens AS pat (UserId, Name, TokenHash, TokenPrefix, Scopes, ExpiresAt)
+    VALUES (p_user_id, p_name, p_token_hash, p_token_prefix, p_scopes,
+            CURRENT_TIMESTAMP + make_interval(secs => p_ttl_seconds))
+    RETURNING pat.Id, pat.Name, pat.TokenPrefix, pat.Scopes, pat.ExpiresAt, pat.LastUsedAt, pat.CreatedAt;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Get Personal Access Tokens of a user (revoked tokens are left out)
+CREATE OR REPLACE FUNCTION sp_get_personal_access_tokens(p_user_id INT)
+RETURNS TABLE (Id INT, Name VARCHAR, TokenPrefix VARCHAR, Scopes TEXT[], ExpiresAt TIMESTAMPTZ, LastUsedAt TIMESTAMPTZ, CreatedAt TIMESTAMPTZ) AS $$
+BEGIN
+    RETURN QUERY
+    SELECT pat.Id, pat.Name, pat.TokenPrefix, pat.Scopes, pat.ExpiresAt, pat.LastUsedAt, pat.CreatedAt
+    FROM PersonalAccessTokens pat
+    WHERE pat.UserId = p_user_id AND pat.RevokedAt IS NULL
+    ORDER BY pat.CreatedAt DESC;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Revoke Personal Access Token
+CREATE OR REPLACE FUNCTION sp_revoke_personal_access_token(p_token_id INT, p_user_id INT)
+RETURNS INTEGER AS $$
+BEGIN
+    UPDATE PersonalAccessTokens pat
+    SET RevokedAt = CURRENT_TIMESTAMP
+    WHERE pat.Id = p_token_id AND pat.UserId = p_user_id AND pat.RevokedAt IS NULL;
+
+    RETURN FOUND::INT;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Authenticate Personal Access Token
+-- Status is one of: valid, expired, revoked, invalid. Valid tokens get their LastUsedAt updated.
+CREATE OR REPLACE FUNCTION sp_authenticate_personal_access_token(p_token_hash VARCHAR)
+RETURNS TABLE (Id INT, UserId INT, Scopes TEXT[], EmailVerified BOOLEAN, Status TEXT) AS $$
+DECLARE
+    v_token PersonalAccessTokens%ROWTYPE;
+    v_email_verified BOOLEAN;
+    v_suspended_at TIMESTAMPTZ;
+BEGIN
+    SELECT pat.* INTO v_token
+    FROM PersonalAccessTokens pat
+    WHERE pat.TokenHash = p_token_hash;
+
+    IF NOT FOUND THEN
+        RETURN QUERY SELECT NULL::INT, NULL::INT, NULL::TEXT[], NULL::BOOLEAN, 'invalid'::TEXT;
+        RETURN;
+    END IF;
+
+    IF v_token.RevokedAt IS NOT NULL THEN
+        RETURN QUERY SELECT v_token.Id, v_token.UserId, NULL::TEXT[], NULL::BOOLEAN, 'revoked'::TEXT;
+        RETURN;
+    END IF;
+
+    IF v_token.ExpiresAt IS NOT NULL AND v_token.ExpiresAt <= CURRENT_TIMESTAMP THEN
+        RETURN QUERY SELECT v_token.Id, v_token.UserId, NULL::TEXT[], NULL::BOOLEAN, 'expired'::TEXT;
+        RETURN;
+    END IF;
+
+    SELECT u.EmailVerified, u.SuspendedAt INTO v_email_verified, v_suspended_at
+    FROM Users u
+    WHERE u.Id = v_token.UserId;
+
+    IF v_suspended_at IS NOT NULL THEN
+        RETURN QUERY SELECT v_token.Id, v_token.UserId, NULL::TEXT[], NULL::BOOLEAN, 'suspended'::TEXT;
+        RETURN;
+    END IF;
+
+    UPDATE PersonalAccessTokens pat
+    SET LastUsedAt = CURRENT_TIMESTAMP
+    WHERE pat.Id = v_token.Id;
+
+    RETURN QUERY SELECT v_token.Id, v_token.UserId, v_token.Scopes, v_email_verified, 'valid'::TEXT;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Create OAuth State (expired states are purged along the way)
+CREATE OR REPLACE FUNCTION sp_create_oauth_state(
+    p_state_hash VARCHAR,
+    p_provider VARCHAR,
+    p_code_verifier VARCHAR,
+    p_nonce VARCHAR,
+    p_ttl_seconds INT
+)
+RETURNS INTEGER AS $$
+BEGIN
+    DELETE FROM OAuthStates os
+    WHERE os.ExpiresAt < CURRENT_TIMESTAMP;
+
+    INSERT INTO OAuthStates (StateHash, Provider, CodeVerifier, Nonce, ExpiresAt)
+    VALUES (p_state_hash, p_provider, p_code_verifier, p_nonce,
+            CURRENT_TIMESTAMP + make_interval(secs => p_ttl_seconds));
+
+    RETURN 1;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Consume OAuth State
+-- Deletes the state and returns its code verifier and nonce; no row when it is unknown,
+-- expired or belongs to another provider
+CREATE OR REPLACE FUNCTION sp_consume_oauth_state(p_state_hash VARCHAR, p_provider VARCHAR)
+RETURNS TABLE (CodeVerifier VARCHAR, Nonce VARCHAR) AS $$
+BEGIN
+    RETURN QUERY
+    DELETE FROM OAuthStates os
+    WHERE os.StateHash = p_state_hash
+    AND os.Provider = p_provider
+    AND os.ExpiresAt > CURRENT_TIMESTAMP
+    RETURNING os.CodeVerifier, os.Nonce;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Find or Create OIDC User
+-- Resolves an external identity to a local user. Status is one of:
+--   linked        the identity was linked before
+--   linked_email  the identity was linked to the existing account with the same (provider-verified) email
+--   created       a new account was created and linked
+--   email_taken   an account with this email exists, but the provider did not verify the email
+CREATE OR REPLACE FUNCTION sp_find_or_create_oidc_user(
+    p_provider VARCHAR,
+    p_subject VARCHAR,
+    p_email VARCHAR,
+    p_email_verified BOOLEAN,
+    p_username VARCHAR,
+    p_password_hash VARCHAR
+)
+RETURNS TABLE (UserId INT, Status TEXT) AS $$
+DECLARE
+    v_user_id INTEGER;
+    v_username VARCHAR;
+    v_status TEXT;
+BEGIN
+    SELECT ui.UserId INTO v_user_id
+    FROM UserIdentities ui
+    WHERE ui.Provider = p_provider AND ui.Subject = p_subject;
+
+    IF FOUND THEN
+        RETURN QUERY SELECT v_user_id, 'linked'::TEXT;
+        RETURN;
+    END IF;
+
+    SELECT u.Id INTO v_user_id
+    FROM Users u
+    WHERE u.Email = p_email;
+
+    IF FOUND THEN
+        IF NOT p_email_verified THEN
+            RETURN QUERY SELECT NULL::INT, 'email_taken'::TEXT;
+            RETURN;
+        END IF;
+        v_status := 'linked_email';
+    ELSE
+        -- Usernames are unique; add a numeric suffix until the name is free
+        v_username := LEFT(p_username, 90);
+        WHILE EXISTS (SELECT 1 FROM Users u WHERE u.Username = v_username) LOOP
+            v_username := LEFT(p_username, 90) || '_' || (floor(random() * 1000000))::INT;
+        END LOOP;
+
+        INSERT INTO Users (Username, Email, PasswordHash, EmailVerified, CreatedAt, UpdatedAt)
+        VALUES (v_username, p_email, p_password_hash, p_email_verified,
+                CURRENT_TIMESTAMP AT TIME ZONE 'Asia/Bangkok',
+                CURRENT_TIMESTAMP AT TIME ZONE 'Asia/Bangkok')
+        RETURNING Id INTO v_user_id;
+        v_status := 'created';
+    END IF;
+
+    INSERT INTO UserIdentities (UserId, Provider, Subject, Email)
+    VALUES (v_user_id, p_provider, p_subject, p_email);
+
+    RETURN QUERY SELECT v_user_id, v_status;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Create Session
+-- Records a login and starts its refresh token family (whose FamilyId is the session ID)
+CREATE OR REPLACE FUNCTION sp_create_session(
+    p_user_id INT,
+    p_device_name VARCHAR,
+    p_user_agent VARCHAR,
+    p_ip_address INET,
+    p_token_hash VARCHAR,
+    p_ttl_seconds INT
+)
+RETURNS UUID AS $$
+DECLARE
+    v_session_id UUID := gen_random_uuid();
+BEGIN
+    INSERT INTO Sessions (Id, UserId, DeviceName, UserAgent, IpAddress)
+    VALUES (v_session_id, p_user_id, p_device_name, p_user_agent, p_ip_address);
+
+    INSERT INTO RefreshTokens (UserId, FamilyId, TokenHash, ExpiresAt)
+    VALUES (p_user_id, v_session_id, p_token_hash,
+            CURRENT_TIMESTAMP + make_interval(secs => p_ttl_seconds));
+
+    RETURN v_session_id;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Get User Sessions
+-- Sessions that are neither signed out nor past the expiry of their refresh token
+CREATE OR REPLACE FUNCTION sp_get_user_sessions(p_user_id INT)
+RETURNS TABLE (
+    Id UUID,
+    DeviceName VARCHAR,
+    UserAgent VARCHAR,
+    IpAddress INET,
+    CreatedAt TIMESTAMPTZ,
+    LastSeenAt TIMESTAMPTZ
+) AS $$
+BEGIN
+    RETURN QUERY
+    SELECT s.Id, s.DeviceName, s.UserAgent, s.IpAddress, s.CreatedAt, s.LastSeenAt
+    FROM Sessions s
+    WHERE s.UserId = p_user_id
+    AND s.RevokedAt IS NULL
+    AND EXISTS (
+        SELECT 1 FROM RefreshTokens rt
+        WHERE rt.FamilyId = s.Id
+        AND rt.RevokedAt IS NULL
+        AND rt.ExpiresAt > CURRENT_TIMESTAMP
+    )
+    ORDER BY s.LastSeenAt DESC;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Revoke Session
+-- Signs a session out: its access tokens are rejected and its refresh tokens revoked.
+-- Returns 1 when an active session of the user was revoked, 0 otherwise.
+CREATE OR REPLACE FUNCTION sp_revoke_session(p_session_id UUID, p_user_id INT)
+RETURNS INTEGER AS $$
+BEGIN
+    UPDATE Sessions s
+    SET RevokedAt = CURRENT_TIMESTAMP
+    WHERE s.Id = p_session_id
+    AND s.UserId = p_user_id
+    AND s.RevokedAt IS NULL;
+
+    IF NOT FOUND THEN
+        RETURN 0;
+    END IF;
+
+    UPDATE RefreshTokens rt
+    SET RevokedAt = CURRENT_TIMESTAMP
+    WHERE rt.FamilyId = p_session_id AND rt.RevokedAt IS NULL;
+
+    RETURN 1;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Revoke Other Sessions
+-- Signs out every session of the user except the given one; returns how many were revoked
+CREATE OR REPLACE FUNCTION sp_revoke_other_sessions(p_user_id INT, p_current_session_id UUID)
+RETURNS INTEGER AS $$
+DECLARE
+    v_revoked_count INTEGER;
+BEGIN
+    WITH revoked AS (
+        UPDATE Sessions s
+        SET RevokedAt = CURRENT_TIMESTAMP
+        WHERE s.UserId = p_user_id
+        AND s.Id <> p_current_session_id
+        AND s.RevokedAt IS NULL
+        RETURNING s.Id
+    )
+    SELECT COUNT(*) INTO v_revoked_count FROM revoked;
+
+    -- Also covers token families that never got a session row
+    UPDATE RefreshTokens rt
+    SET RevokedAt = CURRENT_TIMESTAMP
+    WHERE rt.UserId = p_user_id
+    AND rt.FamilyId <> p_current_session_id
+    AND rt.RevokedAt IS NULL;
+
+    RETURN v_revoked_count;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Get User Access
+-- Role names of a user and the permissions those roles grant
+CREATE OR REPLACE FUNCTION sp_get_user_access(p_user_id INT)
+RETURNS TABLE (Roles VARCHAR[], Permissions VARCHAR[]) AS $$
+BEGIN
+    RETURN QUERY
+    SELECT
+        COALESCE((
+            SELECT array_agg(r.Name ORDER BY r.Name)
+            FROM UserRoles ur
+            JOIN Roles r ON r.Id = ur.RoleId
+            WHERE ur.UserId = p_user_id
+        ), ARRAY[]::VARCHAR[]),
+        COALESCE((
+            SELECT array_agg(DISTINCT p.Name ORDER BY p.Name)
+            FROM UserRoles ur
+            JOIN RolePermissions rp ON rp.RoleId = ur.RoleId
+            JOIN Permissions p ON p.Id = rp.PermissionId
+            WHERE ur.UserId = p_user_id
+        ), ARRAY[]::VARCHAR[]);
+END;
+$$ LANGUAGE plpgsql;
+
+-- Get Roles (with the permissions each role grants)
+CREATE OR REPLACE FUNCTION sp_get_roles()
+RETURNS TABLE (Name VARCHAR, Description VARCHAR, Permissions VARCHAR[]) AS $$
+BEGIN
+    RETURN QUERY
+    SELECT r.Name, r.Description,
+           COALESCE(array_agg(p.Name ORDER BY p.Name) FILTER (WHERE p.Name IS NOT NULL), ARRAY[]::VARCHAR[])
+    FROM Roles r
+    LEFT JOIN RolePermissions rp ON rp.RoleId = r.Id
+    LEFT JOIN Permissions p ON p.Id = rp.PermissionId
+    GROUP BY r.Id, r.Name, r.Description
+    ORDER BY r.Name;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Assign User Role
+-- Status is one of: assigned, unchanged (already held), unknown_user, unknown_role.
+-- Bumps the token version so the user's access tokens are re-issued with the new role.
+CREATE OR REPLACE FUNCTION sp_assign_user_role(p_user_id INT, p_role_name VARCHAR)
+RETURNS TEXT AS $$
+DECLARE
+    v_role_id INTEGER;
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM Users u WHERE u.Id = p_user_id) THEN
+        RETURN 'unknown_user';
+    END IF;
+
+    SELECT r.Id INTO v_role_id FROM Roles r WHERE r.Name = p_role_name;
+    IF NOT FOUND THEN
+        RETURN 'unknown_role';
+    END IF;
+
+    INSERT INTO UserRoles (UserId, RoleId)
+    VALUES (p_user_id, v_role_id)
+    ON CONFLICT DO NOTHING;
+
+    IF NOT FOUND THEN
+        RETURN 'unchanged';
+    END IF;
+
+    UPDATE Users u
+    SET TokenVersion = u.TokenVersion + 1
+    WHERE u.Id = p_user_id;
+
+    RETURN 'assigned';
+END;
+$$ LANGUAGE plpgsql;
+
+-- Remove User Role
+-- Status is one of: removed, unchanged (not held), unknown_user, unknown_role.
+-- Bumps the token version so access tokens carrying the role stop working right away.
+CREATE OR REPLACE FUNCTION sp_remove_user_role(p_user_id INT, p_role_name VARCHAR)
+RETURNS TEXT AS $$
+DECLARE
+    v_role_id INTEGER;
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM Users u WHERE u.Id = p_user_id) THEN
+        RETURN 'unknown_user';
+    END IF;
+
+    SELECT r.Id INTO v_role_id FROM Roles r WHERE r.Name = p_role_name;
+    IF NOT FOUND THEN
+        RETURN 'unknown_role';
+    END IF;
+
+    DELETE FROM UserRoles ur
+    WHERE ur.UserId = p_user_id AND ur.RoleId = v_role_id;
+
+    IF NOT FOUND THEN
+        RETURN 'unchanged';
+    END IF;
+
+    UPDATE Users u
+    SET TokenVersion = u.TokenVersion + 1
+    WHERE u.Id = p_user_id;
+
+    RETURN 'removed';
+END;
+$$ LANGUAGE plpgsql;
+
+-- Admin: Get Users
+-- Page of users, optionally filtered by a search term matching username or email.
+-- TotalCount is the number of matching users across all pages.
+CREATE OR REPLACE FUNCTION sp_admin_get_users(p_search VARCHAR, p_limit INT, p_offset INT)
+RETURNS TABLE (
+    Id INT,
+    Username VARCHAR,
+    Email VARCHAR,
+    EmailVerified BOOLEAN,
+    Roles VARCHAR[],
+    SuspendedAt TIMESTAMPTZ,
+    SuspensionReason VARCHAR,
+    CreatedAt TIMESTAMPTZ,
+    LastLoginAt TIMESTAMPTZ,
+    TotalCount BIGINT
+) AS $$
+BEGIN
+    RETURN QUERY
+    SELECT u.Id, u.Username, u.Email, u.EmailVerified,
+           COALESCE((
+               SELECT array_agg(r.Name ORDER BY r.Name)
+               FROM UserRoles ur
+               JOIN Roles r ON r.Id = ur.RoleId
+               WHERE ur.UserId = u.Id
+           ), ARRAY[]::VARCHAR[]),
+           u.SuspendedAt, u.SuspensionReason, u.CreatedAt,
+           (SELECT MAX(s.CreatedAt) FROM Sessions s WHERE s.UserId = u.Id),
+           COUNT(*) OVER ()
+    FROM Users u
+    WHERE p_search IS NULL
+    OR u.Username ILIKE '%' || p_search || '%'
+    OR u.Email ILIKE '%' || p_search || '%'
+    ORDER BY u.Id
+    LIMIT p_limit OFFSET p_offset;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Admin: Get User
+-- Account details with note count, last login and number of active sessions
+CREATE OR REPLACE FUNCTION sp_admin_get_user(p_user_id INT)
+RETURNS TABLE (
+    Id INT,
+    Username VARCHAR,
+    Email VARCHAR,
+    EmailVerified BOOLEAN,
+    Roles VARCHAR[],
+    SuspendedAt TIMESTAMPTZ,
+    SuspensionReason VARCHAR,
+    CreatedAt TIMESTAMPTZ,
+    LastLoginAt TIMESTAMPTZ,
+    TotpEnabled BOOLEAN,
+    NoteCount BIGINT,
+    ActiveSessions BIGINT
+) AS $$
+BEGIN
+    RETURN QUERY
+    SELECT u.Id, u.Username, u.Email, u.EmailVerified,
+           COALESCE((
+               SELECT array_agg(r.Name ORDER BY r.Name)
+               FROM UserRoles ur
+               JOIN Roles r ON r.Id = ur.RoleId
+               WHERE ur.UserId = u.Id
+           ), ARRAY[]::VARCHAR[]),
+           u.SuspendedAt, u.SuspensionReason, u.CreatedAt,
+           (SELECT MAX(s.CreatedAt) FROM Sessions s WHERE s.UserId = u.Id),
+           u.TotpEnabled,
+           (SELECT COUNT(*) FROM Notes n WHERE n.UserId = u.Id),
+           (SELECT COUNT(*) FROM Sessions s WHERE s.UserId = u.Id AND s.RevokedAt IS NULL)
+    FROM Users u
+    WHERE u.Id = p_user_id;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Admin: Suspend User
+-- Returns 1 when the user exists; suspending again only updates the reason
+CREATE OR REPLACE FUNCTION sp_suspend_user(p_user_id INT, p_reason VARCHAR)
+RETURNS INTEGER AS $$
+BEGIN
+    UPDATE Users u
+    SET SuspendedAt = COALESCE(u.SuspendedAt, CURRENT_TIMESTAMP),
+        SuspensionReason = p_reason,
+        UpdatedAt = CURRENT_TIMESTAMP AT TIME ZONE 'Asia/Bangkok'
+    WHERE u.Id = p_user_id;
+
+    RETURN FOUND::INT;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Admin: Unsuspend User
+-- Returns 1 when the user exists
+CREATE OR REPLACE FUNCTION sp_unsuspend_user(p_user_id INT)
+RETURNS INTEGER AS $$
+BEGIN
+    UPDATE Users u
+    SET SuspendedAt = NULL,
+        SuspensionReason = NULL,
+        UpdatedAt = CURRENT_TIMESTAMP AT TIME ZONE 'Asia/Bangkok'
+    WHERE u.Id = p_user_id;
+
+    RETURN FOUND::INT;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Admin: Force Password Reset
+-- Replaces the password hash with an unusable one and signs the user out everywhere.
+-- Returns the email to send the reset link to; no row when the user does not exist.
+CREATE OR REPLACE FUNCTION sp_force_password_reset(p_user_id INT, p_unusable_hash VARCHAR)
+RETURNS TABLE (Email VARCHAR) AS $$
+BEGIN
+    UPDATE Users u
+    SET PasswordHash = p_unusable_hash,
+        UpdatedAt = CURRENT_TIMESTAMP AT TIME ZONE 'Asia/Bangkok'
+    WHERE u.Id = p_user_id;
+
+    IF NOT FOUND THEN
+        RETURN;
+    END IF;
+
+    PERFORM sp_revoke_user_tokens(p_user_id);
+
+    RETURN QUERY SELECT u.Email FROM Users u WHERE u.Id = p_user_id;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Anonymize Auth Events
+-- Erases the email addresses, IP addresses and user agents from the events of a deleted user,
+-- and the reasons that name an address. What happened and when stays in the log.
+CREATE OR REPLACE FUNCTION sp_anonymize_auth_events(p_user_id INT, p_email VARCHAR)
+RETURNS INTEGER AS $$
+DECLARE
+    v_count INTEGER;
+BEGIN
+    UPDATE AuthEvents ae
+    SET Email = NULL,
+        IpAddress = NULL,
+        UserAgent = NULL,
+        Reason = CASE WHEN ae.EventType IN ('email_change_requested', 'email_changed')
+                      THEN NULL ELSE ae.Reason END
+    WHERE (ae.UserId = p_user_id OR ae.Email = p_email)
+    AND (ae.Email IS NOT NULL OR ae.IpAddress IS NOT NULL OR ae.UserAgent IS NOT NULL);
+
+    GET DIAGNOSTICS v_count = ROW_COUNT;
+    RETURN v_count;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Admin: Delete User
+-- Notes, tokens, sessions and every other row of the user go with it (ON DELETE CASCADE);
+-- only the anonymized security events are kept
+CREATE OR REPLACE FUNCTION sp_delete_user(p_user_id INT)
+RETURNS INTEGER AS $$
+DECLARE
+    v_email VARCHAR;
+BEGIN
+    DELETE FROM Users u
+    WHERE u.Id = p_user_id
+    RETURNING u.Email INTO v_email;
+
+    IF NOT FOUND THEN
+        RETURN 0;
+    END IF;
+
+    PERFORM sp_anonymize_auth_events(p_user_id, v_email);
+
+    RETURN 1;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Change Password
+-- Replaces the password hash if it still is the one the current password was checked
+-- against, then signs out every other session and revokes the personal access tokens; without
+-- a current session (tokens from before sessions existed) all tokens are revoked. Returns 1 on
+-- success, 0 otherwise.
+DROP FUNCTION IF EXISTS sp_change_password(INT, VARCHAR, VARCHAR, UUID, INET);
+CREATE OR REPLACE FUNCTION sp_change_password(
+    p_user_id INT,
+    p_current_hash VARCHAR,
+    p_new_hash VARCHAR,
+    p_current_session_id UUID,
+    p_ip_address INET,
+    p_user_agent VARCHAR
+)
+RETURNS INTEGER AS $$
+BEGIN
+    UPDATE Users u
+    SET PasswordHash = p_new_hash,
+        UpdatedAt = CURRENT_TIMESTAMP AT TIME ZONE 'Asia/Bangkok'
+    WHERE u.Id = p_user_id AND u.PasswordHash = p_current_hash;
+
+    IF NOT FOUND THEN
+        RETURN 0;
+    END IF;
+
+    -- Reset links requested with the old password would otherwise still work
+    DELETE FROM PasswordResetTokens prt
+    WHERE prt.UserId = p_user_id AND prt.UsedAt IS NULL;
+
+    IF p_current_session_id IS NULL THEN
+        PERFORM sp_revoke_user_tokens(p_user_id);
+    ELSE
+        PERFORM sp_revoke_other_sessions(p_user_id, p_current_session_id);
+
+        UPDATE PersonalAccessTokens pat
+        SET RevokedAt = CURRENT_TIMESTAMP
+        WHERE pat.UserId = p_user_id AND pat.RevokedAt IS NULL;
+    END IF;
+
+    PERFORM sp_record_auth_event(p_user_id, NULL, 'password_changed', TRUE, NULL, p_ip_address, p_user_agent);
+
+    RETURN 1;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Request Email Change
+-- Stores a verification token for the new address; the address is only swapped once
+-- the token is redeemed through sp_verify_email.
+-- Status is one of: requested, unchanged (already the current address), email_taken.
+DROP FUNCTION IF EXISTS sp_request_email_change(INT, VARCHAR, VARCHAR, INT, INET);
+CREATE OR REPLACE FUNCTION sp_request_email_change(
+    p_user_id INT,
+    p_new_email VARCHAR,
+    p_token_hash VARCHAR,
+    p_ttl_seconds INT,
+    p_ip_address INET,
+    p_user_agent VARCHAR
+)
+RETURNS TEXT AS $$
+BEGIN
+    IF EXISTS (SELECT 1 FROM Users u WHERE u.Id = p_user_id AND u.Email = p_new_email) THEN
+        RETURN 'unchanged';
+    END IF;
+
+    IF EXISTS (SELECT 1 FROM Users u WHERE u.Email = p_new_email) THEN
+        RETURN 'email_taken';
+    END IF;
+
+    PERFORM sp_create_email_verification_token(p_user_id, p_new_email, p_token_hash, p_ttl_seconds);
+
+    PERFORM sp_record_auth_event(p_user_id, NULL, 'email_change_requested', TRUE, p_new_email, p_ip_address, p_user_agent);
+
+    RETURN 'requested';
+END;
+$$ LANGUAGE plpgsql;
+
+-- Schedule User Deletion
+-- Signs the user out everywhere and schedules the purge; returns when it will happen,
+-- or NULL when the user does not exist
+DROP FUNCTION IF EXISTS sp_schedule_user_deletion(INT, INT, INET);
+CREATE OR REPLACE FUNCTION sp_schedule_user_deletion(p_user_id INT, p_grace_seconds INT, p_ip_address INET, p_user_agent VARCHAR)
+RETURNS TIMESTAMPTZ AS $$
+DECLARE
+    v_scheduled_at TIMESTAMPTZ;
+BEGIN
+    UPDATE Users u
+    SET DeletionScheduledAt = CURRENT_TIMESTAMP + make_interval(secs => p_grace_seconds),
+        UpdatedAt = CURRENT_TIMESTAMP AT TIME ZONE 'Asia/Bangkok'
+    WHERE u.Id = p_user_id
+    RETURNING u.DeletionScheduledAt INTO v_scheduled_at;
+
+    IF v_scheduled_at IS NULL THEN
+        RETURN NULL;
+    END IF;
+
+    PERFORM sp_revoke_user_tokens(p_user_id);
+
+    PERFORM sp_record_auth_event(p_user_id, NULL, 'deletion_scheduled', TRUE,
+                                 'purge at ' || v_scheduled_at::TEXT, p_ip_address, p_user_agent);
+
+    RETURN v_scheduled_at;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Cancel User Deletion
+-- Returns 1 when a pending deletion was cancelled
+DROP FUNCTION IF EXISTS sp_cancel_user_deletion(INT);
+CREATE OR REPLACE FUNCTION sp_cancel_user_deletion(p_user_id INT, p_ip_address INET, p_user_agent VARCHAR)
+RETURNS INTEGER AS $$
+BEGIN
+    UPDATE Users u
+    SET DeletionScheduledAt = NULL,
+        UpdatedAt = CURRENT_TIMESTAMP AT TIME ZONE 'Asia/Bangkok'
+    WHERE u.Id = p_user_id AND u.DeletionScheduledAt IS NOT NULL;
+
+    IF NOT FOUND THEN
+        RETURN 0;
+    END IF;
+
+    PERFORM sp_record_auth_event(p_user_id, NULL, 'deletion_cancelled', TRUE, 'logged in during the grace period',
+                                 p_ip_address, p_user_agent);
+
+    RETURN 1;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Purge Deleted Users
+-- Deletes every account whose grace period has ended (ON DELETE CASCADE removes the rest;
+-- the security events are anonymized).
+-- Returns the IDs of the purged accounts, so their stored files can be removed too.
+DROP FUNCTION IF EXISTS sp_purge_deleted_users();
+CREATE OR REPLACE FUNCTION sp_purge_deleted_users()
+RETURNS TABLE (UserId INT) AS $$
+DECLARE
+    v_user RECORD;
+BEGIN
+    FOR v_user IN
+        DELETE FROM Users u
+        WHERE u.DeletionScheduledAt IS NOT NULL
+        AND u.DeletionScheduledAt <= CURRENT_TIMESTAMP
+        RETURNING u.Id, u.Email
+    LOOP
+        PERFORM sp_anonymize_auth_events(v_user.Id, v_user.Email);
+        UserId := v_user.Id;
+        RETURN NEXT;
+    END LOOP;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Record Auth Event
+-- Appends to the security event log. Without a user ID the user is looked up by p_email,
+-- so failed logins for existing accounts show up in their history.
+CREATE OR REPLACE FUNCTION sp_record_auth_event(
+    p_user_id INT,
+    p_email VARCHAR,
+    p_event_type VARCHAR,
+    p_success BOOLEAN,
+    p_reason VARCHAR,
+    p_ip_address INET,
+    p_user_agent VARCHAR
+)
+RETURNS BIGINT AS $$
+DECLARE
+    v_user_id INTEGER := p_user_id;
+    v_event_id BIGINT;
+BEGIN
+    IF v_user_id IS NULL AND p_email IS NOT NULL THEN
+        SELECT u.Id INTO v_user_id
+        FROM Users u
+        WHERE u.Email = p_email;
+    END IF;
+
+    INSERT INTO AuthEvents (UserId, Email, EventType, Success, Reason, IpAddress, UserAgent)
+    VALUES (v_user_id, p_email, p_event_type, p_success, LEFT(p_reason, 500), p_ip_address, LEFT(p_user_agent, 500))
+    RETURNING Id INTO v_event_id;
+
+    RETURN v_event_id;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Get Auth Events
+-- Newest first. Every filter is optional; a NULL user ID returns the events of all users.
+-- TotalCount is the number of matching events across all pages.
+CREATE OR REPLACE FUNCTION sp_get_auth_events(
+    p_user_id INT,
+    p_event_type VARCHAR,
+    p_success BOOLEAN,
+    p_limit INT,
+    p_offset INT
+)
+RETURNS TABLE (
+    Id BIGINT,
+    UserId INT,
+    Email VARCHAR,
+    EventType VARCHAR,
+    Success BOOLEAN,
+    Reason VARCHAR,
+    IpAddress INET,
+    UserAgent VARCHAR,
+    CreatedAt TIMESTAMPTZ,
+    TotalCount BIGINT
+) AS $$
+BEGIN
+    RETURN QUERY
+    SELECT ae.Id, ae.UserId, ae.Email, ae.EventType, ae.Success, ae.Reason,
+           ae.IpAddress, ae.UserAgent, ae.CreatedAt,
+           COUNT(*) OVER ()
+    FROM AuthEvents ae
+    WHERE (p_user_id IS NULL OR ae.UserId = p_user_id)
+    AND (p_event_type IS NULL OR ae.EventType = p_event_type)
+    AND (p_success IS NULL OR ae.Success = p_success)
+    ORDER BY ae.CreatedAt DESC, ae.Id DESC
+    LIMIT p_limit OFFSET p_offset;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Update User
+-- Profile fields left NULL keep their value; an empty string clears the field
+CREATE OR REPLACE FUNCTION sp_update_user(
+    p_user_id INT,
+    p_display_name VARCHAR,
+    p_bio VARCHAR,
+    p_locale VARCHAR,
+    p_timezone VARCHAR
+)
+RETURNS INTEGER AS $$
+BEGIN
+    UPDATE Users u
+    SET DisplayName = CASE WHEN p_display_name IS NULL THEN u.DisplayName ELSE NULLIF(p_display_name, '') END,
+        Bio = CASE WHEN p_bio IS NULL THEN u.Bio ELSE NULLIF(p_bio, '') END,
+        Locale = CASE WHEN p_locale IS NULL THEN u.Locale ELSE NULLIF(p_locale, '') END,
+        Timezone = CASE WHEN p_timezone IS NULL THEN u.Timezone ELSE NULLIF(p_timezone, '') END,
+        UpdatedAt = CURRENT_TIMESTAMP AT TIME ZONE 'Asia/Bangkok'
+    WHERE u.Id = p_user_id;
+
+    RETURN FOUND::INT;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Set User Avatar
+-- Points the user at a new avatar version, or at none with NULL; returns the version it replaced
+CREATE OR REPLACE FUNCTION sp_set_user_avatar(p_user_id INT, p_version UUID)
+RETURNS UUID AS $$
+DECLARE
+    v_previous_version UUID;
+BEGIN
+    SELECT u.AvatarVersion INTO v_previous_version
+    FROM Users u
+    WHERE u.Id = p_user_id
+    FOR UPDATE;
+
+    UPDATE Users u
+    SET AvatarVersion = p_version,
+        UpdatedAt = CURRENT_TIMESTAMP AT TIME ZONE 'Asia/Bangkok'
+    WHERE u.Id = p_user_id;
+
+    RETURN v_previous_version;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Get Tags
+-- The user's tags by name, with the number of notes carrying each
+CREATE OR REPLACE FUNCTION sp_get_tags(p_user_id INT)
+RETURNS TABLE (Id INT, Name VARCHAR, NoteCount BIGINT, CreatedAt TIMESTAMPTZ) AS $$
+BEGIN
+    RETURN QUERY
+    SELECT t.Id, t.Name, COUNT(nt.NoteId), t.CreatedAt
+    FROM Tags t
+    LEFT JOIN NoteTags nt ON nt.TagId = t.Id
+    WHERE t.UserId = p_user_id
+    GROUP BY t.Id
+    ORDER BY LOWER(t.Name), t.Id;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Create Tag
+-- Returns no row when the user already has a tag with that name, in any case
+CREATE OR REPLACE FUNCTION sp_create_tag(p_user_id INT, p_name VARCHAR)
+RETURNS TABLE (Id INT, Name VARCHAR, NoteCount BIGINT, CreatedAt TIMESTAMPTZ) AS $$
+DECLARE
+    v_tag_id INT;
+BEGIN
+    INSERT INTO Tags (UserId, Name)
+    VALUES (p_user_id, p_name)
+    ON CONFLICT DO NOTHING
+    RETURNING Tags.Id INTO v_tag_id;
+
+    RETURN QUERY
+    SELECT t.Id, t.Name, 0::BIGINT, t.CreatedAt
+    FROM Tags t
+    WHERE t.Id = v_tag_id;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Rename Tag
+-- Status is one of: renamed, not_found, name_taken. Changing only the case of a name is a rename.
+CREATE OR REPLACE FUNCTION sp_rename_tag(p_tag_id INT, p_user_id INT, p_name VARCHAR)
+RETURNS TEXT AS $$
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM Tags t WHERE t.Id = p_tag_id AND t.UserId = p_user_id) THEN
+        RETURN 'not_found';
+    END IF;
+
+    IF EXISTS (
+        SELECT 1 FROM Tags t
+        WHERE t.UserId = p_user_id AND LOWER(t.Name) = LOWER(p_name) AND t.Id <> p_tag_id
+    ) THEN
+        RETURN 'name_taken';
+    END IF;
+
+    UPDATE Tags t SET Name = p_name WHERE t.Id = p_tag_id;
+    RETURN 'renamed';
+END;
+$$ LANGUAGE plpgsql;
+
+-- Merge Tags
+-- Moves every note of the source tag onto the target tag and deletes the source.
+-- Status is one of: merged, not_found (either tag), same_tag.
+CREATE OR REPLACE FUNCTION sp_merge_tags(p_source_tag_id INT, p_target_tag_id INT, p_user_id INT)
+RETURNS TEXT AS $$
+BEGIN
+    IF p_source_tag_id = p_target_tag_id THEN
+        RETURN 'same_tag';
+    END IF;
+
+    IF (SELECT COUNT(*) FROM Tags t
+        WHERE t.Id IN (p_source_tag_id, p_target_tag_id) AND t.UserId = p_user_id) < 2 THEN
+        RETURN 'not_found';
+    END IF;
+
+    INSERT INTO NoteTags (NoteId, TagId)
+    SELECT nt.NoteId, p_target_tag_id
+    FROM NoteTags nt
+    WHERE nt.TagId = p_source_tag_id
+    ON CONFLICT DO NOTHING;
+
+    DELETE FROM Tags t WHERE t.Id = p_source_tag_id;
+    RETURN 'merged';
+END;
+$$ LANGUAGE plpgsql;
+
+-- Delete Tag
+-- Removes the tag from every note; the notes themselves stay
+CREATE OR REPLACE FUNCTION sp_delete_tag(p_tag_id INT, p_user_id INT)
+RETURNS INTEGER AS $$
+BEGIN
+    DELETE FROM Tags t
+    WHERE t.Id = p_tag_id AND t.UserId = p_user_id;
+
+    RETURN FOUND::INT;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Get Notebooks
+-- Every notebook of the user by name, with the number of notes directly inside each
+CREATE OR REPLACE FUNCTION sp_get_notebooks(p_user_id INT)
+RETURNS TABLE (Id INT, ParentId INT, Name VARCHAR, NoteCount BIGINT, CreatedAt TIMESTAMPTZ, UpdatedAt TIMESTAMPTZ) AS $$
+BEGIN
+    RETURN QUERY
+    SELECT nb.Id, nb.ParentId, nb.Name, COUNT(n.Id), nb.CreatedAt, nb.UpdatedAt
+    FROM Notebooks nb
+    LEFT JOIN Notes n ON n.NotebookId = nb.Id
+    WHERE nb.UserId = p_user_id
+    GROUP BY nb.Id
+    ORDER BY LOWER(nb.Name), nb.Id;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Create Notebook
+-- Returns no row when the parent is not one of the user's notebooks
+CREATE OR REPLACE FUNCTION sp_create_notebook(p_user_id INT, p_name VARCHAR, p_parent_id INT)
+RETURNS TABLE (Id INT, ParentId INT, Name VARCHAR, NoteCount BIGINT, CreatedAt TIMESTAMPTZ, UpdatedAt TIMESTAMPTZ) AS $$
+DECLARE
+    v_notebook_id INT;
+BEGIN
+    IF p_parent_id IS NOT NULL AND NOT EXISTS (
+        SELECT 1 FROM Notebooks nb WHERE nb.Id = p_parent_id AND nb.UserId = p_user_id
+    ) THEN
+        RETURN;
+    END IF;
+
+    INSERT INTO Notebooks (UserId, ParentId, Name)
+    VALUES (p_user_id, p_parent_id, p_name)
+    RETURNING Notebooks.Id INTO v_notebook_id;
+
+    RETURN QUERY
+    SELECT nb.Id, nb.ParentId, nb.Name, 0::BIGINT, nb.CreatedAt, nb.UpdatedAt
+    FROM Notebooks nb
+    WHERE nb.Id = v_notebook_id;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Rename Notebook
+CREATE OR REPLACE FUNCTION sp_rename_notebook(p_notebook_id INT, p_user_id INT, p_name VARCHAR)
+RETURNS INTEGER AS $$
+BEGIN
+    UPDATE Notebooks nb
+    SET Name = p_name,
+        UpdatedAt = CURRENT_TIMESTAMP AT TIME ZONE 'Asia/Bangkok'
+    WHERE nb.Id = p_notebook_id AND nb.UserId = p_user_id;
+
+    RETURN FOUND::INT;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Move Notebook
+-- Moves a notebook, with everything nested in it, below another notebook or to the top level
+-- (NULL p_parent_id). Status is one of: moved, not_found, parent_not_found, cycle (the parent
+-- is the notebook itself or nested inside it). Moves of one user are serialized so that two
+-- concurrent moves cannot form a cycle together.
+CREATE OR REPLACE FUNCTION sp_move_notebook(p_notebook_id INT, p_user_id INT, p_parent_id INT)
+RETURNS TEXT AS $$
+BEGIN
+    PERFORM 1 FROM Users u WHERE u.Id = p_user_id FOR UPDATE;
+
+    IF NOT EXISTS (SELECT 1 FROM Notebooks nb WHERE nb.Id = p_notebook_id AND nb.UserId = p_user_id) THEN
+        RETURN 'not_found';
+    END IF;
+
+    IF p_parent_id IS NOT NULL THEN
+        IF NOT EXISTS (SELECT 1 FROM Notebooks nb WHERE nb.Id = p_parent_id AND nb.UserId = p_user_id) THEN
+            RETURN 'parent_not_found';
+        END IF;
+
+        IF p_parent_id = ANY(sp_get_notebook_tree(p_notebook_id, p_user_id)) THEN
+            RETURN 'cycle';
+        END IF;
+    END IF;
+
+    UPDATE Notebooks nb
+    SET ParentId = p_parent_id,
+        UpdatedAt = CURRENT_TIMESTAMP AT TIME ZONE 'Asia/Bangkok'
+    WHERE nb.Id = p_notebook_id;
+
+    RETURN 'moved';
+END;
+$$ LANGUAGE plpgsql;
+
+-- Delete Notebook
+-- With p_cascade the nested notebooks and every note in them are deleted too. Otherwise the
+-- notebook's notes and child notebooks move up to its parent, or out of any notebook for a
+-- top-level notebook.
+CREATE OR REPLACE FUNCTION sp_delete_notebook(p_notebook_id INT, p_user_id INT, p_cascade BOOLEAN)
+RETURNS INTEGER AS $$
+DECLARE
+    v_parent_id INT;
+BEGIN
+    PERFORM 1 FROM Users u WHERE u.Id = p_user_id FOR UPDATE;
+
+    SELECT nb.ParentId INTO v_parent_id
+    FROM Notebooks nb
+    WHERE nb.Id = p_notebook_id AND nb.UserId = p_user_id;
+
+    IF NOT FOUND THEN
+        RETURN 0;
+    END IF;
+
+    IF p_cascade THEN
+        DELETE FROM Notes n
+        WHERE n.NotebookId = ANY(sp_get_notebook_tree(p_notebook_id, p_user_id));
+    ELSE
+        UPDATE Notes n
+        SET NotebookId = v_parent_id
+        WHERE n.NotebookId = p_notebook_id;
+
+        UPDATE Notebooks nb
+        SET ParentId = v_parent_id
+        WHERE nb.ParentId = p_notebook_id;
+    END IF;
+
+    -- When cascading, the nested notebooks go with it through their parent reference
+    DELETE FROM Notebooks nb WHERE nb.Id = p_notebook_id;
+
+    RETURN 1;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Get Shared Notes
+-- Notes other users shared with the user, most recently updated first, paged like sp_search_notes
+CREATE OR REPLACE FUNCTION sp_get_shared_notes(
+    p_user_id INT,
+    p_limit INT DEFAULT NULL,
+    p_after_updated_at TIMESTAMPTZ DEFAULT NULL,
+    p_after_id INT DEFAULT NULL
+)
+RETURNS TABLE (
+    Id INT, Title VARCHAR, Content TEXT, CreatedAt TIMESTAMPTZ, UpdatedAt TIMESTAMPTZ, Tags VARCHAR[],
+    NotebookId INT, OwnerId INT, Permission VARCHAR
+) AS $$
+BEGIN
+    RETURN QUERY
+    SELECT n.Id, n.Title, n.Content, n.CreatedAt, n.UpdatedAt,
+           ARRAY(SELECT t.Name FROM NoteTags nt JOIN Tags t ON t.Id = nt.TagId
+                 WHERE nt.NoteId = n.Id ORDER BY t.Name)::VARCHAR[],
+           NULL::INT,
+           n.UserId,
+           ns.Permission::VARCHAR
+    FROM NoteShares ns
+    JOIN Notes n ON n.Id = ns.NoteId
+    WHERE ns.UserId = p_user_id
+    AND (p_after_id IS NULL OR (n.UpdatedAt, n.Id) < (p_after_updated_at, p_after_id))
+    ORDER BY n.UpdatedAt DESC, n.Id DESC
+    LIMIT p_limit;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Get Note Shares
+-- Collaborators of a note, for its owner only
+CREATE OR REPLACE FUNCTION sp_get_note_shares(p_note_id INT, p_owner_id INT)
+RETURNS TABLE (UserId INT, Username VARCHAR, Permission VARCHAR, CreatedAt TIMESTAMPTZ) AS $$
+BEGIN
+    RETURN QUERY
+    SELECT u.Id, u.Username, ns.Permission, ns.CreatedAt
+    FROM NoteShares ns
+    JOIN Notes n ON n.Id = ns.NoteId
+    JOIN Users u ON u.Id = ns.UserId
+    WHERE ns.NoteId = p_note_id AND n.UserId = p_owner_id
+    ORDER BY u.Username;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Share Note
+-- Grants a user read or write access to the owner's note, or changes the access they have.
+-- Status is one of: shared, updated, note_not_found, user_not_found, own_note.
+CREATE OR REPLACE FUNCTION sp_share_note(p_note_id INT, p_owner_id INT, p_username VARCHAR, p_permission VARCHAR)
+RETURNS TABLE (Status TEXT, UserId INT, Username VARCHAR, Permission VARCHAR, CreatedAt TIMESTAMPTZ) AS $$
+DECLARE
+    v_user_id INT;
+    v_status TEXT;
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM Notes n WHERE n.Id = p_note_id AND n.UserId = p_owner_id) THEN
+        RETURN QUERY SELECT 'note_not_found'::TEXT, NULL::INT, NULL::VARCHAR, NULL::VARCHAR, NULL::TIMESTAMPTZ;
+        RETURN;
+    END IF;
+
+    SELECT u.Id INTO v_user_id FROM Users u WHERE u.Username = p_username;
+
+    IF v_user_id IS NULL THEN
+        RETURN QUERY SELECT 'user_not_found'::TEXT, NULL::INT, NULL::VARCHAR, NULL::VARCHAR, NULL::TIMESTAMPTZ;
+        RETURN;
+    END IF;
+
+    IF v_user_id = p_owner_id THEN
+        RETURN QUERY SELECT 'own_note'::TEXT, NULL::INT, NULL::VARCHAR, NULL::VARCHAR, NULL::TIMESTAMPTZ;
+        RETURN;
+    END IF;
+
+    UPDATE NoteShares ns
+    SET Permission = p_permission
+    WHERE ns.NoteId = p_note_id AND ns.UserId = v_user_id;
+
+    IF FOUND THEN
+        v_status := 'updated';
+    ELSE
+        INSERT INTO NoteShares (NoteId, UserId, Permission)
+        VALUES (p_note_id, v_user_id, p_permission)
+        ON CONFLICT DO NOTHING;
+        v_status := 'shared';
+    END IF;
+
+    RETURN QUERY
+    SELECT v_status, u.Id, u.Username, ns.Permission, ns.CreatedAt
+    FROM NoteShares ns
+    JOIN Users u ON u.Id = ns.UserId
+    WHERE ns.NoteId = p_note_id AND ns.UserId = v_user_id;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Unshare Note
+-- Revokes a user's access to a note. The owner can revoke anyone's access; collaborators can
+-- only leave a note themselves.
+CREATE OR REPLACE FUNCTION sp_unshare_note(p_note_id INT, p_user_id INT, p_caller_id INT)
+RETURNS INTEGER AS $$
+BEGIN
+    DELETE FROM NoteShares ns
+    WHERE ns.NoteId = p_note_id AND ns.UserId = p_user_id
+    AND (
+        ns.UserId = p_caller_id OR
+        EXISTS (SELECT 1 FROM Notes n WHERE n.Id = p_note_id AND n.UserId = p_caller_id)
+    );
+
+    RETURN FOUND::INT;
 END;
 $$ LANGUAGE plpgsql;
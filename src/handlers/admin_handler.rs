@@ -0,0 +1,390 @@
+use crate::models::admin_model::*;
+use crate::models::auth_model::ApiError;
+use crate::models::auth_event_model::{AdminSecurityEventQuery, SecurityEventListResponse};
+use crate::services::admin_service::AdminService;
+use crate::services::avatar_service::AvatarService;
+use crate::services::auth_event_service::{AuthEvent, AuthEventService, AuthEventType};
+use crate::services::database::DatabasePool;
+use crate::services::mailer::Mailer;
+use crate::services::storage::Storage;
+use crate::utils::client_device::ClientDevice;
+use axum::{
+    extract::{Path, Query, State, Extension},
+    http::StatusCode,
+    response::Json,
+};
+use std::sync::Arc;
+use validator::Validate;
+use tracing::{info, error, warn};
+
+/// List and search users
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/users",
+    params(
+        ("search" = Option<String>, Query, description = "Part of a username or email address"),
+        ("page" = Option<i64>, Query, description = "Page number, starting at 1"),
+        ("per_page" = Option<i64>, Query, description = "Users per page, at most 100 (default 20)")
+    ),
+    responses(
+        (status = 200, description = "A page of users, ordered by ID", body = AdminUserListResponse),
+        (status = 400, description = "Invalid query", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "Requires the users:admin permission", body = ApiError)
+    ),
+    tag = "admin",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_users(
+    State(db_pool): State<DatabasePool>,
+    Extension(current_user_id): Extension<i32>,
+    Query(query): Query<AdminUserQuery>,
+) -> Result<Json<AdminUserListResponse>, (StatusCode, Json<ApiError>)> {
+    info!("User {} is listing users with search: {:?}", current_user_id, query.search);
+    // Validate request
+    if let Err(errors) = query.validate() {
+        error!("Validation failed: {}", errors);
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ApiError {
+                error: "Validation Error".to_string(),
+                message: format!("Validation failed: {}", errors),
+            }),
+        ));
+    }
+
+    let admin_service = AdminService::new(db_pool);
+
+    match admin_service.get_users(query).await {
+        Ok(users) => Ok(Json(users)),
+        Err(err) => {
+            error!("Failed to list users: {}", err);
+            Err(admin_error("Failed to Fetch Users", err))
+        }
+    }
+}
+
+/// Get a user with their note count and last login
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/users/{id}",
+    params(
+        ("id" = i32, Path, description = "User ID")
+    ),
+    responses(
+        (status = 200, description = "User found", body = AdminUserDetailResponse),
+        (status = 404, description = "User not found", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "Requires the users:admin permission", body = ApiError)
+    ),
+    tag = "admin",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_user(
+    State(db_pool): State<DatabasePool>,
+    Path(user_id): Path<i32>,
+) -> Result<Json<AdminUserDetailResponse>, (StatusCode, Json<ApiError>)> {
+    let admin_service = AdminService::new(db_pool);
+
+    match admin_service.get_user(user_id).await {
+        Ok(Some(user)) => Ok(Json(user)),
+        Ok(None) => Err(user_not_found()),
+        Err(err) => {
+            error!("Failed to fetch user_id: {}: {}", user_id, err);
+            Err(admin_error("Failed to Fetch User", err))
+        }
+    }
+}
+
+/// Suspend a user
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/users/{id}/suspend",
+    params(
+        ("id" = i32, Path, description = "User ID")
+    ),
+    request_body(content = Option<SuspendUserRequest>, description = "Optional reason, visible to admins"),
+    responses(
+        (status = 204, description = "User suspended; their tokens are rejected from the next request on"),
+        (status = 400, description = "Invalid request, or an attempt to suspend yourself", body = ApiError),
+        (status = 404, description = "User not found", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "Requires the users:admin permission", body = ApiError)
+    ),
+    tag = "admin",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn suspend_user(
+    State(db_pool): State<DatabasePool>,
+    Path(user_id): Path<i32>,
+    Extension(current_user_id): Extension<i32>,
+    device: ClientDevice,
+    request: Option<Json<SuspendUserRequest>>,
+) -> Result<StatusCode, (StatusCode, Json<ApiError>)> {
+    info!("User {} is suspending user_id: {}", current_user_id, user_id);
+    let request = request.map(|Json(request)| request).unwrap_or(SuspendUserRequest { reason: None });
+    // Validate request
+    if let Err(errors) = request.validate() {
+        error!("Validation failed: {}", errors);
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ApiError {
+                error: "Validation Error".to_string(),
+                message: format!("Validation failed: {}", errors),
+            }),
+        ));
+    }
+    reject_self(current_user_id, user_id, "suspend")?;
+
+    let admin_service = AdminService::new(db_pool.clone());
+    let reason = match &request.reason {
+        Some(reason) => format!("by user {}: {}", current_user_id, reason),
+        None => format!("by user {}", current_user_id),
+    };
+
+    match admin_service.suspend_user(user_id, request.reason).await {
+        Ok(true) => {
+            warn!("User {} suspended user_id: {}", current_user_id, user_id);
+            let event = AuthEvent::succeeded(AuthEventType::AccountSuspended, user_id).with_reason(reason);
+            AuthEventService::new(db_pool).record(event, &device).await;
+            Ok(StatusCode::NO_CONTENT)
+        },
+        Ok(false) => Err(user_not_found()),
+        Err(err) => {
+            error!("Failed to suspend user_id: {}: {}", user_id, err);
+            Err(admin_error("Suspension Failed", err))
+        }
+    }
+}
+
+/// Lift a user's suspension
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/users/{id}/unsuspend",
+    params(
+        ("id" = i32, Path, description = "User ID")
+    ),
+    responses(
+        (status = 204, description = "User can log in again"),
+        (status = 404, description = "User not found", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "Requires the users:admin permission", body = ApiError)
+    ),
+    tag = "admin",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn unsuspend_user(
+    State(db_pool): State<DatabasePool>,
+    Path(user_id): Path<i32>,
+    Extension(current_user_id): Extension<i32>,
+    device: ClientDevice,
+) -> Result<StatusCode, (StatusCode, Json<ApiError>)> {
+    info!("User {} is lifting the suspension of user_id: {}", current_user_id, user_id);
+    let admin_service = AdminService::new(db_pool.clone());
+
+    match admin_service.unsuspend_user(user_id).await {
+        Ok(true) => {
+            let event = AuthEvent::succeeded(AuthEventType::AccountUnsuspended, user_id)
+                .with_reason(format!("by user {}", current_user_id));
+            AuthEventService::new(db_pool).record(event, &device).await;
+            Ok(StatusCode::NO_CONTENT)
+        },
+        Ok(false) => Err(user_not_found()),
+        Err(err) => {
+            error!("Failed to unsuspend user_id: {}: {}", user_id, err);
+            Err(admin_error("Unsuspension Failed", err))
+        }
+    }
+}
+
+/// Force a user to choose a new password
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/users/{id}/force-password-reset",
+    params(
+        ("id" = i32, Path, description = "User ID")
+    ),
+    responses(
+        (status = 204, description = "Password disabled, every session signed out, personal access tokens revoked and a reset link emailed to the user"),
+        (status = 404, description = "User not found", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "Requires the users:admin permission", body = ApiError)
+    ),
+    tag = "admin",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn force_password_reset(
+    State(db_pool): State<DatabasePool>,
+    State(mailer): State<Arc<dyn Mailer>>,
+    Path(user_id): Path<i32>,
+    Extension(current_user_id): Extension<i32>,
+    device: ClientDevice,
+) -> Result<StatusCode, (StatusCode, Json<ApiError>)> {
+    info!("User {} is forcing a password reset for user_id: {}", current_user_id, user_id);
+    let admin_service = AdminService::new(db_pool.clone());
+
+    match admin_service.force_password_reset(user_id, mailer).await {
+        Ok(true) => {
+            warn!("User {} forced a password reset for user_id: {}", current_user_id, user_id);
+            let event = AuthEvent::succeeded(AuthEventType::PasswordResetForced, user_id)
+                .with_reason(format!("by user {}", current_user_id));
+            AuthEventService::new(db_pool).record(event, &device).await;
+            Ok(StatusCode::NO_CONTENT)
+        },
+        Ok(false) => Err(user_not_found()),
+        Err(err) => {
+            error!("Failed to force a password reset for user_id: {}: {}", user_id, err);
+            Err(admin_error("Password Reset Failed", err))
+        }
+    }
+}
+
+/// Delete a user and everything they own
+#[utoipa::path(
+    delete,
+    path = "/api/v1/admin/users/{id}",
+    params(
+        ("id" = i32, Path, description = "User ID")
+    ),
+    responses(
+        (status = 204, description = "User deleted with their notes, tokens and sessions"),
+        (status = 400, description = "An attempt to delete yourself", body = ApiError),
+        (status = 404, description = "User not found", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "Requires the users:admin permission", body = ApiError)
+    ),
+    tag = "admin",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn delete_user(
+    State(db_pool): State<DatabasePool>,
+    State(storage): State<Arc<dyn Storage>>,
+    Path(user_id): Path<i32>,
+    Extension(current_user_id): Extension<i32>,
+    device: ClientDevice,
+) -> Result<StatusCode, (StatusCode, Json<ApiError>)> {
+    info!("User {} is deleting user_id: {}", current_user_id, user_id);
+    reject_self(current_user_id, user_id, "delete")?;
+
+    let admin_service = AdminService::new(db_pool.clone());
+
+    match admin_service.delete_user(user_id).await {
+        Ok(true) => {
+            warn!("User {} deleted user_id: {}", current_user_id, user_id);
+            // Without the email: the account's events are anonymized along with it
+            let event = AuthEvent::succeeded(AuthEventType::AccountDeleted, user_id)
+                .with_reason(format!("by user {}", current_user_id));
+            AuthEventService::new(db_pool.clone()).record(event, &device).await;
+            if let Err(err) = AvatarService::new(db_pool, storage).delete_user_files(user_id).await {
+                error!("Failed to delete the avatars of user_id: {}: {}", user_id, err);
+            }
+            Ok(StatusCode::NO_CONTENT)
+        },
+        Ok(false) => Err(user_not_found()),
+        Err(err) => {
+            error!("Failed to delete user_id: {}: {}", user_id, err);
+            Err(admin_error("Deletion Failed", err))
+        }
+    }
+}
+
+/// Query the security event log of every user
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/security-events",
+    params(
+        ("user_id" = Option<i32>, Query, description = "Only events of this user"),
+        ("event_type" = Option<String>, Query, description = "Only events of this type, e.g. `login`"),
+        ("success" = Option<bool>, Query, description = "Only successful (`true`) or failed (`false`) events"),
+        ("page" = Option<i64>, Query, description = "Page number, starting at 1"),
+        ("per_page" = Option<i64>, Query, description = "Events per page, at most 100 (default 20)")
+    ),
+    responses(
+        (status = 200, description = "A page of events, newest first", body = SecurityEventListResponse),
+        (status = 400, description = "Invalid query", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "Requires the users:admin permission", body = ApiError)
+    ),
+    tag = "admin",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_security_events(
+    State(db_pool): State<DatabasePool>,
+    Extension(current_user_id): Extension<i32>,
+    Query(query): Query<AdminSecurityEventQuery>,
+) -> Result<Json<SecurityEventListResponse>, (StatusCode, Json<ApiError>)> {
+    info!("User {} is querying the security event log", current_user_id);
+    // Validate query
+    if let Err(errors) = query.validate() {
+        error!("Validation failed: {}", errors);
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ApiError {
+                error: "Validation Error".to_string(),
+                message: format!("Validation failed: {}", errors),
+            }),
+        ));
+    }
+
+    let auth_event_service = AuthEventService::new(db_pool);
+
+    match auth_event_service
+        .get_events(query.user_id, query.event_type, query.success, query.page, query.per_page)
+        .await
+    {
+        Ok(response) => Ok(Json(response)),
+        Err(err) => {
+            error!("Failed to query the security event log: {}", err);
+            Err(admin_error("Failed to Get Security Events", err))
+        }
+    }
+}
+
+// Admins locking themselves out could leave nobody able to undo it
+fn reject_self(current_user_id: i32, user_id: i32, action: &str) -> Result<(), (StatusCode, Json<ApiError>)> {
+    if current_user_id == user_id {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ApiError {
+                error: "Invalid Target".to_string(),
+                message: format!("You cannot {} your own account", action),
+            }),
+        ));
+    }
+
+    Ok(())
+}
+
+fn user_not_found() -> (StatusCode, Json<ApiError>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(ApiError {
+            error: "User Not Found".to_string(),
+            message: "User with the specified ID was not found".to_string(),
+        }),
+    )
+}
+
+fn admin_error(error: &str, err: anyhow::Error) -> (StatusCode, Json<ApiError>) {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ApiError {
+            error: error.to_string(),
+            message: err.to_string(),
+        }),
+    )
+}
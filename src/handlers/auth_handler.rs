@@ -1,30 +1,45 @@
 use crate::models::auth_model::*;
+use crate::services::auth_event_service::{AuthEvent, AuthEventService, AuthEventType};
 use crate::services::auth_service::AuthService;
 use crate::services::database::DatabasePool;
+use crate::services::email_verification_service::{EmailVerificationService, VerificationResendLocked};
+use crate::services::login_throttle_service::LoginLocked;
+use crate::services::mailer::Mailer;
+use crate::utils::auth_cookies::{clear_auth_cookies, cookie_value, csrf_token_valid, TokenDelivery, REFRESH_TOKEN_COOKIE};
+use crate::utils::client_device::ClientDevice;
+use crate::utils::jwt;
+use crate::utils::password_policy::PasswordPolicyViolations;
 use axum::{
-    extract::State,
-    http::StatusCode,
-    response::Json,
+    extract::{Extension, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Json, Response},
 };
+use std::sync::Arc;
 use validator::Validate;
-use tracing::{info, error};
+use tracing::{info, error, warn};
 
 /// Register a new user
 #[utoipa::path(
     post,
     path = "/api/v1/auth/register",
+    params(
+        ("X-Auth-Mode" = Option<String>, Header, description = "`cookie` to receive the tokens as HttpOnly cookies instead of in the body")
+    ),
     request_body = RegisterRequest,
     responses(
         (status = 200, description = "User registered successfully", body = AuthResponse),
-        (status = 400, description = "Invalid request", body = ApiError),
+        (status = 400, description = "Invalid request, or the password breaks the password policy", body = PasswordPolicyError),
         (status = 409, description = "User already exists", body = ApiError)
     ),
     tag = "auth"
 )]
 pub async fn register(
     State(db_pool): State<DatabasePool>,
+    State(mailer): State<Arc<dyn Mailer>>,
+    device: ClientDevice,
+    delivery: TokenDelivery,
     Json(request): Json<RegisterRequest>,
-) -> Result<Json<AuthResponse>, (StatusCode, Json<ApiError>)> {
+) -> Result<Response, Response> {
     info!("Attempting to register a new user with email: {}", request.email);
     // Validate request
     if let Err(errors) = request.validate() {
@@ -35,18 +50,26 @@ pub async fn register(
                 error: "Validation Error".to_string(),
                 message: format!("Validation failed: {}", errors),
             }),
-        ));
+        ).into_response());
     }
 
-    let auth_service = AuthService::new(db_pool);
-    
-    match auth_service.register_user(request).await {
+    let auth_service = AuthService::new(db_pool.clone());
+    let events = AuthEventService::new(db_pool);
+    let email = request.email.clone();
+
+    match auth_service.register_user(request, mailer, &device).await {
         Ok(response) => {
             info!("Successfully registered user with email: {}", response.email);
-            Ok(Json(response))
+            events.record(AuthEvent::succeeded(AuthEventType::Register, response.user_id), &device).await;
+            Ok(delivery.auth_response(response))
         },
         Err(err) => {
             error!("Failed to register user: {}", err);
+            events.record(AuthEvent::failed(AuthEventType::Register, &err).for_email(&email), &device).await;
+            if let Some(response) = password_policy_error(&err) {
+                return Err(response);
+            }
+
             let status_code = if err.to_string().contains("duplicate") {
                 StatusCode::CONFLICT
             } else {
@@ -59,7 +82,7 @@ pub async fn register(
                     error: "Registration Failed".to_string(),
                     message: err.to_string(),
                 }),
-            ))
+            ).into_response())
         }
     }
 }
@@ -68,18 +91,25 @@ pub async fn register(
 #[utoipa::path(
     post,
     path = "/api/v1/auth/login",
+    params(
+        ("X-Auth-Mode" = Option<String>, Header, description = "`cookie` to receive the tokens as HttpOnly cookies instead of in the body")
+    ),
     request_body = LoginRequest,
     responses(
-        (status = 200, description = "User logged in successfully", body = AuthResponse),
+        (status = 200, description = "User logged in successfully, or a two-factor challenge when 2FA is enabled", body = LoginResponse),
         (status = 400, description = "Invalid request", body = ApiError),
-        (status = 401, description = "Invalid credentials", body = ApiError)
+        (status = 401, description = "Invalid email or password", body = ApiError),
+        (status = 403, description = "Account suspended", body = ApiError),
+        (status = 429, description = "Too many failed attempts; see the Retry-After header", body = ApiError)
     ),
     tag = "auth"
 )]
 pub async fn login(
     State(db_pool): State<DatabasePool>,
+    device: ClientDevice,
+    delivery: TokenDelivery,
     Json(request): Json<LoginRequest>,
-) -> Result<Json<AuthResponse>, (StatusCode, Json<ApiError>)> {
+) -> Result<Response, Response> {
     info!("Login request received for email: {}", request.email);
     // Validate request
     if let Err(errors) = request.validate() {
@@ -90,31 +120,529 @@ pub async fn login(
                 error: "Validation Error".to_string(),
                 message: format!("Validation failed: {}", errors),
             }),
-        ));
+        ).into_response());
     }
 
-    let auth_service = AuthService::new(db_pool);
+    let auth_service = AuthService::new(db_pool.clone());
+    let events = AuthEventService::new(db_pool);
     
-    match auth_service.login_user(request).await {
+    let email = request.email.clone();
+
+    match auth_service.login_user(request, &device).await {
+        Ok(response) => {
+            match &response {
+                LoginResponse::Authenticated(response) => {
+                    info!("Successfully logged in user with email: {}", email);
+                    events.record(AuthEvent::succeeded(AuthEventType::Login, response.user_id), &device).await;
+                },
+                // The login is recorded once the second factor is checked
+                LoginResponse::TwoFactorRequired(_) => info!("Two-factor challenge issued for email: {}", email),
+            }
+            Ok(delivery.login_response(response))
+        },
+        Err(err) => {
+            warn!("Failed to login user with email: {} from {}: {}", email, device.ip, err);
+            events.record(AuthEvent::failed(AuthEventType::Login, &err).for_email(&email), &device).await;
+            let status_code = if err.to_string().contains("Invalid email or password") {
+                StatusCode::UNAUTHORIZED
+            } else if err.to_string().contains("Account suspended") {
+                StatusCode::FORBIDDEN
+            } else {
+                StatusCode::INTERNAL_SERVER_ERROR
+            };
+
+            Err(login_error(status_code, err))
+        }
+    }
+}
+
+/// Complete a login with a two-factor code
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/login/2fa",
+    params(
+        ("X-Auth-Mode" = Option<String>, Header, description = "`cookie` to receive the tokens as HttpOnly cookies instead of in the body")
+    ),
+    request_body = TwoFactorLoginRequest,
+    responses(
+        (status = 200, description = "User logged in successfully", body = AuthResponse),
+        (status = 400, description = "Invalid request", body = ApiError),
+        (status = 401, description = "Invalid or expired challenge, or invalid code", body = ApiError),
+        (status = 403, description = "Account suspended", body = ApiError),
+        (status = 429, description = "Too many failed attempts; see the Retry-After header", body = ApiError)
+    ),
+    tag = "auth"
+)]
+pub async fn login_two_factor(
+    State(db_pool): State<DatabasePool>,
+    device: ClientDevice,
+    delivery: TokenDelivery,
+    Json(request): Json<TwoFactorLoginRequest>,
+) -> Result<Response, Response> {
+    info!("Two-factor login attempt received");
+    // Validate request
+    if let Err(errors) = request.validate() {
+        error!("Validation failed: {}", errors);
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ApiError {
+                error: "Validation Error".to_string(),
+                message: format!("Validation failed: {}", errors),
+            }),
+        ).into_response());
+    }
+
+    let auth_service = AuthService::new(db_pool.clone());
+    let events = AuthEventService::new(db_pool);
+
+    match auth_service.complete_two_factor_login(request, &device).await {
         Ok(response) => {
             info!("Successfully logged in user with email: {}", response.email);
-            Ok(Json(response))
+            events.record(AuthEvent::succeeded(AuthEventType::TwoFactorLogin, response.user_id), &device).await;
+            Ok(delivery.auth_response(response))
         },
         Err(err) => {
-            error!("Failed to login user: {}", err);
-            let status_code = if err.to_string().contains("Invalid credentials") || err.to_string().contains("User not found") {
+            warn!("Failed to complete two-factor login from {}: {}", device.ip, err);
+            events.record(AuthEvent::failed(AuthEventType::TwoFactorLogin, &err), &device).await;
+            let message = err.to_string();
+            let status_code = if message.contains("challenge token")
+                || message.contains("two-factor")
+                || message.contains("Two-factor")
+                || message.contains("User not found")
+            {
                 StatusCode::UNAUTHORIZED
+            } else if message.contains("Account suspended") {
+                StatusCode::FORBIDDEN
             } else {
                 StatusCode::INTERNAL_SERVER_ERROR
             };
-            
+
+            Err(login_error(status_code, err))
+        }
+    }
+}
+
+// Lockouts become 429 with a Retry-After header; everything else keeps the given status
+pub(crate) fn login_error(status_code: StatusCode, err: anyhow::Error) -> Response {
+    if let Some(locked) = err.downcast_ref::<LoginLocked>() {
+        return (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(header::RETRY_AFTER, locked.retry_after_seconds.to_string())],
+            Json(ApiError {
+                error: "Too Many Attempts".to_string(),
+                message: err.to_string(),
+            }),
+        ).into_response();
+    }
+
+    (
+        status_code,
+        Json(ApiError {
+            error: "Login Failed".to_string(),
+            message: err.to_string(),
+        }),
+    ).into_response()
+}
+
+/// Refresh access token
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/refresh",
+    params(
+        ("X-Auth-Mode" = Option<String>, Header, description = "`cookie` to receive the tokens as HttpOnly cookies instead of in the body")
+    ),
+    request_body(content = Option<RefreshRequest>, description = "The refresh token; cookie-mode clients send none and the refresh token cookie is used instead"),
+    responses(
+        (status = 200, description = "Tokens refreshed successfully", body = AuthResponse),
+        (status = 400, description = "Invalid request, or no refresh token", body = ApiError),
+        (status = 401, description = "Invalid, expired or reused refresh token", body = ApiError),
+        (status = 403, description = "Account suspended, or a missing or mismatched CSRF token in cookie mode", body = ApiError)
+    ),
+    tag = "auth"
+)]
+pub async fn refresh(
+    State(db_pool): State<DatabasePool>,
+    device: ClientDevice,
+    headers: HeaderMap,
+    delivery: TokenDelivery,
+    request: Option<Json<RefreshRequest>>,
+) -> Result<Response, (StatusCode, Json<ApiError>)> {
+    info!("Refresh token request received");
+    let (request, delivery) = match request {
+        Some(Json(request)) => (request, delivery),
+        // Cookie mode: the refresh token comes from its cookie and the new tokens go back in cookies
+        None => {
+            let Some(refresh_token) = cookie_value(&headers, REFRESH_TOKEN_COOKIE) else {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    Json(ApiError {
+                        error: "Validation Error".to_string(),
+                        message: "No refresh token in the body or cookies".to_string(),
+                    }),
+                ));
+            };
+            if !csrf_token_valid(&headers) {
+                warn!("Rejected cookie refresh with a missing or mismatched CSRF token");
+                return Err((
+                    StatusCode::FORBIDDEN,
+                    Json(ApiError {
+                        error: "CSRF Token Mismatch".to_string(),
+                        message: "Send the csrf_token cookie value in the X-CSRF-Token header".to_string(),
+                    }),
+                ));
+            }
+            (RefreshRequest { refresh_token: refresh_token.to_string() }, TokenDelivery::Cookies)
+        }
+    };
+    // Validate request
+    if let Err(errors) = request.validate() {
+        error!("Validation failed: {}", errors);
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ApiError {
+                error: "Validation Error".to_string(),
+                message: format!("Validation failed: {}", errors),
+            }),
+        ));
+    }
+
+    let auth_service = AuthService::new(db_pool.clone());
+    let events = AuthEventService::new(db_pool);
+
+    match auth_service.refresh_tokens(request, device.ip).await {
+        Ok(response) => {
+            info!("Successfully refreshed tokens for user_id: {}", response.user_id);
+            events.record(AuthEvent::succeeded(AuthEventType::TokenRefresh, response.user_id), &device).await;
+            Ok(delivery.auth_response(response))
+        },
+        Err(err) => {
+            error!("Failed to refresh tokens: {}", err);
+            events.record(AuthEvent::failed(AuthEventType::TokenRefresh, &err), &device).await;
+            let message = err.to_string();
+            let status_code = if message.contains("refresh token")
+                || message.contains("Refresh token")
+                || message.contains("User not found")
+            {
+                StatusCode::UNAUTHORIZED
+            } else if message.contains("Account suspended") {
+                StatusCode::FORBIDDEN
+            } else {
+                StatusCode::INTERNAL_SERVER_ERROR
+            };
+
             Err((
                 status_code,
                 Json(ApiError {
-                    error: "Login Failed".to_string(),
+                    error: "Token Refresh Failed".to_string(),
+                    message,
+                }),
+            ))
+        }
+    }
+}
+
+/// Logout user
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/logout",
+    request_body(content = Option<LogoutRequest>, description = "Optional refresh token to revoke, or `all_devices` to invalidate every token of the user"),
+    responses(
+        (status = 204, description = "User logged out successfully; authentication cookies are cleared"),
+        (status = 401, description = "Unauthorized", body = ApiError)
+    ),
+    tag = "auth",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn logout(
+    State(db_pool): State<DatabasePool>,
+    Extension(context): Extension<AuthContext>,
+    device: ClientDevice,
+    headers: HeaderMap,
+    request: Option<Json<LogoutRequest>>,
+) -> Result<Response, (StatusCode, Json<ApiError>)> {
+    info!("Logout request received for user_id: {}", context.user_id);
+    let mut request = request.map(|Json(request)| request).unwrap_or(LogoutRequest {
+        refresh_token: None,
+        all_devices: false,
+    });
+    if request.refresh_token.is_none() {
+        request.refresh_token = cookie_value(&headers, REFRESH_TOKEN_COOKIE).map(str::to_string);
+    }
+
+    let auth_service = AuthService::new(db_pool.clone());
+    let all_devices = request.all_devices;
+
+    match auth_service.logout(&context, request).await {
+        Ok(()) => {
+            info!("Successfully logged out user_id: {}", context.user_id);
+            let event = AuthEvent::succeeded(AuthEventType::Logout, context.user_id);
+            let event = if all_devices { event.with_reason("all devices") } else { event };
+            AuthEventService::new(db_pool).record(event, &device).await;
+            let mut response = StatusCode::NO_CONTENT.into_response();
+            clear_auth_cookies(response.headers_mut());
+            Ok(response)
+        },
+        Err(err) => {
+            error!("Failed to logout user_id: {}: {}", context.user_id, err);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiError {
+                    error: "Logout Failed".to_string(),
                     message: err.to_string(),
                 }),
             ))
         }
     }
-}
\ No newline at end of file
+}
+
+/// Request a password reset email
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/forgot-password",
+    request_body = ForgotPasswordRequest,
+    responses(
+        (status = 202, description = "A reset link was sent if the email belongs to an account"),
+        (status = 400, description = "Invalid request", body = ApiError)
+    ),
+    tag = "auth"
+)]
+pub async fn forgot_password(
+    State(db_pool): State<DatabasePool>,
+    State(mailer): State<Arc<dyn Mailer>>,
+    Json(request): Json<ForgotPasswordRequest>,
+) -> Result<StatusCode, (StatusCode, Json<ApiError>)> {
+    info!("Password reset requested");
+    // Validate request
+    if let Err(errors) = request.validate() {
+        error!("Validation failed: {}", errors);
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ApiError {
+                error: "Validation Error".to_string(),
+                message: format!("Validation failed: {}", errors),
+            }),
+        ));
+    }
+
+    let auth_service = AuthService::new(db_pool);
+
+    match auth_service.request_password_reset(request, mailer).await {
+        Ok(()) => Ok(StatusCode::ACCEPTED),
+        Err(err) => {
+            error!("Failed to create password reset token: {}", err);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiError {
+                    error: "Password Reset Failed".to_string(),
+                    message: err.to_string(),
+                }),
+            ))
+        }
+    }
+}
+
+/// Reset password with a token from the reset email
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/reset-password",
+    request_body = ResetPasswordRequest,
+    responses(
+        (status = 204, description = "Password reset successfully"),
+        (status = 400, description = "Invalid request, invalid/expired token, or the password breaks the password policy", body = PasswordPolicyError)
+    ),
+    tag = "auth"
+)]
+pub async fn reset_password(
+    State(db_pool): State<DatabasePool>,
+    device: ClientDevice,
+    Json(request): Json<ResetPasswordRequest>,
+) -> Result<StatusCode, Response> {
+    info!("Password reset attempt received");
+    // Validate request
+    if let Err(errors) = request.validate() {
+        error!("Validation failed: {}", errors);
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ApiError {
+                error: "Validation Error".to_string(),
+                message: format!("Validation failed: {}", errors),
+            }),
+        ).into_response());
+    }
+
+    let auth_service = AuthService::new(db_pool.clone());
+    let events = AuthEventService::new(db_pool);
+
+    match auth_service.reset_password(request).await {
+        Ok(user_id) => {
+            info!("Password reset successfully for user_id: {}", user_id);
+            events.record(AuthEvent::succeeded(AuthEventType::PasswordReset, user_id), &device).await;
+            Ok(StatusCode::NO_CONTENT)
+        },
+        Err(err) => {
+            error!("Failed to reset password: {}", err);
+            events.record(AuthEvent::failed(AuthEventType::PasswordReset, &err), &device).await;
+            if let Some(response) = password_policy_error(&err) {
+                return Err(response);
+            }
+
+            let status_code = if err.to_string().contains("Invalid or expired reset token") {
+                StatusCode::BAD_REQUEST
+            } else {
+                StatusCode::INTERNAL_SERVER_ERROR
+            };
+
+            Err((
+                status_code,
+                Json(ApiError {
+                    error: "Password Reset Failed".to_string(),
+                    message: err.to_string(),
+                }),
+            ).into_response())
+        }
+    }
+}
+
+// Policy violations are reported per rule, so clients can point at what to fix
+pub(crate) fn password_policy_error(err: &anyhow::Error) -> Option<Response> {
+    let violations = err.downcast_ref::<PasswordPolicyViolations>()?;
+
+    Some((
+        StatusCode::BAD_REQUEST,
+        Json(PasswordPolicyError {
+            error: "Password Policy Violation".to_string(),
+            message: err.to_string(),
+            violations: violations.0.clone(),
+        }),
+    ).into_response())
+}
+
+/// Verify email address
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/verify-email",
+    request_body = VerifyEmailRequest,
+    responses(
+        (status = 204, description = "Email address verified successfully"),
+        (status = 400, description = "Invalid request or invalid/expired token", body = ApiError)
+    ),
+    tag = "auth"
+)]
+pub async fn verify_email(
+    State(db_pool): State<DatabasePool>,
+    device: ClientDevice,
+    Json(request): Json<VerifyEmailRequest>,
+) -> Result<StatusCode, (StatusCode, Json<ApiError>)> {
+    info!("Email verification attempt received");
+    // Validate request
+    if let Err(errors) = request.validate() {
+        error!("Validation failed: {}", errors);
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ApiError {
+                error: "Validation Error".to_string(),
+                message: format!("Validation failed: {}", errors),
+            }),
+        ));
+    }
+
+    let verification_service = EmailVerificationService::new(db_pool);
+
+    match verification_service.verify_email(&request.token, &device).await {
+        Ok(()) => {
+            info!("Email address verified successfully");
+            Ok(StatusCode::NO_CONTENT)
+        },
+        Err(err) => {
+            error!("Failed to verify email address: {}", err);
+            let status_code = if err.to_string().contains("Invalid or expired verification token") {
+                StatusCode::BAD_REQUEST
+            } else {
+                StatusCode::INTERNAL_SERVER_ERROR
+            };
+
+            Err((
+                status_code,
+                Json(ApiError {
+                    error: "Email Verification Failed".to_string(),
+                    message: err.to_string(),
+                }),
+            ))
+        }
+    }
+}
+
+/// Resend the email verification link
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/resend-verification",
+    request_body = ResendVerificationRequest,
+    responses(
+        (status = 202, description = "A new link was sent if the email belongs to an unverified account"),
+        (status = 400, description = "Invalid request", body = ApiError),
+        (status = 429, description = "Too many requests for this address or from this client; see the Retry-After header", body = ApiError)
+    ),
+    tag = "auth"
+)]
+pub async fn resend_verification(
+    State(db_pool): State<DatabasePool>,
+    State(mailer): State<Arc<dyn Mailer>>,
+    device: ClientDevice,
+    Json(request): Json<ResendVerificationRequest>,
+) -> Result<StatusCode, Response> {
+    info!("Verification email resend requested");
+    // Validate request
+    if let Err(errors) = request.validate() {
+        error!("Validation failed: {}", errors);
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ApiError {
+                error: "Validation Error".to_string(),
+                message: format!("Validation failed: {}", errors),
+            }),
+        ).into_response());
+    }
+
+    let verification_service = EmailVerificationService::new(db_pool);
+
+    match verification_service.resend_verification(&request.email, &device, mailer).await {
+        Ok(()) => Ok(StatusCode::ACCEPTED),
+        Err(err) => {
+            error!("Failed to resend verification email: {}", err);
+            if let Some(locked) = err.downcast_ref::<VerificationResendLocked>() {
+                return Err((
+                    StatusCode::TOO_MANY_REQUESTS,
+                    [(header::RETRY_AFTER, locked.retry_after_seconds.to_string())],
+                    Json(ApiError {
+                        error: "Too Many Requests".to_string(),
+                        message: err.to_string(),
+                    }),
+                ).into_response());
+            }
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiError {
+                    error: "Verification Resend Failed".to_string(),
+                    message: err.to_string(),
+                }),
+            ).into_response())
+        }
+    }
+}
+
+/// Get the public keys used to verify access tokens
+#[utoipa::path(
+    get,
+    path = "/.well-known/jwks.json",
+    responses(
+        (status = 200, description = "JSON Web Key Set with every active verification key", body = JwksResponse)
+    ),
+    tag = "auth"
+)]
+pub async fn jwks() -> Json<JwksResponse> {
+    Json(JwksResponse {
+        keys: jwt::public_keys().to_vec(),
+    })
+}
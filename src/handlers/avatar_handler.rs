@@ -0,0 +1,199 @@
+use crate::models::auth_model::ApiError;
+use crate::models::users_model::{AvatarUploadRequest, UserResponse};
+use crate::services::avatar_service::AvatarService;
+use crate::services::database::DatabasePool;
+use crate::services::storage::Storage;
+use crate::services::user_service::UserService;
+use axum::{
+    extract::{Extension, Multipart, Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Json, Response},
+};
+use std::sync::Arc;
+use tracing::{info, error, warn};
+use uuid::Uuid;
+
+/// Upload an avatar for the current user
+#[utoipa::path(
+    put,
+    path = "/api/v1/users/me/avatar",
+    request_body(content = AvatarUploadRequest, content_type = "multipart/form-data"),
+    responses(
+        (status = 200, description = "Avatar replaced; the user with the URLs of the new thumbnails", body = UserResponse),
+        (status = 400, description = "No avatar field, or the file is not a valid image", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "Personal access tokens cannot change the avatar", body = ApiError),
+        (status = 413, description = "The image is larger than AVATAR_MAX_BYTES, 4096x4096 pixels or 64 MiB once decoded", body = ApiError),
+        (status = 415, description = "The file is not a PNG, JPEG, GIF or WebP image", body = ApiError)
+    ),
+    tag = "users",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn upload_avatar(
+    State(db_pool): State<DatabasePool>,
+    State(storage): State<Arc<dyn Storage>>,
+    Extension(user_id): Extension<i32>,
+    mut multipart: Multipart,
+) -> Result<Json<UserResponse>, (StatusCode, Json<ApiError>)> {
+    info!("Avatar upload received for user_id: {}", user_id);
+    let data = loop {
+        match multipart.next_field().await {
+            Ok(Some(field)) if field.name() == Some("avatar") => match field.bytes().await {
+                Ok(data) => break data,
+                Err(err) => return Err(multipart_error(err)),
+            },
+            Ok(Some(_)) => continue,
+            Ok(None) => {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    Json(ApiError {
+                        error: "Validation Error".to_string(),
+                        message: "Send the image in an `avatar` form field".to_string(),
+                    }),
+                ));
+            }
+            Err(err) => return Err(multipart_error(err)),
+        }
+    };
+
+    let avatar_service = AvatarService::new(db_pool.clone(), storage);
+
+    if let Err(err) = avatar_service.upload(user_id, data.to_vec()).await {
+        warn!("Failed to upload avatar for user_id: {}: {}", user_id, err);
+        let message = err.to_string();
+        let status_code = if message.contains("at most") {
+            StatusCode::PAYLOAD_TOO_LARGE
+        } else if message.contains("Unsupported image type") {
+            StatusCode::UNSUPPORTED_MEDIA_TYPE
+        } else if message.contains("Invalid image") {
+            StatusCode::BAD_REQUEST
+        } else {
+            StatusCode::INTERNAL_SERVER_ERROR
+        };
+
+        return Err((
+            status_code,
+            Json(ApiError {
+                error: "Avatar Upload Failed".to_string(),
+                message,
+            }),
+        ));
+    }
+
+    info!("Avatar replaced for user_id: {}", user_id);
+    match UserService::new(db_pool).get_user_by_id(user_id).await {
+        Ok(Some(user)) => Ok(Json(user)),
+        Ok(None) => Err(internal_error("User not found".to_string())),
+        Err(err) => {
+            error!("Failed to retrieve user with id: {}: {}", user_id, err);
+            Err(internal_error(err.to_string()))
+        }
+    }
+}
+
+/// Remove the avatar of the current user
+#[utoipa::path(
+    delete,
+    path = "/api/v1/users/me/avatar",
+    responses(
+        (status = 204, description = "Avatar removed"),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "Personal access tokens cannot change the avatar", body = ApiError),
+        (status = 404, description = "The user has no avatar", body = ApiError)
+    ),
+    tag = "users",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn delete_avatar(
+    State(db_pool): State<DatabasePool>,
+    State(storage): State<Arc<dyn Storage>>,
+    Extension(user_id): Extension<i32>,
+) -> Result<StatusCode, (StatusCode, Json<ApiError>)> {
+    info!("Avatar removal requested for user_id: {}", user_id);
+    let avatar_service = AvatarService::new(db_pool, storage);
+
+    match avatar_service.remove(user_id).await {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err((
+            StatusCode::NOT_FOUND,
+            Json(ApiError {
+                error: "Avatar Not Found".to_string(),
+                message: "You have no avatar".to_string(),
+            }),
+        )),
+        Err(err) => {
+            error!("Failed to remove avatar of user_id: {}: {}", user_id, err);
+            Err(internal_error(err.to_string()))
+        }
+    }
+}
+
+/// Get an avatar thumbnail
+#[utoipa::path(
+    get,
+    path = "/api/v1/avatars/{user_id}/{version}/{size}",
+    params(
+        ("user_id" = i32, Path, description = "User ID"),
+        ("version" = String, Path, description = "Avatar version from the user's avatar URLs"),
+        ("size" = u32, Path, description = "Edge length in pixels: 64, 128 or 256")
+    ),
+    responses(
+        (status = 200, description = "Square PNG thumbnail", body = Vec<u8>, content_type = "image/png"),
+        (status = 404, description = "No such avatar", body = ApiError)
+    ),
+    tag = "users"
+)]
+pub async fn get_avatar(
+    State(db_pool): State<DatabasePool>,
+    State(storage): State<Arc<dyn Storage>>,
+    Path((user_id, version, size)): Path<(i32, Uuid, u32)>,
+) -> Result<Response, (StatusCode, Json<ApiError>)> {
+    let avatar_service = AvatarService::new(db_pool, storage);
+
+    match avatar_service.get_thumbnail(user_id, version, size).await {
+        // A version is never overwritten, so clients and proxies may cache it for good
+        Ok(Some(png)) => Ok((
+            [
+                (header::CONTENT_TYPE, "image/png"),
+                (header::CACHE_CONTROL, "public, max-age=31536000, immutable"),
+            ],
+            png,
+        ).into_response()),
+        Ok(None) => Err((
+            StatusCode::NOT_FOUND,
+            Json(ApiError {
+                error: "Avatar Not Found".to_string(),
+                message: "No avatar with this version and size".to_string(),
+            }),
+        )),
+        Err(err) => {
+            error!("Failed to read avatar of user_id: {}: {}", user_id, err);
+            Err(internal_error(err.to_string()))
+        }
+    }
+}
+
+// Oversized bodies keep their 413; malformed forms are the caller's fault
+fn multipart_error(err: axum::extract::multipart::MultipartError) -> (StatusCode, Json<ApiError>) {
+    (
+        err.status(),
+        Json(ApiError {
+            error: "Avatar Upload Failed".to_string(),
+            message: err.body_text(),
+        }),
+    )
+}
+
+fn internal_error(message: String) -> (StatusCode, Json<ApiError>) {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ApiError {
+            error: "Internal Server Error".to_string(),
+            message,
+        }),
+    )
+}
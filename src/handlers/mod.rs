@@ -1,3 +1,13 @@
 pub mod auth_handler;
 pub mod notes_handler;
-pub mod users_handler;
\ No newline at end of file
+pub mod users_handler;
+pub mod two_factor_handler;
+pub mod token_handler;
+pub mod oidc_handler;
+pub mod session_handler;
+pub mod role_handler;
+pub mod admin_handler;
+pub mod avatar_handler;
+pub mod tag_handler;
+pub mod notebook_handler;
+pub mod note_share_handler;
\ No newline at end of file
@@ -0,0 +1,163 @@
+use crate::models::auth_model::ApiError;
+use crate::models::note_share_model::*;
+use crate::services::database::DatabasePool;
+use crate::services::note_share_service::NoteShareService;
+use axum::{
+    extract::{Path, State, Extension},
+    http::StatusCode,
+    response::Json,
+};
+use validator::Validate;
+use tracing::{info, error};
+
+/// List the collaborators of a note
+#[utoipa::path(
+    get,
+    path = "/api/v1/notes/{id}/shares",
+    params(
+        ("id" = i32, Path, description = "Note ID")
+    ),
+    responses(
+        (status = 200, description = "Users the note is shared with", body = [NoteShareResponse]),
+        (status = 404, description = "Note not found or not owned by the caller", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError)
+    ),
+    tag = "notes",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_note_shares(
+    State(db_pool): State<DatabasePool>,
+    Path(note_id): Path<i32>,
+    Extension(user_id): Extension<i32>,
+) -> Result<Json<Vec<NoteShareResponse>>, (StatusCode, Json<ApiError>)> {
+    info!("Fetching collaborators of note {} for user_id: {}", note_id, user_id);
+    let share_service = NoteShareService::new(db_pool);
+
+    match share_service.get_shares(note_id, user_id).await {
+        Ok(shares) => Ok(Json(shares)),
+        Err(err) => {
+            error!("Failed to fetch collaborators of note {}: {}", note_id, err);
+            Err(share_error("Failed to Fetch Collaborators", err))
+        }
+    }
+}
+
+/// Share a note with another user, or change their access
+#[utoipa::path(
+    post,
+    path = "/api/v1/notes/{id}/shares",
+    params(
+        ("id" = i32, Path, description = "Note ID")
+    ),
+    request_body = ShareNoteRequest,
+    responses(
+        (status = 201, description = "Note shared with the user", body = NoteShareResponse),
+        (status = 200, description = "The user's access to the note was changed", body = NoteShareResponse),
+        (status = 400, description = "Invalid request, or the user is the note's owner", body = ApiError),
+        (status = 404, description = "Note not found or not owned by the caller, or user not found", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError)
+    ),
+    tag = "notes",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn share_note(
+    State(db_pool): State<DatabasePool>,
+    Path(note_id): Path<i32>,
+    Extension(user_id): Extension<i32>,
+    Json(request): Json<ShareNoteRequest>,
+) -> Result<(StatusCode, Json<NoteShareResponse>), (StatusCode, Json<ApiError>)> {
+    info!("User {} is sharing note {} with {} ({})", user_id, note_id, request.username, request.permission.as_str());
+    // Validate request
+    if let Err(errors) = request.validate() {
+        error!("Validation failed: {}", errors);
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ApiError {
+                error: "Validation Error".to_string(),
+                message: format!("Validation failed: {}", errors),
+            }),
+        ));
+    }
+
+    let share_service = NoteShareService::new(db_pool);
+
+    match share_service.share_note(note_id, request, user_id).await {
+        Ok((created, share)) => {
+            info!("Shared note {} with user {}", note_id, share.user_id);
+            let status_code = if created { StatusCode::CREATED } else { StatusCode::OK };
+            Ok((status_code, Json(share)))
+        },
+        Err(err) => {
+            error!("Failed to share note {}: {}", note_id, err);
+            Err(share_error("Sharing Failed", err))
+        }
+    }
+}
+
+/// Stop sharing a note with a user
+#[utoipa::path(
+    delete,
+    path = "/api/v1/notes/{id}/shares/{user_id}",
+    params(
+        ("id" = i32, Path, description = "Note ID"),
+        ("user_id" = i32, Path, description = "ID of the collaborator")
+    ),
+    responses(
+        (status = 204, description = "Access revoked; collaborators may also remove themselves"),
+        (status = 404, description = "The note is not shared with the user, or the caller may not revoke it", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError)
+    ),
+    tag = "notes",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn unshare_note(
+    State(db_pool): State<DatabasePool>,
+    Path((note_id, shared_user_id)): Path<(i32, i32)>,
+    Extension(user_id): Extension<i32>,
+) -> Result<StatusCode, (StatusCode, Json<ApiError>)> {
+    info!("User {} is revoking access of user {} to note {}", user_id, shared_user_id, note_id);
+    let share_service = NoteShareService::new(db_pool);
+
+    match share_service.unshare_note(note_id, shared_user_id, user_id).await {
+        Ok(true) => {
+            info!("Revoked access of user {} to note {}", shared_user_id, note_id);
+            Ok(StatusCode::NO_CONTENT)
+        },
+        Ok(false) => Err((
+            StatusCode::NOT_FOUND,
+            Json(ApiError {
+                error: "Share Not Found".to_string(),
+                message: "The note is not shared with this user".to_string(),
+            }),
+        )),
+        Err(err) => {
+            error!("Failed to revoke access of user {} to note {}: {}", shared_user_id, note_id, err);
+            Err(share_error("Unsharing Failed", err))
+        }
+    }
+}
+
+fn share_error(error: &str, err: anyhow::Error) -> (StatusCode, Json<ApiError>) {
+    let message = err.to_string();
+    let status_code = if message.contains("not found") {
+        StatusCode::NOT_FOUND
+    } else if message.contains("cannot be shared") {
+        StatusCode::BAD_REQUEST
+    } else {
+        StatusCode::INTERNAL_SERVER_ERROR
+    };
+
+    (
+        status_code,
+        Json(ApiError {
+            error: error.to_string(),
+            message,
+        }),
+    )
+}
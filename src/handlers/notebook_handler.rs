@@ -0,0 +1,251 @@
+use crate::models::auth_model::ApiError;
+use crate::models::notebook_model::*;
+use crate::services::database::DatabasePool;
+use crate::services::notebook_service::NotebookService;
+use axum::{
+    extract::{Path, Query, State, Extension},
+    http::StatusCode,
+    response::Json,
+};
+use validator::Validate;
+use tracing::{info, error};
+
+/// List the notebooks of the authenticated user
+#[utoipa::path(
+    get,
+    path = "/api/v1/notebooks",
+    responses(
+        (status = 200, description = "Every notebook of the user by name; `parent_id` links them into a tree", body = [NotebookResponse]),
+        (status = 401, description = "Unauthorized", body = ApiError)
+    ),
+    tag = "notebooks",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_notebooks(
+    State(db_pool): State<DatabasePool>,
+    Extension(user_id): Extension<i32>,
+) -> Result<Json<Vec<NotebookResponse>>, (StatusCode, Json<ApiError>)> {
+    info!("Fetching notebooks for user_id: {}", user_id);
+    let notebook_service = NotebookService::new(db_pool);
+
+    match notebook_service.get_notebooks(user_id).await {
+        Ok(notebooks) => Ok(Json(notebooks)),
+        Err(err) => {
+            error!("Failed to fetch notebooks for user_id: {}: {}", user_id, err);
+            Err(notebook_error("Failed to Fetch Notebooks", err))
+        }
+    }
+}
+
+/// Create a notebook
+#[utoipa::path(
+    post,
+    path = "/api/v1/notebooks",
+    request_body = CreateNotebookRequest,
+    responses(
+        (status = 201, description = "Notebook created", body = NotebookResponse),
+        (status = 400, description = "Invalid request", body = ApiError),
+        (status = 404, description = "Parent notebook not found", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError)
+    ),
+    tag = "notebooks",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn create_notebook(
+    State(db_pool): State<DatabasePool>,
+    Extension(user_id): Extension<i32>,
+    Json(request): Json<CreateNotebookRequest>,
+) -> Result<(StatusCode, Json<NotebookResponse>), (StatusCode, Json<ApiError>)> {
+    info!("Attempting to create a notebook for user_id: {}", user_id);
+    // Validate request
+    if let Err(errors) = request.validate() {
+        error!("Validation failed: {}", errors);
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ApiError {
+                error: "Validation Error".to_string(),
+                message: format!("Validation failed: {}", errors),
+            }),
+        ));
+    }
+
+    let notebook_service = NotebookService::new(db_pool);
+
+    match notebook_service.create_notebook(request, user_id).await {
+        Ok(notebook) => {
+            info!("Created notebook {} for user_id: {}", notebook.id, user_id);
+            Ok((StatusCode::CREATED, Json(notebook)))
+        },
+        Err(err) => {
+            error!("Failed to create notebook for user_id: {}: {}", user_id, err);
+            Err(notebook_error("Notebook Creation Failed", err))
+        }
+    }
+}
+
+/// Rename a notebook
+#[utoipa::path(
+    patch,
+    path = "/api/v1/notebooks/{id}",
+    params(
+        ("id" = i32, Path, description = "Notebook ID")
+    ),
+    request_body = RenameNotebookRequest,
+    responses(
+        (status = 204, description = "Notebook renamed"),
+        (status = 400, description = "Invalid request", body = ApiError),
+        (status = 404, description = "Notebook not found", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError)
+    ),
+    tag = "notebooks",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn rename_notebook(
+    State(db_pool): State<DatabasePool>,
+    Path(notebook_id): Path<i32>,
+    Extension(user_id): Extension<i32>,
+    Json(request): Json<RenameNotebookRequest>,
+) -> Result<StatusCode, (StatusCode, Json<ApiError>)> {
+    info!("Attempting to rename notebook {} for user_id: {}", notebook_id, user_id);
+    // Validate request
+    if let Err(errors) = request.validate() {
+        error!("Validation failed: {}", errors);
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ApiError {
+                error: "Validation Error".to_string(),
+                message: format!("Validation failed: {}", errors),
+            }),
+        ));
+    }
+
+    let notebook_service = NotebookService::new(db_pool);
+
+    match notebook_service.rename_notebook(notebook_id, request, user_id).await {
+        Ok(true) => {
+            info!("Renamed notebook {} for user_id: {}", notebook_id, user_id);
+            Ok(StatusCode::NO_CONTENT)
+        },
+        Ok(false) => Err(notebook_not_found()),
+        Err(err) => {
+            error!("Failed to rename notebook {} for user_id: {}: {}", notebook_id, user_id, err);
+            Err(notebook_error("Notebook Rename Failed", err))
+        }
+    }
+}
+
+/// Move a notebook into another notebook or to the top level
+#[utoipa::path(
+    post,
+    path = "/api/v1/notebooks/{id}/move",
+    params(
+        ("id" = i32, Path, description = "Notebook ID")
+    ),
+    request_body = MoveNotebookRequest,
+    responses(
+        (status = 204, description = "Notebook moved together with its notes and nested notebooks"),
+        (status = 400, description = "The new parent is the notebook itself or nested inside it", body = ApiError),
+        (status = 404, description = "Notebook or parent notebook not found", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError)
+    ),
+    tag = "notebooks",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn move_notebook(
+    State(db_pool): State<DatabasePool>,
+    Path(notebook_id): Path<i32>,
+    Extension(user_id): Extension<i32>,
+    Json(request): Json<MoveNotebookRequest>,
+) -> Result<StatusCode, (StatusCode, Json<ApiError>)> {
+    info!("Attempting to move notebook {} under {:?} for user_id: {}", notebook_id, request.parent_id, user_id);
+    let notebook_service = NotebookService::new(db_pool);
+
+    match notebook_service.move_notebook(notebook_id, request, user_id).await {
+        Ok(()) => {
+            info!("Moved notebook {} for user_id: {}", notebook_id, user_id);
+            Ok(StatusCode::NO_CONTENT)
+        },
+        Err(err) => {
+            error!("Failed to move notebook {} for user_id: {}: {}", notebook_id, user_id, err);
+            Err(notebook_error("Notebook Move Failed", err))
+        }
+    }
+}
+
+/// Delete a notebook
+#[utoipa::path(
+    delete,
+    path = "/api/v1/notebooks/{id}",
+    params(
+        ("id" = i32, Path, description = "Notebook ID"),
+        ("mode" = Option<NotebookDeleteMode>, Query, description = "`move_up` (default) moves the notebook's notes and nested notebooks to its parent; `cascade` deletes them too")
+    ),
+    responses(
+        (status = 204, description = "Notebook deleted"),
+        (status = 404, description = "Notebook not found", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError)
+    ),
+    tag = "notebooks",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn delete_notebook(
+    State(db_pool): State<DatabasePool>,
+    Path(notebook_id): Path<i32>,
+    Extension(user_id): Extension<i32>,
+    Query(query): Query<DeleteNotebookQuery>,
+) -> Result<StatusCode, (StatusCode, Json<ApiError>)> {
+    let mode = query.mode.unwrap_or_default();
+    info!("Attempting to delete notebook {} ({:?}) for user_id: {}", notebook_id, mode, user_id);
+    let notebook_service = NotebookService::new(db_pool);
+
+    match notebook_service.delete_notebook(notebook_id, mode, user_id).await {
+        Ok(true) => {
+            info!("Deleted notebook {} for user_id: {}", notebook_id, user_id);
+            Ok(StatusCode::NO_CONTENT)
+        },
+        Ok(false) => Err(notebook_not_found()),
+        Err(err) => {
+            error!("Failed to delete notebook {} for user_id: {}: {}", notebook_id, user_id, err);
+            Err(notebook_error("Notebook Deletion Failed", err))
+        }
+    }
+}
+
+fn notebook_not_found() -> (StatusCode, Json<ApiError>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(ApiError {
+            error: "Notebook Not Found".to_string(),
+            message: "Notebook with the specified ID was not found".to_string(),
+        }),
+    )
+}
+
+fn notebook_error(error: &str, err: anyhow::Error) -> (StatusCode, Json<ApiError>) {
+    let message = err.to_string();
+    let status_code = if message.contains("not found") {
+        StatusCode::NOT_FOUND
+    } else if message.contains("cannot be moved") {
+        StatusCode::BAD_REQUEST
+    } else {
+        StatusCode::INTERNAL_SERVER_ERROR
+    };
+
+    (
+        status_code,
+        Json(ApiError {
+            error: error.to_string(),
+            message,
+        }),
+    )
+}
@@ -18,6 +18,7 @@ use tracing::{info, error};
     responses(
         (status = 201, description = "Note created successfully", body = NoteResponse),
         (status = 400, description = "Invalid request", body = ApiError),
+        (status = 404, description = "Notebook not found, or the note to update when an `id` is given", body = ApiError),
         (status = 401, description = "Unauthorized", body = ApiError)
     ),
     tag = "notes",
@@ -52,23 +53,35 @@ pub async fn create_note(
         },
         Err(err) => {
             error!("Failed to create note for user_id: {}: {}", user_id, err);
-            Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ApiError {
-                error: "Note Creation Failed".to_string(),
-                message: err.to_string(),
-            }),
-        ))
+            Err(note_write_error("Note Creation Failed", err))
         },
     }
 }
 
-/// Get all notes for the authenticated user
+/// Get the notes of the authenticated user, a page at a time
 #[utoipa::path(
     get,
     path = "/api/v1/notes",
+    params(
+        ("limit" = Option<i64>, Query, description = "Notes per page, at most 100 (default 20)"),
+        ("cursor" = Option<String>, Query, description = "`next_cursor` of the previous page"),
+        ("include_total" = Option<bool>, Query, description = "Also count the notes across all pages"),
+        ("sort" = Option<NoteSortField>, Query, description = "Sort by `title`, `created_at` or `updated_at` (default)"),
+        ("order" = Option<SortOrder>, Query, description = "`asc` or `desc`; defaults to `asc` for `title` and `desc` otherwise"),
+        ("created_from" = Option<String>, Query, description = "Only notes created at or after this RFC 3339 time (any UTC offset; matched against the Asia/Bangkok wall-clock time notes are stamped with)"),
+        ("created_to" = Option<String>, Query, description = "Only notes created before this RFC 3339 time, on the same clock as `created_from`"),
+        ("updated_from" = Option<String>, Query, description = "Only notes last updated at or after this RFC 3339 time, on the same clock as `created_from`"),
+        ("updated_to" = Option<String>, Query, description = "Only notes last updated before this RFC 3339 time, on the same clock as `created_from`"),
+        ("min_length" = Option<i32>, Query, description = "Only notes with at least this many characters of content"),
+        ("max_length" = Option<i32>, Query, description = "Only notes with at most this many characters of content"),
+        ("tag" = Option<String>, Query, description = "Only notes with these comma-separated tags, matched regardless of case"),
+        ("tag_match" = Option<TagMatch>, Query, description = "`all` (default) to require every tag, `any` for at least one"),
+        ("notebook_id" = Option<i32>, Query, description = "Only notes in this notebook"),
+        ("include_sub_notebooks" = Option<bool>, Query, description = "With `notebook_id`, also notes in the notebooks nested below it")
+    ),
     responses(
-        (status = 200, description = "Notes retrieved successfully", body = [NoteResponse]),
+        (status = 200, description = "A page of notes in the requested order", body = NoteListResponse),
+        (status = 400, description = "Invalid parameter, empty range, or a cursor from a different sort order", body = ApiError),
         (status = 401, description = "Unauthorized", body = ApiError)
     ),
     tag = "notes",
@@ -79,29 +92,86 @@ pub async fn create_note(
 pub async fn get_user_notes(
     State(db_pool): State<DatabasePool>,
     Extension(user_id): Extension<i32>,
-) -> Result<Json<Vec<NoteResponse>>, (StatusCode, Json<ApiError>)> {
+    Query(query): Query<NoteListQuery>,
+) -> Result<Json<NoteListResponse>, (StatusCode, Json<ApiError>)> {
     info!("Attempting to retrieve notes for user_id: {}", user_id);
+    // Validate query
+    if let Err(errors) = query.validate() {
+        error!("Validation failed: {}", errors);
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ApiError {
+                error: "Validation Error".to_string(),
+                message: format!("Validation failed: {}", errors),
+            }),
+        ));
+    }
+
     let note_service = NoteService::new(db_pool);
     
-    match note_service.get_user_notes(user_id).await {
-        Ok(notes) => {
-            info!("Successfully retrieved {} notes for user_id: {}", notes.len(), user_id);
-            Ok(Json(notes))
+    match note_service.get_user_notes(user_id, &query).await {
+        Ok(page) => {
+            info!("Successfully retrieved {} notes for user_id: {}", page.items.len(), user_id);
+            Ok(Json(page))
         },
         Err(err) => {
             error!("Failed to retrieve notes for user_id: {}: {}", user_id, err);
-            Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
+            Err(note_list_error("Failed to retrieve notes", err))
+        },
+    }
+}
+
+/// Get the notes other users shared with the authenticated user, a page at a time
+#[utoipa::path(
+    get,
+    path = "/api/v1/notes/shared-with-me",
+    params(
+        ("limit" = Option<i64>, Query, description = "Notes per page, at most 100 (default 20)"),
+        ("cursor" = Option<String>, Query, description = "`next_cursor` of the previous page")
+    ),
+    responses(
+        (status = 200, description = "A page of shared notes, most recently updated first", body = NoteListResponse),
+        (status = 400, description = "Invalid limit or cursor", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError)
+    ),
+    tag = "notes",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_shared_notes(
+    State(db_pool): State<DatabasePool>,
+    Extension(user_id): Extension<i32>,
+    Query(query): Query<SharedNotesQuery>,
+) -> Result<Json<NoteListResponse>, (StatusCode, Json<ApiError>)> {
+    info!("Attempting to retrieve notes shared with user_id: {}", user_id);
+    // Validate query
+    if let Err(errors) = query.validate() {
+        error!("Validation failed: {}", errors);
+        return Err((
+            StatusCode::BAD_REQUEST,
             Json(ApiError {
-                error: "Failed to retrieve notes".to_string(),
-                message: err.to_string(),
+                error: "Validation Error".to_string(),
+                message: format!("Validation failed: {}", errors),
             }),
-        ))
+        ));
+    }
+
+    let note_service = NoteService::new(db_pool);
+
+    match note_service.get_shared_notes(user_id, &query).await {
+        Ok(page) => {
+            info!("Successfully retrieved {} shared notes for user_id: {}", page.items.len(), user_id);
+            Ok(Json(page))
+        },
+        Err(err) => {
+            error!("Failed to retrieve shared notes for user_id: {}: {}", user_id, err);
+            Err(note_list_error("Failed to retrieve shared notes", err))
         },
     }
 }
 
-/// Get a specific note by ID
+/// Get a specific note by ID, whether owned or shared with the caller
 #[utoipa::path(
     get,
     path = "/api/v1/notes/{id}",
@@ -165,7 +235,8 @@ pub async fn get_note_by_id(
     responses(
         (status = 200, description = "Note updated successfully", body = NoteResponse),
         (status = 400, description = "Invalid request", body = ApiError),
-        (status = 404, description = "Note not found", body = ApiError),
+        (status = 403, description = "The note is shared with the caller read-only, or a collaborator sent `tags` or `notebook_id`", body = ApiError),
+        (status = 404, description = "Note or notebook not found", body = ApiError),
         (status = 401, description = "Unauthorized", body = ApiError)
     ),
     tag = "notes",
@@ -211,13 +282,7 @@ pub async fn update_note(
         },
         Err(err) => {
             error!("Failed to update note with id: {}: {}", note_id, err);
-            Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ApiError {
-                error: "Note Update Failed".to_string(),
-                message: err.to_string(),
-            }),
-        ))
+            Err(note_write_error("Note Update Failed", err))
         },
     }
 }
@@ -275,15 +340,21 @@ pub async fn delete_note(
     }
 }
 
-/// Search notes
+/// Search the caller's own notes and the notes shared with them
 #[utoipa::path(
     get,
     path = "/api/v1/notes/search",
     params(
-        ("search_term" = Option<String>, Query, description = "Search term to filter notes")
+        ("search_term" = Option<String>, Query, description = "Search term to filter notes"),
+        ("limit" = Option<i64>, Query, description = "Notes per page, at most 100 (default 20)"),
+        ("cursor" = Option<String>, Query, description = "`next_cursor` of the previous page"),
+        ("include_total" = Option<bool>, Query, description = "Also count the matching notes across all pages"),
+        ("notebook_id" = Option<i32>, Query, description = "Only notes in this notebook"),
+        ("include_sub_notebooks" = Option<bool>, Query, description = "With `notebook_id`, also notes in the notebooks nested below it")
     ),
     responses(
-        (status = 200, description = "Notes retrieved successfully", body = [NoteResponse]),
+        (status = 200, description = "A page of matching notes, most recently updated first", body = NoteListResponse),
+        (status = 400, description = "Invalid limit or cursor", body = ApiError),
         (status = 401, description = "Unauthorized", body = ApiError)
     ),
     tag = "notes",
@@ -295,24 +366,66 @@ pub async fn search_notes(
     State(db_pool): State<DatabasePool>,
     Extension(user_id): Extension<i32>,
     Query(search_request): Query<SearchRequest>,
-) -> Result<Json<Vec<NoteResponse>>, (StatusCode, Json<ApiError>)> {
+) -> Result<Json<NoteListResponse>, (StatusCode, Json<ApiError>)> {
     info!("Attempting to search notes with term: {:?} for user_id: {}", search_request.search_term, user_id);
+    // Validate query
+    if let Err(errors) = search_request.validate() {
+        error!("Validation failed: {}", errors);
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ApiError {
+                error: "Validation Error".to_string(),
+                message: format!("Validation failed: {}", errors),
+            }),
+        ));
+    }
+
     let note_service = NoteService::new(db_pool);
     
-    match note_service.search_notes(user_id, search_request.search_term).await {
-        Ok(notes) => {
-            info!("Successfully found {} notes for user_id: {}", notes.len(), user_id);
-            Ok(Json(notes))
+    match note_service.search_notes(user_id, &search_request).await {
+        Ok(page) => {
+            info!("Successfully found {} notes for user_id: {}", page.items.len(), user_id);
+            Ok(Json(page))
         },
         Err(err) => {
             error!("Failed to search notes for user_id: {}: {}", user_id, err);
-            Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ApiError {
-                error: "Search Failed".to_string(),
-                message: err.to_string(),
-            }),
-        ))
+            Err(note_list_error("Search Failed", err))
         },
     }
+}
+
+// Cursors are opaque to clients, so one that does not decode was altered or truncated
+fn note_list_error(error: &str, err: anyhow::Error) -> (StatusCode, Json<ApiError>) {
+    let status_code = if err.to_string().contains("Invalid cursor") {
+        StatusCode::BAD_REQUEST
+    } else {
+        StatusCode::INTERNAL_SERVER_ERROR
+    };
+
+    (
+        status_code,
+        Json(ApiError {
+            error: error.to_string(),
+            message: err.to_string(),
+        }),
+    )
+}
+
+// A note can only be put into one of the user's own notebooks, and shared notes need write access
+fn note_write_error(error: &str, err: anyhow::Error) -> (StatusCode, Json<ApiError>) {
+    let status_code = if err.to_string().contains("not found") {
+        StatusCode::NOT_FOUND
+    } else if err.to_string().contains("read-only") || err.to_string().contains("Only the owner") {
+        StatusCode::FORBIDDEN
+    } else {
+        StatusCode::INTERNAL_SERVER_ERROR
+    };
+
+    (
+        status_code,
+        Json(ApiError {
+            error: error.to_string(),
+            message: err.to_string(),
+        }),
+    )
 }
\ No newline at end of file
@@ -0,0 +1,151 @@
+use crate::models::auth_model::{ApiError, LoginResponse};
+use crate::models::oidc_model::*;
+use crate::services::auth_event_service::{AuthEvent, AuthEventService, AuthEventType};
+use crate::services::database::DatabasePool;
+use crate::services::oidc_service::OidcService;
+use crate::utils::auth_cookies::TokenDelivery;
+use crate::utils::client_device::ClientDevice;
+use crate::utils::oidc;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{Json, Response},
+};
+use validator::Validate;
+use tracing::{info, error, warn};
+
+/// List the configured OpenID Connect providers
+#[utoipa::path(
+    get,
+    path = "/api/v1/auth/oidc/providers",
+    responses(
+        (status = 200, description = "Providers available for login", body = OidcProvidersResponse)
+    ),
+    tag = "oidc"
+)]
+pub async fn get_providers() -> Json<OidcProvidersResponse> {
+    Json(OidcProvidersResponse {
+        providers: oidc::provider_names(),
+    })
+}
+
+/// Start a login with an OpenID Connect provider
+#[utoipa::path(
+    get,
+    path = "/api/v1/auth/oidc/{provider}/authorize",
+    params(
+        ("provider" = String, Path, description = "Provider name")
+    ),
+    responses(
+        (status = 200, description = "URL to send the user to", body = OidcAuthorizationResponse),
+        (status = 404, description = "Unknown provider", body = ApiError),
+        (status = 502, description = "The provider could not be reached", body = ApiError)
+    ),
+    tag = "oidc"
+)]
+pub async fn authorize(
+    State(db_pool): State<DatabasePool>,
+    Path(provider): Path<String>,
+) -> Result<Json<OidcAuthorizationResponse>, (StatusCode, Json<ApiError>)> {
+    info!("OIDC login started with provider: {}", provider);
+
+    let oidc_service = OidcService::new(db_pool);
+
+    match oidc_service.begin_login(&provider).await {
+        Ok(response) => Ok(Json(response)),
+        Err(err) => {
+            error!("Failed to start OIDC login with provider: {}: {}", provider, err);
+            Err(oidc_error(err))
+        }
+    }
+}
+
+/// Complete a login with an OpenID Connect provider
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/oidc/{provider}/callback",
+    params(
+        ("provider" = String, Path, description = "Provider name"),
+        ("X-Auth-Mode" = Option<String>, Header, description = "`cookie` to receive the tokens as HttpOnly cookies instead of in the body")
+    ),
+    request_body = OidcCallbackRequest,
+    responses(
+        (status = 200, description = "User logged in successfully, or a two-factor challenge when 2FA is enabled", body = LoginResponse),
+        (status = 400, description = "Invalid request, or unknown or expired state", body = ApiError),
+        (status = 401, description = "The ID token was rejected or carries no email address", body = ApiError),
+        (status = 403, description = "Account suspended", body = ApiError),
+        (status = 404, description = "Unknown provider", body = ApiError),
+        (status = 409, description = "An account with the email exists, but the provider has not verified the email", body = ApiError),
+        (status = 502, description = "The provider could not be reached or rejected the code", body = ApiError)
+    ),
+    tag = "oidc"
+)]
+pub async fn callback(
+    State(db_pool): State<DatabasePool>,
+    Path(provider): Path<String>,
+    device: ClientDevice,
+    delivery: TokenDelivery,
+    Json(request): Json<OidcCallbackRequest>,
+) -> Result<Response, (StatusCode, Json<ApiError>)> {
+    info!("OIDC callback received for provider: {}", provider);
+    // Validate request
+    if let Err(errors) = request.validate() {
+        error!("Validation failed: {}", errors);
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ApiError {
+                error: "Validation Error".to_string(),
+                message: format!("Validation failed: {}", errors),
+            }),
+        ));
+    }
+
+    let oidc_service = OidcService::new(db_pool.clone());
+    let events = AuthEventService::new(db_pool);
+
+    match oidc_service.complete_login(&provider, request, &device).await {
+        Ok(response) => {
+            match &response {
+                LoginResponse::Authenticated(auth) => {
+                    info!("Successfully logged in user_id: {} with provider: {}", auth.user_id, provider);
+                    let event = AuthEvent::succeeded(AuthEventType::Login, auth.user_id).with_reason(format!("via {}", provider));
+                    events.record(event, &device).await;
+                },
+                LoginResponse::TwoFactorRequired(_) => info!("Two-factor challenge issued after login with provider: {}", provider),
+            }
+            Ok(delivery.login_response(response))
+        },
+        Err(err) => {
+            warn!("Failed to complete OIDC login with provider: {}: {:#}", provider, err);
+            events.record(AuthEvent::failed(AuthEventType::Login, format!("via {}: {}", provider, err)), &device).await;
+            Err(oidc_error(err))
+        }
+    }
+}
+
+fn oidc_error(err: anyhow::Error) -> (StatusCode, Json<ApiError>) {
+    let message = err.to_string();
+    let status_code = if message.contains("Unknown OIDC provider") {
+        StatusCode::NOT_FOUND
+    } else if message.contains("Invalid or expired state") {
+        StatusCode::BAD_REQUEST
+    } else if message.contains("Invalid ID token") || message.contains("did not share an email") {
+        StatusCode::UNAUTHORIZED
+    } else if message.contains("Account suspended") {
+        StatusCode::FORBIDDEN
+    } else if message.contains("already exists") {
+        StatusCode::CONFLICT
+    } else if message.contains("Identity provider request failed") {
+        StatusCode::BAD_GATEWAY
+    } else {
+        StatusCode::INTERNAL_SERVER_ERROR
+    };
+
+    (
+        status_code,
+        Json(ApiError {
+            error: "OIDC Login Failed".to_string(),
+            message,
+        }),
+    )
+}
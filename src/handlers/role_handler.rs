@@ -0,0 +1,149 @@
+use crate::models::auth_model::ApiError;
+use crate::models::role_model::RoleResponse;
+use crate::services::database::DatabasePool;
+use crate::services::role_service::RoleService;
+use axum::{
+    extract::{Path, State, Extension},
+    http::StatusCode,
+    response::Json,
+};
+use tracing::{info, error};
+
+/// List roles and the permissions they grant
+#[utoipa::path(
+    get,
+    path = "/api/v1/roles",
+    responses(
+        (status = 200, description = "Every role with its permissions", body = [RoleResponse]),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "Requires the roles:read permission", body = ApiError)
+    ),
+    tag = "roles",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_roles(
+    State(db_pool): State<DatabasePool>,
+) -> Result<Json<Vec<RoleResponse>>, (StatusCode, Json<ApiError>)> {
+    info!("Fetching roles");
+    let role_service = RoleService::new(db_pool);
+
+    match role_service.get_roles().await {
+        Ok(roles) => Ok(Json(roles)),
+        Err(err) => {
+            error!("Failed to fetch roles: {}", err);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiError {
+                    error: "Failed to Fetch Roles".to_string(),
+                    message: err.to_string(),
+                }),
+            ))
+        }
+    }
+}
+
+/// Grant a role to a user
+#[utoipa::path(
+    put,
+    path = "/api/v1/users/{id}/roles/{role}",
+    params(
+        ("id" = i32, Path, description = "User ID"),
+        ("role" = String, Path, description = "Role name")
+    ),
+    responses(
+        (status = 201, description = "Role granted; the user's access tokens are re-issued on their next refresh"),
+        (status = 204, description = "The user already has the role"),
+        (status = 404, description = "User or role not found", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "Requires the roles:assign permission", body = ApiError)
+    ),
+    tag = "roles",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn assign_role(
+    State(db_pool): State<DatabasePool>,
+    Path((user_id, role)): Path<(i32, String)>,
+    Extension(current_user_id): Extension<i32>,
+) -> Result<StatusCode, (StatusCode, Json<ApiError>)> {
+    info!("User {} is granting role: {} to user {}", current_user_id, role, user_id);
+    let role_service = RoleService::new(db_pool);
+
+    match role_service.assign_role(user_id, &role).await {
+        Ok(true) => {
+            info!("Granted role: {} to user {}", role, user_id);
+            Ok(StatusCode::CREATED)
+        },
+        Ok(false) => Ok(StatusCode::NO_CONTENT),
+        Err(err) => {
+            error!("Failed to grant role: {} to user {}: {}", role, user_id, err);
+            Err(role_error(err))
+        }
+    }
+}
+
+/// Revoke a role from a user
+#[utoipa::path(
+    delete,
+    path = "/api/v1/users/{id}/roles/{role}",
+    params(
+        ("id" = i32, Path, description = "User ID"),
+        ("role" = String, Path, description = "Role name")
+    ),
+    responses(
+        (status = 204, description = "Role revoked; access tokens carrying it stop working immediately"),
+        (status = 404, description = "User or role not found, or the user does not have the role", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "Requires the roles:assign permission", body = ApiError)
+    ),
+    tag = "roles",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn remove_role(
+    State(db_pool): State<DatabasePool>,
+    Path((user_id, role)): Path<(i32, String)>,
+    Extension(current_user_id): Extension<i32>,
+) -> Result<StatusCode, (StatusCode, Json<ApiError>)> {
+    info!("User {} is revoking role: {} from user {}", current_user_id, role, user_id);
+    let role_service = RoleService::new(db_pool);
+
+    match role_service.remove_role(user_id, &role).await {
+        Ok(true) => {
+            info!("Revoked role: {} from user {}", role, user_id);
+            Ok(StatusCode::NO_CONTENT)
+        },
+        Ok(false) => Err((
+            StatusCode::NOT_FOUND,
+            Json(ApiError {
+                error: "Role Not Assigned".to_string(),
+                message: format!("The user does not have the {} role", role),
+            }),
+        )),
+        Err(err) => {
+            error!("Failed to revoke role: {} from user {}: {}", role, user_id, err);
+            Err(role_error(err))
+        }
+    }
+}
+
+fn role_error(err: anyhow::Error) -> (StatusCode, Json<ApiError>) {
+    let message = err.to_string();
+    let status_code = if message.contains("not found") {
+        StatusCode::NOT_FOUND
+    } else {
+        StatusCode::INTERNAL_SERVER_ERROR
+    };
+
+    (
+        status_code,
+        Json(ApiError {
+            error: "Role Change Failed".to_string(),
+            message,
+        }),
+    )
+}
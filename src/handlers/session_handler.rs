@@ -0,0 +1,152 @@
+use crate::models::auth_model::{ApiError, AuthContext};
+use crate::models::session_model::*;
+use crate::services::database::DatabasePool;
+use crate::services::session_service::SessionService;
+use axum::{
+    extract::{Path, State, Extension},
+    http::StatusCode,
+    response::Json,
+};
+use tracing::{info, error};
+use uuid::Uuid;
+
+/// List the sessions of the current user
+#[utoipa::path(
+    get,
+    path = "/api/v1/users/me/sessions",
+    responses(
+        (status = 200, description = "Active sessions, most recently used first", body = [SessionResponse]),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "Personal access tokens cannot list sessions", body = ApiError)
+    ),
+    tag = "sessions",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_sessions(
+    State(db_pool): State<DatabasePool>,
+    Extension(context): Extension<AuthContext>,
+) -> Result<Json<Vec<SessionResponse>>, (StatusCode, Json<ApiError>)> {
+    info!("Fetching sessions for user_id: {}", context.user_id);
+    let session_service = SessionService::new(db_pool);
+
+    match session_service.get_user_sessions(context.user_id, context.session_id).await {
+        Ok(sessions) => Ok(Json(sessions)),
+        Err(err) => {
+            error!("Failed to fetch sessions for user_id: {}: {}", context.user_id, err);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiError {
+                    error: "Failed to Fetch Sessions".to_string(),
+                    message: err.to_string(),
+                }),
+            ))
+        }
+    }
+}
+
+/// Sign a session out
+#[utoipa::path(
+    delete,
+    path = "/api/v1/users/me/sessions/{id}",
+    params(
+        ("id" = String, Path, description = "Session ID")
+    ),
+    responses(
+        (status = 204, description = "Session signed out; its tokens stop working immediately"),
+        (status = 404, description = "Session not found", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "Personal access tokens cannot sign sessions out", body = ApiError)
+    ),
+    tag = "sessions",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn revoke_session(
+    State(db_pool): State<DatabasePool>,
+    Path(session_id): Path<Uuid>,
+    Extension(user_id): Extension<i32>,
+) -> Result<StatusCode, (StatusCode, Json<ApiError>)> {
+    info!("Attempting to revoke session: {} for user_id: {}", session_id, user_id);
+    let session_service = SessionService::new(db_pool);
+
+    match session_service.revoke_session(session_id, user_id).await {
+        Ok(true) => {
+            info!("Successfully revoked session: {}", session_id);
+            Ok(StatusCode::NO_CONTENT)
+        },
+        Ok(false) => {
+            error!("Session: {} not found for user_id: {}", session_id, user_id);
+            Err((
+                StatusCode::NOT_FOUND,
+                Json(ApiError {
+                    error: "Session Not Found".to_string(),
+                    message: "Session with the specified ID was not found".to_string(),
+                }),
+            ))
+        },
+        Err(err) => {
+            error!("Failed to revoke session: {}: {}", session_id, err);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiError {
+                    error: "Session Revocation Failed".to_string(),
+                    message: err.to_string(),
+                }),
+            ))
+        },
+    }
+}
+
+/// Sign out every other session of the current user
+#[utoipa::path(
+    post,
+    path = "/api/v1/users/me/sessions/logout-others",
+    responses(
+        (status = 200, description = "Every session except the current one was signed out", body = RevokedSessionsResponse),
+        (status = 400, description = "The access token predates sessions; log in again", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "Personal access tokens cannot sign sessions out", body = ApiError)
+    ),
+    tag = "sessions",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn revoke_other_sessions(
+    State(db_pool): State<DatabasePool>,
+    Extension(context): Extension<AuthContext>,
+) -> Result<Json<RevokedSessionsResponse>, (StatusCode, Json<ApiError>)> {
+    info!("Attempting to revoke other sessions for user_id: {}", context.user_id);
+
+    let Some(session_id) = context.session_id else {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ApiError {
+                error: "No Current Session".to_string(),
+                message: "This access token does not belong to a session; log in again".to_string(),
+            }),
+        ));
+    };
+
+    let session_service = SessionService::new(db_pool);
+
+    match session_service.revoke_other_sessions(context.user_id, session_id).await {
+        Ok(revoked_sessions) => {
+            info!("Revoked {} other sessions for user_id: {}", revoked_sessions, context.user_id);
+            Ok(Json(RevokedSessionsResponse { revoked_sessions }))
+        },
+        Err(err) => {
+            error!("Failed to revoke other sessions for user_id: {}: {}", context.user_id, err);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiError {
+                    error: "Session Revocation Failed".to_string(),
+                    message: err.to_string(),
+                }),
+            ))
+        }
+    }
+}
@@ -0,0 +1,246 @@
+use crate::models::auth_model::ApiError;
+use crate::models::tag_model::*;
+use crate::services::database::DatabasePool;
+use crate::services::tag_service::TagService;
+use axum::{
+    extract::{Path, State, Extension},
+    http::StatusCode,
+    response::Json,
+};
+use validator::Validate;
+use tracing::{info, error};
+
+/// List the tags of the authenticated user with their usage counts
+#[utoipa::path(
+    get,
+    path = "/api/v1/tags",
+    responses(
+        (status = 200, description = "Every tag of the user by name, with the number of notes carrying it", body = [TagResponse]),
+        (status = 401, description = "Unauthorized", body = ApiError)
+    ),
+    tag = "tags",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_tags(
+    State(db_pool): State<DatabasePool>,
+    Extension(user_id): Extension<i32>,
+) -> Result<Json<Vec<TagResponse>>, (StatusCode, Json<ApiError>)> {
+    info!("Fetching tags for user_id: {}", user_id);
+    let tag_service = TagService::new(db_pool);
+
+    match tag_service.get_tags(user_id).await {
+        Ok(tags) => Ok(Json(tags)),
+        Err(err) => {
+            error!("Failed to fetch tags for user_id: {}: {}", user_id, err);
+            Err(tag_error("Failed to Fetch Tags", err))
+        }
+    }
+}
+
+/// Create a tag
+#[utoipa::path(
+    post,
+    path = "/api/v1/tags",
+    request_body = CreateTagRequest,
+    responses(
+        (status = 201, description = "Tag created", body = TagResponse),
+        (status = 400, description = "Invalid request", body = ApiError),
+        (status = 409, description = "The user already has a tag with this name", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError)
+    ),
+    tag = "tags",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn create_tag(
+    State(db_pool): State<DatabasePool>,
+    Extension(user_id): Extension<i32>,
+    Json(request): Json<CreateTagRequest>,
+) -> Result<(StatusCode, Json<TagResponse>), (StatusCode, Json<ApiError>)> {
+    info!("Attempting to create a tag for user_id: {}", user_id);
+    // Validate request
+    if let Err(errors) = request.validate() {
+        error!("Validation failed: {}", errors);
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ApiError {
+                error: "Validation Error".to_string(),
+                message: format!("Validation failed: {}", errors),
+            }),
+        ));
+    }
+
+    let tag_service = TagService::new(db_pool);
+
+    match tag_service.create_tag(request, user_id).await {
+        Ok(tag) => {
+            info!("Created tag {} for user_id: {}", tag.id, user_id);
+            Ok((StatusCode::CREATED, Json(tag)))
+        },
+        Err(err) => {
+            error!("Failed to create tag for user_id: {}: {}", user_id, err);
+            Err(tag_error("Tag Creation Failed", err))
+        }
+    }
+}
+
+/// Rename a tag
+#[utoipa::path(
+    patch,
+    path = "/api/v1/tags/{id}",
+    params(
+        ("id" = i32, Path, description = "Tag ID")
+    ),
+    request_body = RenameTagRequest,
+    responses(
+        (status = 204, description = "Tag renamed on every note carrying it"),
+        (status = 400, description = "Invalid request", body = ApiError),
+        (status = 404, description = "Tag not found", body = ApiError),
+        (status = 409, description = "Another tag already has this name; merge the tags instead", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError)
+    ),
+    tag = "tags",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn rename_tag(
+    State(db_pool): State<DatabasePool>,
+    Path(tag_id): Path<i32>,
+    Extension(user_id): Extension<i32>,
+    Json(request): Json<RenameTagRequest>,
+) -> Result<StatusCode, (StatusCode, Json<ApiError>)> {
+    info!("Attempting to rename tag {} for user_id: {}", tag_id, user_id);
+    // Validate request
+    if let Err(errors) = request.validate() {
+        error!("Validation failed: {}", errors);
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ApiError {
+                error: "Validation Error".to_string(),
+                message: format!("Validation failed: {}", errors),
+            }),
+        ));
+    }
+
+    let tag_service = TagService::new(db_pool);
+
+    match tag_service.rename_tag(tag_id, request, user_id).await {
+        Ok(()) => {
+            info!("Renamed tag {} for user_id: {}", tag_id, user_id);
+            Ok(StatusCode::NO_CONTENT)
+        },
+        Err(err) => {
+            error!("Failed to rename tag {} for user_id: {}: {}", tag_id, user_id, err);
+            Err(tag_error("Tag Rename Failed", err))
+        }
+    }
+}
+
+/// Merge a tag into another
+#[utoipa::path(
+    post,
+    path = "/api/v1/tags/{id}/merge",
+    params(
+        ("id" = i32, Path, description = "ID of the tag to merge away")
+    ),
+    request_body = MergeTagRequest,
+    responses(
+        (status = 204, description = "The tag's notes now carry the target tag and the tag is deleted"),
+        (status = 400, description = "The tag would be merged into itself", body = ApiError),
+        (status = 404, description = "Either tag not found", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError)
+    ),
+    tag = "tags",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn merge_tag(
+    State(db_pool): State<DatabasePool>,
+    Path(tag_id): Path<i32>,
+    Extension(user_id): Extension<i32>,
+    Json(request): Json<MergeTagRequest>,
+) -> Result<StatusCode, (StatusCode, Json<ApiError>)> {
+    info!("Attempting to merge tag {} into tag {} for user_id: {}", tag_id, request.into_tag_id, user_id);
+    let tag_service = TagService::new(db_pool);
+
+    match tag_service.merge_tags(tag_id, request, user_id).await {
+        Ok(()) => {
+            info!("Merged tag {} for user_id: {}", tag_id, user_id);
+            Ok(StatusCode::NO_CONTENT)
+        },
+        Err(err) => {
+            error!("Failed to merge tag {} for user_id: {}: {}", tag_id, user_id, err);
+            Err(tag_error("Tag Merge Failed", err))
+        }
+    }
+}
+
+/// Delete a tag
+#[utoipa::path(
+    delete,
+    path = "/api/v1/tags/{id}",
+    params(
+        ("id" = i32, Path, description = "Tag ID")
+    ),
+    responses(
+        (status = 204, description = "Tag deleted and removed from its notes; the notes are kept"),
+        (status = 404, description = "Tag not found", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError)
+    ),
+    tag = "tags",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn delete_tag(
+    State(db_pool): State<DatabasePool>,
+    Path(tag_id): Path<i32>,
+    Extension(user_id): Extension<i32>,
+) -> Result<StatusCode, (StatusCode, Json<ApiError>)> {
+    info!("Attempting to delete tag {} for user_id: {}", tag_id, user_id);
+    let tag_service = TagService::new(db_pool);
+
+    match tag_service.delete_tag(tag_id, user_id).await {
+        Ok(true) => {
+            info!("Deleted tag {} for user_id: {}", tag_id, user_id);
+            Ok(StatusCode::NO_CONTENT)
+        },
+        Ok(false) => Err((
+            StatusCode::NOT_FOUND,
+            Json(ApiError {
+                error: "Tag Not Found".to_string(),
+                message: "Tag with the specified ID was not found".to_string(),
+            }),
+        )),
+        Err(err) => {
+            error!("Failed to delete tag {} for user_id: {}: {}", tag_id, user_id, err);
+            Err(tag_error("Tag Deletion Failed", err))
+        }
+    }
+}
+
+fn tag_error(error: &str, err: anyhow::Error) -> (StatusCode, Json<ApiError>) {
+    let message = err.to_string();
+    let status_code = if message.contains("not found") {
+        StatusCode::NOT_FOUND
+    } else if message.contains("already exists") {
+        StatusCode::CONFLICT
+    } else if message.contains("cannot be merged") {
+        StatusCode::BAD_REQUEST
+    } else {
+        StatusCode::INTERNAL_SERVER_ERROR
+    };
+
+    (
+        status_code,
+        Json(ApiError {
+            error: error.to_string(),
+            message,
+        }),
+    )
+}
@@ -0,0 +1,161 @@
+use crate::models::auth_model::ApiError;
+use crate::models::token_model::*;
+use crate::services::database::DatabasePool;
+use crate::services::personal_access_token_service::PersonalAccessTokenService;
+use axum::{
+    extract::{Path, State, Extension},
+    http::StatusCode,
+    response::Json,
+};
+use validator::Validate;
+use tracing::{info, error};
+
+/// Create a personal access token
+#[utoipa::path(
+    post,
+    path = "/api/v1/tokens",
+    request_body = CreatePersonalAccessTokenRequest,
+    responses(
+        (status = 201, description = "Token created; the token value is only returned once", body = CreatedPersonalAccessTokenResponse),
+        (status = 400, description = "Invalid request or unknown scope", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "Personal access tokens cannot create tokens", body = ApiError)
+    ),
+    tag = "tokens",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn create_token(
+    State(db_pool): State<DatabasePool>,
+    Extension(user_id): Extension<i32>,
+    Json(request): Json<CreatePersonalAccessTokenRequest>,
+) -> Result<(StatusCode, Json<CreatedPersonalAccessTokenResponse>), (StatusCode, Json<ApiError>)> {
+    info!("Attempting to create a personal access token for user_id: {}", user_id);
+    // Validate request
+    if let Err(errors) = request.validate() {
+        error!("Validation failed: {}", errors);
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ApiError {
+                error: "Validation Error".to_string(),
+                message: format!("Validation failed: {}", errors),
+            }),
+        ));
+    }
+
+    let token_service = PersonalAccessTokenService::new(db_pool);
+
+    match token_service.create_token(request, user_id).await {
+        Ok(response) => {
+            info!("Successfully created personal access token with id: {}", response.details.id);
+            Ok((StatusCode::CREATED, Json(response)))
+        },
+        Err(err) => {
+            error!("Failed to create personal access token for user_id: {}: {}", user_id, err);
+            let status_code = if err.to_string().contains("Unknown scope") {
+                StatusCode::BAD_REQUEST
+            } else {
+                StatusCode::INTERNAL_SERVER_ERROR
+            };
+
+            Err((
+                status_code,
+                Json(ApiError {
+                    error: "Token Creation Failed".to_string(),
+                    message: err.to_string(),
+                }),
+            ))
+        }
+    }
+}
+
+/// List personal access tokens
+#[utoipa::path(
+    get,
+    path = "/api/v1/tokens",
+    responses(
+        (status = 200, description = "Active personal access tokens of the user", body = [PersonalAccessTokenResponse]),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "Personal access tokens cannot list tokens", body = ApiError)
+    ),
+    tag = "tokens",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_tokens(
+    State(db_pool): State<DatabasePool>,
+    Extension(user_id): Extension<i32>,
+) -> Result<Json<Vec<PersonalAccessTokenResponse>>, (StatusCode, Json<ApiError>)> {
+    info!("Fetching personal access tokens for user_id: {}", user_id);
+    let token_service = PersonalAccessTokenService::new(db_pool);
+
+    match token_service.get_user_tokens(user_id).await {
+        Ok(tokens) => Ok(Json(tokens)),
+        Err(err) => {
+            error!("Failed to fetch personal access tokens for user_id: {}: {}", user_id, err);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiError {
+                    error: "Failed to Fetch Tokens".to_string(),
+                    message: err.to_string(),
+                }),
+            ))
+        }
+    }
+}
+
+/// Revoke a personal access token
+#[utoipa::path(
+    delete,
+    path = "/api/v1/tokens/{id}",
+    params(
+        ("id" = i32, Path, description = "Token ID")
+    ),
+    responses(
+        (status = 204, description = "Token revoked successfully"),
+        (status = 404, description = "Token not found", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "Personal access tokens cannot revoke tokens", body = ApiError)
+    ),
+    tag = "tokens",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn revoke_token(
+    State(db_pool): State<DatabasePool>,
+    Path(token_id): Path<i32>,
+    Extension(user_id): Extension<i32>,
+) -> Result<StatusCode, (StatusCode, Json<ApiError>)> {
+    info!("Attempting to revoke personal access token with id: {} for user_id: {}", token_id, user_id);
+    let token_service = PersonalAccessTokenService::new(db_pool);
+
+    match token_service.revoke_token(token_id, user_id).await {
+        Ok(true) => {
+            info!("Successfully revoked personal access token with id: {}", token_id);
+            Ok(StatusCode::NO_CONTENT)
+        },
+        Ok(false) => {
+            error!("Personal access token with id: {} not found for user_id: {}", token_id, user_id);
+            Err((
+                StatusCode::NOT_FOUND,
+                Json(ApiError {
+                    error: "Token Not Found".to_string(),
+                    message: "Token with the specified ID was not found".to_string(),
+                }),
+            ))
+        },
+        Err(err) => {
+            error!("Failed to revoke personal access token with id: {}: {}", token_id, err);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiError {
+                    error: "Token Revocation Failed".to_string(),
+                    message: err.to_string(),
+                }),
+            ))
+        },
+    }
+}
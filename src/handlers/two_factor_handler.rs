@@ -0,0 +1,239 @@
+use crate::handlers::auth_handler::login_error;
+use crate::models::auth_model::ApiError;
+use crate::models::two_factor_model::*;
+use crate::services::auth_event_service::{AuthEvent, AuthEventService, AuthEventType};
+use crate::services::database::DatabasePool;
+use crate::services::login_throttle_service::LoginLocked;
+use crate::services::two_factor_service::TwoFactorService;
+use crate::utils::client_device::ClientDevice;
+use axum::{
+    extract::{Extension, State},
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+};
+use validator::Validate;
+use tracing::{info, error};
+
+/// Start two-factor enrollment
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/2fa/enroll",
+    responses(
+        (status = 200, description = "New TOTP secret; confirm it with a code to enable two-factor authentication", body = TotpEnrollmentResponse),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 409, description = "Two-factor authentication is already enabled", body = ApiError)
+    ),
+    tag = "two-factor",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn enroll(
+    State(db_pool): State<DatabasePool>,
+    Extension(user_id): Extension<i32>,
+) -> Result<Json<TotpEnrollmentResponse>, (StatusCode, Json<ApiError>)> {
+    info!("Two-factor enrollment started for user_id: {}", user_id);
+    let two_factor_service = TwoFactorService::new(db_pool);
+
+    match two_factor_service.begin_enrollment(user_id).await {
+        Ok(response) => Ok(Json(response)),
+        Err(err) => {
+            error!("Failed to start two-factor enrollment for user_id: {}: {}", user_id, err);
+            let status_code = if err.to_string().contains("already enabled") {
+                StatusCode::CONFLICT
+            } else {
+                StatusCode::INTERNAL_SERVER_ERROR
+            };
+
+            Err((
+                status_code,
+                Json(ApiError {
+                    error: "Two-Factor Enrollment Failed".to_string(),
+                    message: err.to_string(),
+                }),
+            ))
+        }
+    }
+}
+
+/// Confirm two-factor enrollment with a TOTP code
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/2fa/confirm",
+    request_body = TwoFactorCodeRequest,
+    responses(
+        (status = 200, description = "Two-factor authentication enabled; store the recovery codes", body = RecoveryCodesResponse),
+        (status = 400, description = "Invalid request, invalid code or no pending enrollment", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError)
+    ),
+    tag = "two-factor",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn confirm(
+    State(db_pool): State<DatabasePool>,
+    Extension(user_id): Extension<i32>,
+    device: ClientDevice,
+    Json(request): Json<TwoFactorCodeRequest>,
+) -> Result<Json<RecoveryCodesResponse>, (StatusCode, Json<ApiError>)> {
+    info!("Two-factor enrollment confirmation for user_id: {}", user_id);
+    // Validate request
+    if let Err(errors) = request.validate() {
+        error!("Validation failed: {}", errors);
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ApiError {
+                error: "Validation Error".to_string(),
+                message: format!("Validation failed: {}", errors),
+            }),
+        ));
+    }
+
+    let two_factor_service = TwoFactorService::new(db_pool.clone());
+    let events = AuthEventService::new(db_pool);
+
+    match two_factor_service.confirm_enrollment(user_id, &request.code).await {
+        Ok(response) => {
+            info!("Two-factor authentication enabled for user_id: {}", user_id);
+            events.record(AuthEvent::succeeded(AuthEventType::TwoFactorEnabled, user_id), &device).await;
+            Ok(Json(response))
+        },
+        Err(err) => {
+            error!("Failed to confirm two-factor enrollment for user_id: {}: {}", user_id, err);
+            events.record(AuthEvent::failed(AuthEventType::TwoFactorEnabled, &err).for_user(user_id), &device).await;
+            Err(two_factor_error("Two-Factor Confirmation Failed", err))
+        }
+    }
+}
+
+/// Disable two-factor authentication
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/2fa/disable",
+    request_body(content = TwoFactorCodeRequest, description = "Current TOTP code or an unused recovery code"),
+    responses(
+        (status = 204, description = "Two-factor authentication disabled"),
+        (status = 400, description = "Invalid request, invalid code or 2FA not enabled", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 429, description = "Too many failed attempts; see the Retry-After header", body = ApiError)
+    ),
+    tag = "two-factor",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn disable(
+    State(db_pool): State<DatabasePool>,
+    Extension(user_id): Extension<i32>,
+    device: ClientDevice,
+    Json(request): Json<TwoFactorCodeRequest>,
+) -> Result<StatusCode, Response> {
+    info!("Two-factor disable request for user_id: {}", user_id);
+    // Validate request
+    if let Err(errors) = request.validate() {
+        error!("Validation failed: {}", errors);
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ApiError {
+                error: "Validation Error".to_string(),
+                message: format!("Validation failed: {}", errors),
+            }),
+        ).into_response());
+    }
+
+    let two_factor_service = TwoFactorService::new(db_pool.clone());
+    let events = AuthEventService::new(db_pool);
+
+    match two_factor_service.disable(user_id, &request.code, &device).await {
+        Ok(()) => {
+            info!("Two-factor authentication disabled for user_id: {}", user_id);
+            events.record(AuthEvent::succeeded(AuthEventType::TwoFactorDisabled, user_id), &device).await;
+            Ok(StatusCode::NO_CONTENT)
+        },
+        Err(err) => {
+            error!("Failed to disable two-factor authentication for user_id: {}: {}", user_id, err);
+            events.record(AuthEvent::failed(AuthEventType::TwoFactorDisabled, &err).for_user(user_id), &device).await;
+            Err(code_check_error("Two-Factor Disable Failed", err))
+        }
+    }
+}
+
+/// Regenerate recovery codes
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/2fa/recovery-codes",
+    request_body(content = TwoFactorCodeRequest, description = "Current TOTP code or an unused recovery code"),
+    responses(
+        (status = 200, description = "New recovery codes; every earlier code is invalidated", body = RecoveryCodesResponse),
+        (status = 400, description = "Invalid request, invalid code or 2FA not enabled", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 429, description = "Too many failed attempts; see the Retry-After header", body = ApiError)
+    ),
+    tag = "two-factor",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn regenerate_recovery_codes(
+    State(db_pool): State<DatabasePool>,
+    Extension(user_id): Extension<i32>,
+    device: ClientDevice,
+    Json(request): Json<TwoFactorCodeRequest>,
+) -> Result<Json<RecoveryCodesResponse>, Response> {
+    info!("Recovery code regeneration for user_id: {}", user_id);
+    // Validate request
+    if let Err(errors) = request.validate() {
+        error!("Validation failed: {}", errors);
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ApiError {
+                error: "Validation Error".to_string(),
+                message: format!("Validation failed: {}", errors),
+            }),
+        ).into_response());
+    }
+
+    let two_factor_service = TwoFactorService::new(db_pool.clone());
+    let events = AuthEventService::new(db_pool);
+
+    match two_factor_service.regenerate_recovery_codes(user_id, &request.code, &device).await {
+        Ok(response) => {
+            info!("Recovery codes regenerated for user_id: {}", user_id);
+            events.record(AuthEvent::succeeded(AuthEventType::RecoveryCodesRegenerated, user_id), &device).await;
+            Ok(Json(response))
+        },
+        Err(err) => {
+            error!("Failed to regenerate recovery codes for user_id: {}: {}", user_id, err);
+            events.record(AuthEvent::failed(AuthEventType::RecoveryCodesRegenerated, &err).for_user(user_id), &device).await;
+            Err(code_check_error("Recovery Code Regeneration Failed", err))
+        }
+    }
+}
+
+// Lockouts become 429 with a Retry-After header; everything else is handled like any other code problem
+fn code_check_error(error: &str, err: anyhow::Error) -> Response {
+    if err.downcast_ref::<LoginLocked>().is_some() {
+        return login_error(StatusCode::TOO_MANY_REQUESTS, err);
+    }
+
+    two_factor_error(error, err).into_response()
+}
+
+// Code and enrollment state problems are the caller's fault; anything else is ours
+fn two_factor_error(error: &str, err: anyhow::Error) -> (StatusCode, Json<ApiError>) {
+    let message = err.to_string();
+    let status_code = if message.contains("two-factor") || message.contains("Two-factor") {
+        StatusCode::BAD_REQUEST
+    } else {
+        StatusCode::INTERNAL_SERVER_ERROR
+    };
+
+    (
+        status_code,
+        Json(ApiError {
+            error: error.to_string(),
+            message,
+        }),
+    )
+}
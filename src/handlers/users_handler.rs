@@ -1,13 +1,22 @@
-use crate::models::auth_model::ApiError;
-use crate::models::users_model::UserResponse;
+use crate::handlers::auth_handler::{login_error, password_policy_error};
+use crate::models::auth_model::{ApiError, AuthContext, PasswordPolicyError};
+use crate::models::auth_event_model::{SecurityEventListResponse, SecurityEventQuery};
+use crate::models::users_model::*;
+use crate::services::auth_event_service::AuthEventService;
 use crate::services::database::DatabasePool;
+use crate::services::login_throttle_service::LoginLocked;
+use crate::services::mailer::Mailer;
 use crate::services::user_service::UserService;
+use crate::utils::client_device::ClientDevice;
 use axum::{
-    extract::{Path, State, Extension},
-    http::StatusCode,
-    response::Json,
+    extract::{Path, Query, State, Extension},
+    http::{header, StatusCode},
+    response::{IntoResponse, Json, Response},
 };
-use tracing::{info, error};
+use chrono::Utc;
+use std::sync::Arc;
+use validator::Validate;
+use tracing::{info, error, warn};
 
 /// Get user by ID
 #[utoipa::path(
@@ -19,7 +28,8 @@ use tracing::{info, error};
     responses(
         (status = 200, description = "User found", body = UserResponse),
         (status = 404, description = "User not found", body = ApiError),
-        (status = 401, description = "Unauthorized", body = ApiError)
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "Another user's profile, without the users:read permission", body = ApiError)
     ),
     tag = "users",
     security(
@@ -29,12 +39,12 @@ use tracing::{info, error};
 pub async fn get_user_by_id(
     State(db_pool): State<DatabasePool>,
     Path(user_id): Path<i32>,
-    Extension(current_user_id): Extension<i32>,
+    Extension(context): Extension<AuthContext>,
 ) -> Result<Json<UserResponse>, (StatusCode, Json<ApiError>)> {
     info!("Attempting to retrieve user with id: {}", user_id);
-    // Check if user is requesting their own information
-    if current_user_id != user_id {
-        error!("Forbidden request: user {} attempted to access user {} information", current_user_id, user_id);
+    // Users can read their own information; other profiles need the users:read permission
+    if context.user_id != user_id && !context.has_permission("users:read") {
+        error!("Forbidden request: user {} attempted to access user {} information", context.user_id, user_id);
         return Err((
             StatusCode::FORBIDDEN,
             Json(ApiError {
@@ -72,4 +82,352 @@ pub async fn get_user_by_id(
         ))
         },
     }
-}
\ No newline at end of file
+}
+
+/// Update the profile of the current user
+#[utoipa::path(
+    patch,
+    path = "/api/v1/users/me",
+    request_body = UpdateProfileRequest,
+    responses(
+        (status = 200, description = "Profile updated", body = UserResponse),
+        (status = 400, description = "Invalid request, e.g. an unknown time zone", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "Personal access tokens cannot edit the profile", body = ApiError)
+    ),
+    tag = "users",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn update_profile(
+    State(db_pool): State<DatabasePool>,
+    Extension(user_id): Extension<i32>,
+    Json(request): Json<UpdateProfileRequest>,
+) -> Result<Json<UserResponse>, (StatusCode, Json<ApiError>)> {
+    info!("Profile update requested for user_id: {}", user_id);
+    // Validate request
+    if let Err(errors) = request.validate() {
+        error!("Validation failed: {}", errors);
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ApiError {
+                error: "Validation Error".to_string(),
+                message: format!("Validation failed: {}", errors),
+            }),
+        ));
+    }
+
+    let user_service = UserService::new(db_pool);
+
+    match user_service.update_profile(user_id, request).await {
+        Ok(user) => {
+            info!("Profile updated for user_id: {}", user_id);
+            Ok(Json(user))
+        },
+        Err(err) => {
+            error!("Failed to update profile for user_id: {}: {}", user_id, err);
+            let status_code = if err.to_string().contains("User not found") {
+                StatusCode::NOT_FOUND
+            } else {
+                StatusCode::INTERNAL_SERVER_ERROR
+            };
+
+            Err((
+                status_code,
+                Json(ApiError {
+                    error: "Profile Update Failed".to_string(),
+                    message: err.to_string(),
+                }),
+            ))
+        }
+    }
+}
+
+/// Change the password of the current user
+#[utoipa::path(
+    put,
+    path = "/api/v1/users/me/password",
+    request_body = ChangePasswordRequest,
+    responses(
+        (status = 204, description = "Password changed; every other session is signed out and personal access tokens are revoked"),
+        (status = 400, description = "Invalid request, or the new password breaks the password policy", body = PasswordPolicyError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "Current password is incorrect, or a personal access token was used", body = ApiError),
+        (status = 409, description = "The password was changed concurrently", body = ApiError),
+        (status = 429, description = "Too many failed attempts; see the Retry-After header", body = ApiError)
+    ),
+    tag = "users",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn change_password(
+    State(db_pool): State<DatabasePool>,
+    Extension(context): Extension<AuthContext>,
+    device: ClientDevice,
+    Json(request): Json<ChangePasswordRequest>,
+) -> Result<StatusCode, Response> {
+    info!("Password change requested for user_id: {}", context.user_id);
+    // Validate request
+    if let Err(errors) = request.validate() {
+        error!("Validation failed: {}", errors);
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ApiError {
+                error: "Validation Error".to_string(),
+                message: format!("Validation failed: {}", errors),
+            }),
+        ).into_response());
+    }
+
+    let user_service = UserService::new(db_pool);
+
+    match user_service.change_password(&context, request, &device).await {
+        Ok(()) => {
+            info!("Password changed for user_id: {}", context.user_id);
+            Ok(StatusCode::NO_CONTENT)
+        },
+        Err(err) => {
+            warn!("Failed to change password for user_id: {}: {}", context.user_id, err);
+            if let Some(response) = password_policy_error(&err) {
+                return Err(response);
+            }
+            Err(account_change_error("Password Change Failed", err))
+        }
+    }
+}
+
+/// Change the email address of the current user
+#[utoipa::path(
+    put,
+    path = "/api/v1/users/me/email",
+    request_body = ChangeEmailRequest,
+    responses(
+        (status = 202, description = "Verification link sent to the new address; the email changes once it is opened"),
+        (status = 400, description = "Invalid request, or the new address is the current one", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "Current password is incorrect, or a personal access token was used", body = ApiError),
+        (status = 409, description = "An account with the new address already exists", body = ApiError),
+        (status = 429, description = "Too many failed attempts; see the Retry-After header", body = ApiError)
+    ),
+    tag = "users",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn change_email(
+    State(db_pool): State<DatabasePool>,
+    State(mailer): State<Arc<dyn Mailer>>,
+    Extension(context): Extension<AuthContext>,
+    device: ClientDevice,
+    Json(request): Json<ChangeEmailRequest>,
+) -> Result<StatusCode, Response> {
+    info!("Email change to: {} requested for user_id: {}", request.new_email, context.user_id);
+    // Validate request
+    if let Err(errors) = request.validate() {
+        error!("Validation failed: {}", errors);
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ApiError {
+                error: "Validation Error".to_string(),
+                message: format!("Validation failed: {}", errors),
+            }),
+        ).into_response());
+    }
+
+    let user_service = UserService::new(db_pool);
+
+    match user_service.change_email(&context, request, &device, mailer).await {
+        Ok(()) => {
+            info!("Email change verification sent for user_id: {}", context.user_id);
+            Ok(StatusCode::ACCEPTED)
+        },
+        Err(err) => {
+            warn!("Failed to change email for user_id: {}: {}", context.user_id, err);
+            Err(account_change_error("Email Change Failed", err))
+        }
+    }
+}
+
+/// Download everything the current user stored
+#[utoipa::path(
+    get,
+    path = "/api/v1/users/me/export",
+    responses(
+        (status = 200, description = "Zip archive with profile.json, notes.json and every note as Markdown", body = Vec<u8>, content_type = "application/zip"),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "Personal access tokens cannot export account data", body = ApiError)
+    ),
+    tag = "users",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn export_data(
+    State(db_pool): State<DatabasePool>,
+    Extension(user_id): Extension<i32>,
+) -> Result<Response, (StatusCode, Json<ApiError>)> {
+    info!("Data export requested for user_id: {}", user_id);
+    let user_service = UserService::new(db_pool);
+
+    match user_service.export_data(user_id).await {
+        Ok(archive) => {
+            info!("Exported {} bytes of data for user_id: {}", archive.len(), user_id);
+            let file_name = format!("export-{}-{}.zip", user_id, Utc::now().format("%Y%m%d"));
+            Ok((
+                [
+                    (header::CONTENT_TYPE, "application/zip".to_string()),
+                    (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", file_name)),
+                ],
+                archive,
+            ).into_response())
+        },
+        Err(err) => {
+            error!("Failed to export data for user_id: {}: {}", user_id, err);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiError {
+                    error: "Export Failed".to_string(),
+                    message: err.to_string(),
+                }),
+            ))
+        }
+    }
+}
+
+/// Delete the current user's account after a grace period
+#[utoipa::path(
+    delete,
+    path = "/api/v1/users/me",
+    request_body = DeleteAccountRequest,
+    responses(
+        (status = 202, description = "Signed out everywhere; the account is purged when the grace period ends unless the user logs in again", body = AccountDeletionResponse),
+        (status = 400, description = "Invalid request", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "Password is incorrect, or a personal access token was used", body = ApiError),
+        (status = 429, description = "Too many failed attempts; see the Retry-After header", body = ApiError)
+    ),
+    tag = "users",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn delete_account(
+    State(db_pool): State<DatabasePool>,
+    State(mailer): State<Arc<dyn Mailer>>,
+    Extension(context): Extension<AuthContext>,
+    device: ClientDevice,
+    Json(request): Json<DeleteAccountRequest>,
+) -> Result<(StatusCode, Json<AccountDeletionResponse>), Response> {
+    info!("Account deletion requested for user_id: {}", context.user_id);
+    // Validate request
+    if let Err(errors) = request.validate() {
+        error!("Validation failed: {}", errors);
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ApiError {
+                error: "Validation Error".to_string(),
+                message: format!("Validation failed: {}", errors),
+            }),
+        ).into_response());
+    }
+
+    let user_service = UserService::new(db_pool);
+
+    match user_service.schedule_deletion(&context, request, &device, mailer).await {
+        Ok(response) => {
+            warn!("Account of user_id: {} scheduled for deletion at {}", context.user_id, response.deletion_scheduled_at);
+            Ok((StatusCode::ACCEPTED, Json(response)))
+        },
+        Err(err) => {
+            warn!("Failed to schedule deletion of user_id: {}: {}", context.user_id, err);
+            Err(account_change_error("Account Deletion Failed", err))
+        }
+    }
+}
+
+/// Get the security event log of the current user
+#[utoipa::path(
+    get,
+    path = "/api/v1/users/me/security-events",
+    params(
+        ("event_type" = Option<String>, Query, description = "Only events of this type, e.g. `login`"),
+        ("success" = Option<bool>, Query, description = "Only successful (`true`) or failed (`false`) events"),
+        ("page" = Option<i64>, Query, description = "Page number, starting at 1"),
+        ("per_page" = Option<i64>, Query, description = "Events per page, at most 100 (default 20)")
+    ),
+    responses(
+        (status = 200, description = "A page of logins, password changes and other security events, newest first", body = SecurityEventListResponse),
+        (status = 400, description = "Invalid query", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 403, description = "Personal access tokens cannot read the security event log", body = ApiError)
+    ),
+    tag = "users",
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_security_events(
+    State(db_pool): State<DatabasePool>,
+    Extension(user_id): Extension<i32>,
+    Query(query): Query<SecurityEventQuery>,
+) -> Result<Json<SecurityEventListResponse>, (StatusCode, Json<ApiError>)> {
+    info!("Security events requested for user_id: {}", user_id);
+    // Validate query
+    if let Err(errors) = query.validate() {
+        error!("Validation failed: {}", errors);
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ApiError {
+                error: "Validation Error".to_string(),
+                message: format!("Validation failed: {}", errors),
+            }),
+        ));
+    }
+
+    let auth_event_service = AuthEventService::new(db_pool);
+
+    match auth_event_service
+        .get_events(Some(user_id), query.event_type, query.success, query.page, query.per_page)
+        .await
+    {
+        Ok(response) => Ok(Json(response)),
+        Err(err) => {
+            error!("Failed to retrieve security events for user_id: {}: {}", user_id, err);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiError {
+                    error: "Internal Server Error".to_string(),
+                    message: err.to_string(),
+                }),
+            ))
+        }
+    }
+}
+
+// Lockouts become 429 with a Retry-After header; wrong passwords and conflicts are the caller's fault
+fn account_change_error(error: &str, err: anyhow::Error) -> Response {
+    if err.downcast_ref::<LoginLocked>().is_some() {
+        return login_error(StatusCode::TOO_MANY_REQUESTS, err);
+    }
+
+    let message = err.to_string();
+    let status_code = if message.contains("Current password is incorrect") {
+        StatusCode::FORBIDDEN
+    } else if message.contains("already exists") || message.contains("changed concurrently") {
+        StatusCode::CONFLICT
+    } else if message.contains("is the current one") {
+        StatusCode::BAD_REQUEST
+    } else {
+        StatusCode::INTERNAL_SERVER_ERROR
+    };
+
+    (
+        status_code,
+        Json(ApiError {
+            error: error.to_string(),
+            message,
+        }),
+    ).into_response()
+}
@@ -1,7 +1,6 @@
 use axum::Router;
 use std::net::SocketAddr;
 use tower_http::cors::CorsLayer;
-use tracing_subscriber;
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 
@@ -9,17 +8,52 @@ mod handlers;
 mod models;
 mod routes;
 mod services;
+mod state;
 mod utils;
 
 use services::database::DatabasePool;
+use state::AppState;
 use crate::models::{
-    auth_model::{ApiError, AuthResponse, LoginRequest, RegisterRequest},
-    notes_model::{CreateNoteRequest, NoteResponse, SearchRequest, UpdateNoteRequest},
-    users_model::UserResponse,
+    admin_model::{AdminUserDetailResponse, AdminUserListResponse, AdminUserResponse, SuspendUserRequest},
+    auth_event_model::{SecurityEventListResponse, SecurityEventResponse},
+    auth_model::{
+        ApiError, AuthResponse, ForgotPasswordRequest, JwksResponse, LoginRequest, LoginResponse,
+        LogoutRequest, PasswordPolicyError, PasswordRuleViolation, RefreshRequest, RegisterRequest,
+        ResendVerificationRequest, ResetPasswordRequest, TwoFactorChallengeResponse, TwoFactorLoginRequest,
+        VerifyEmailRequest,
+    },
+    two_factor_model::{RecoveryCodesResponse, TotpEnrollmentResponse, TwoFactorCodeRequest},
+    oidc_model::{OidcAuthorizationResponse, OidcCallbackRequest, OidcProvidersResponse},
+    role_model::RoleResponse,
+    session_model::{RevokedSessionsResponse, SessionResponse},
+    notes_model::{
+        CreateNoteRequest, NoteListQuery, NoteListResponse, NotePermission, NoteResponse, NoteSortField, SearchRequest,
+        SharedNotesQuery, SortOrder, TagMatch, UpdateNoteRequest,
+    },
+    note_share_model::{NoteShareResponse, ShareNoteRequest, SharePermission},
+    tag_model::{CreateTagRequest, MergeTagRequest, RenameTagRequest, TagResponse},
+    notebook_model::{
+        CreateNotebookRequest, MoveNotebookRequest, NotebookDeleteMode, NotebookResponse, RenameNotebookRequest,
+    },
+    token_model::{CreatePersonalAccessTokenRequest, CreatedPersonalAccessTokenResponse, PersonalAccessTokenResponse},
+    users_model::{
+        AccountDeletionResponse, AvatarUploadRequest, AvatarUrls, ChangeEmailRequest, ChangePasswordRequest,
+        DeleteAccountRequest, UpdateProfileRequest, UserResponse,
+    },
 };
 use crate::handlers::{
+    admin_handler,
     auth_handler,
+    avatar_handler,
+    note_share_handler,
+    notebook_handler,
     notes_handler,
+    oidc_handler,
+    role_handler,
+    session_handler,
+    tag_handler,
+    token_handler,
+    two_factor_handler,
     users_handler,
 };
 
@@ -28,29 +62,147 @@ use crate::handlers::{
     paths(
         auth_handler::register,
         auth_handler::login,
+        auth_handler::login_two_factor,
+        auth_handler::refresh,
+        auth_handler::logout,
+        auth_handler::forgot_password,
+        auth_handler::reset_password,
+        auth_handler::verify_email,
+        auth_handler::resend_verification,
+        auth_handler::jwks,
+        oidc_handler::get_providers,
+        oidc_handler::authorize,
+        oidc_handler::callback,
+        two_factor_handler::enroll,
+        two_factor_handler::confirm,
+        two_factor_handler::disable,
+        two_factor_handler::regenerate_recovery_codes,
         users_handler::get_user_by_id,
+        users_handler::update_profile,
+        avatar_handler::upload_avatar,
+        avatar_handler::delete_avatar,
+        avatar_handler::get_avatar,
+        users_handler::change_password,
+        users_handler::change_email,
+        users_handler::export_data,
+        users_handler::delete_account,
+        users_handler::get_security_events,
+        session_handler::get_sessions,
+        session_handler::revoke_session,
+        session_handler::revoke_other_sessions,
+        role_handler::get_roles,
+        role_handler::assign_role,
+        role_handler::remove_role,
+        admin_handler::get_users,
+        admin_handler::get_user,
+        admin_handler::suspend_user,
+        admin_handler::unsuspend_user,
+        admin_handler::force_password_reset,
+        admin_handler::delete_user,
+        admin_handler::get_security_events,
         notes_handler::create_note,
         notes_handler::get_user_notes,
         notes_handler::get_note_by_id,
         notes_handler::update_note,
         notes_handler::delete_note,
         notes_handler::search_notes,
+        notes_handler::get_shared_notes,
+        note_share_handler::get_note_shares,
+        note_share_handler::share_note,
+        note_share_handler::unshare_note,
+        notebook_handler::get_notebooks,
+        notebook_handler::create_notebook,
+        notebook_handler::rename_notebook,
+        notebook_handler::move_notebook,
+        notebook_handler::delete_notebook,
+        tag_handler::get_tags,
+        tag_handler::create_tag,
+        tag_handler::rename_tag,
+        tag_handler::merge_tag,
+        tag_handler::delete_tag,
+        token_handler::create_token,
+        token_handler::get_tokens,
+        token_handler::revoke_token,
     ),
     components(schemas(
         RegisterRequest,
         LoginRequest,
+        RefreshRequest,
+        LogoutRequest,
+        ForgotPasswordRequest,
+        ResetPasswordRequest,
+        VerifyEmailRequest,
+        ResendVerificationRequest,
+        TwoFactorLoginRequest,
         AuthResponse,
+        LoginResponse,
+        TwoFactorChallengeResponse,
+        TwoFactorCodeRequest,
+        TotpEnrollmentResponse,
+        RecoveryCodesResponse,
+        OidcProvidersResponse,
+        OidcAuthorizationResponse,
+        OidcCallbackRequest,
+        JwksResponse,
         CreateNoteRequest,
         UpdateNoteRequest,
         NoteResponse,
+        NoteListQuery,
+        NoteListResponse,
+        NoteSortField,
+        SortOrder,
+        TagMatch,
         SearchRequest,
+        SharedNotesQuery,
+        NotePermission,
+        ShareNoteRequest,
+        NoteShareResponse,
+        SharePermission,
+        NotebookResponse,
+        CreateNotebookRequest,
+        RenameNotebookRequest,
+        MoveNotebookRequest,
+        NotebookDeleteMode,
+        TagResponse,
+        CreateTagRequest,
+        RenameTagRequest,
+        MergeTagRequest,
         UserResponse,
+        UpdateProfileRequest,
+        AvatarUrls,
+        AvatarUploadRequest,
+        ChangePasswordRequest,
+        ChangeEmailRequest,
+        DeleteAccountRequest,
+        AccountDeletionResponse,
+        SessionResponse,
+        RevokedSessionsResponse,
+        RoleResponse,
+        AdminUserResponse,
+        AdminUserDetailResponse,
+        AdminUserListResponse,
+        SuspendUserRequest,
+        SecurityEventResponse,
+        SecurityEventListResponse,
+        CreatePersonalAccessTokenRequest,
+        CreatedPersonalAccessTokenResponse,
+        PersonalAccessTokenResponse,
         ApiError,
+        PasswordPolicyError,
+        PasswordRuleViolation,
     )),
     tags(
         (name = "auth", description = "Authentication endpoints"),
+        (name = "two-factor", description = "TOTP two-factor authentication endpoints"),
+        (name = "oidc", description = "OpenID Connect login endpoints"),
         (name = "users", description = "User management endpoints"),
-        (name = "notes", description = "Notes management endpoints")
+        (name = "sessions", description = "Login session management endpoints"),
+        (name = "roles", description = "Role and permission management endpoints"),
+        (name = "admin", description = "User administration endpoints"),
+        (name = "notes", description = "Notes management endpoints"),
+        (name = "notebooks", description = "Notebook endpoints"),
+        (name = "tags", description = "Note tag endpoints"),
+        (name = "tokens", description = "Personal access token endpoints")
     )
 )]
 struct ApiDoc;
@@ -65,13 +217,37 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Load environment variables
     dotenv::dotenv().ok();
 
+    // Load token signing and verification keys
+    utils::jwt::load_keys()?;
+
+    // Configure password hashing
+    utils::password::load_config()?;
+    utils::password_policy::load_policy()?;
+
+    // Decide what accounts with an unverified email address may do
+    utils::auth_middleware::load_unverified_account_policy()?;
+
+    // Configure OpenID Connect login providers
+    utils::oidc::load_providers()?;
+
     // Initialize database pool
     let db_pool = DatabasePool::new().await?;
 
+    // Initialize the storage backend for uploaded files
+    let storage = services::storage::from_env()?;
+
+    // Delete accounts whose deletion grace period has ended
+    services::account_purge_job::spawn(db_pool.clone(), storage.clone());
+
+    // Initialize the mail transport
+    let mailer = services::mailer::from_env()?;
+    let state = AppState { db_pool, mailer, storage };
+
     // Create the router
     let app = Router::new()
         .merge(SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", ApiDoc::openapi()))
-        .nest("/api/v1", routes::create_routes(db_pool))
+        .nest("/api/v1", routes::create_routes(state))
+        .nest("/.well-known", routes::create_well_known_routes())
         .layer(CorsLayer::permissive());
 
     // Start the server
@@ -79,7 +255,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     tracing::info!("Server starting on {}", addr);
     
     let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+    // Connection info gives the login throttle the client address
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await?;
 
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,61 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+use chrono::{DateTime, Utc};
+
+/// Highest page number accepted, so the offset of a full page still fits the database's INT
+pub const MAX_PAGE: i64 = i32::MAX as i64 / 100;
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+pub struct AdminUserQuery {
+    /// Matches part of the username or email, case-insensitively
+    #[validate(length(max = 255))]
+    pub search: Option<String>,
+    #[validate(range(min = 1, max = MAX_PAGE))]
+    pub page: Option<i64>,
+    #[validate(range(min = 1, max = 100))]
+    pub per_page: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AdminUserResponse {
+    pub id: i32,
+    pub username: String,
+    pub email: String,
+    pub email_verified: bool,
+    pub roles: Vec<String>,
+    /// Set while the account is suspended
+    #[schema(value_type = Option<String>)]
+    pub suspended_at: Option<DateTime<Utc>>,
+    pub suspension_reason: Option<String>,
+    #[schema(value_type = Option<String>)]
+    pub created_at: Option<DateTime<Utc>>,
+    /// Start of the most recent session
+    #[schema(value_type = Option<String>)]
+    pub last_login_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AdminUserDetailResponse {
+    #[serde(flatten)]
+    pub user: AdminUserResponse,
+    pub two_factor_enabled: bool,
+    pub note_count: i64,
+    pub active_sessions: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AdminUserListResponse {
+    pub items: Vec<AdminUserResponse>,
+    pub page: i64,
+    pub per_page: i64,
+    /// Number of matching users across all pages
+    pub total: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+pub struct SuspendUserRequest {
+    /// Shown to other admins; not to the user
+    #[validate(length(max = 500))]
+    pub reason: Option<String>,
+}
@@ -0,0 +1,59 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+use chrono::{DateTime, Utc};
+
+/// Highest page number accepted, so the offset of a full page still fits the database's INT
+pub const MAX_PAGE: i64 = i32::MAX as i64 / 100;
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+pub struct SecurityEventQuery {
+    /// Only events of this type, e.g. `login`
+    #[validate(length(min = 1, max = 50))]
+    pub event_type: Option<String>,
+    /// Only successful (`true`) or failed (`false`) events
+    pub success: Option<bool>,
+    #[validate(range(min = 1, max = MAX_PAGE))]
+    pub page: Option<i64>,
+    #[validate(range(min = 1, max = 100))]
+    pub per_page: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+pub struct AdminSecurityEventQuery {
+    /// Only events of this user
+    pub user_id: Option<i32>,
+    #[validate(length(min = 1, max = 50))]
+    pub event_type: Option<String>,
+    pub success: Option<bool>,
+    #[validate(range(min = 1, max = MAX_PAGE))]
+    pub page: Option<i64>,
+    #[validate(range(min = 1, max = 100))]
+    pub per_page: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SecurityEventResponse {
+    pub id: i64,
+    /// Absent when the event could not be tied to an account, e.g. a failed login for an unknown email
+    pub user_id: Option<i32>,
+    /// Email address that was tried, for events that identify the account by email
+    pub email: Option<String>,
+    pub event_type: String,
+    pub success: bool,
+    /// Why the event failed, or extra detail such as the identity provider of a login
+    pub reason: Option<String>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    #[schema(value_type = Option<String>)]
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SecurityEventListResponse {
+    pub items: Vec<SecurityEventResponse>,
+    pub page: i64,
+    pub per_page: i64,
+    /// Number of matching events across all pages
+    pub total: i64,
+}
@@ -8,7 +8,8 @@ pub struct RegisterRequest {
     pub username: String,
     #[validate(email)]
     pub email: String,
-    #[validate(length(min = 6))]
+    /// Checked against the password policy
+    #[validate(length(min = 1))]
     pub password: String,
 }
 
@@ -20,22 +21,179 @@ pub struct LoginRequest {
     pub password: String,
 }
 
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+pub struct RefreshRequest {
+    #[validate(length(min = 1))]
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+pub struct ForgotPasswordRequest {
+    #[validate(email)]
+    pub email: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+pub struct ResetPasswordRequest {
+    #[validate(length(min = 1))]
+    pub token: String,
+    /// Checked against the password policy
+    #[validate(length(min = 1))]
+    pub new_password: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+pub struct VerifyEmailRequest {
+    #[validate(length(min = 1))]
+    pub token: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+pub struct ResendVerificationRequest {
+    #[validate(email)]
+    pub email: String,
+}
+
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct AuthResponse {
     pub user_id: i32,
     pub username: String,
     pub email: String,
+    /// Omitted when the tokens are delivered as cookies (`X-Auth-Mode: cookie`)
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub token: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub refresh_token: String,
+    pub expires_in: u64, // Access token lifetime in seconds
+}
+
+/// Returned by login instead of tokens when the account has two-factor authentication enabled
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct TwoFactorChallengeResponse {
+    pub two_factor_required: bool,
+    /// Exchange at `/auth/login/2fa` together with a TOTP or recovery code
+    pub challenge_token: String,
+    pub expires_in: u64, // Challenge lifetime in seconds
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(untagged)]
+pub enum LoginResponse {
+    Authenticated(AuthResponse),
+    TwoFactorRequired(TwoFactorChallengeResponse),
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+pub struct TwoFactorLoginRequest {
+    #[validate(length(min = 1))]
+    pub challenge_token: String,
+    /// Current TOTP code or an unused recovery code
+    #[validate(length(min = 1))]
+    pub code: String,
+}
+
+/// Account row as returned by `sp_login_user` and `sp_get_user_credentials`
+#[derive(Debug, Clone)]
+pub struct UserCredentials {
+    pub user_id: i32,
+    pub username: String,
+    pub email: String,
+    pub password_hash: String,
+    pub token_version: i32,
+    pub totp_enabled: bool,
+    pub suspended: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct Claims {
     pub sub: String, // Subject (user ID)
     pub exp: usize,  // Expiration time
+    pub jti: String, // Unique token ID, used for revocation
+    pub ver: i32,    // User's token version at issue time
+    #[serde(default)]
+    #[schema(value_type = Option<String>)]
+    pub sid: Option<uuid::Uuid>, // Session the token belongs to
+    #[serde(default)]
+    pub roles: Vec<String>,
+    #[serde(default)]
+    pub permissions: Vec<String>, // Granted by the roles at issue time
+}
+
+/// Roles of a user and the permissions they grant, as embedded in access tokens
+#[derive(Debug, Clone, Default)]
+pub struct UserAccess {
+    pub roles: Vec<String>,
+    pub permissions: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TwoFactorChallengeClaims {
+    pub sub: String, // Subject (user ID)
+    pub exp: usize,  // Expiration time
+    pub aud: String, // Always "2fa"
+}
+
+/// Identity of the authenticated caller, inserted into request extensions by `auth_middleware`
+#[derive(Debug, Clone)]
+pub struct AuthContext {
+    pub user_id: i32,
+    pub token_id: String,
+    pub expires_at: usize,
+    pub email_verified: bool,
+    /// Scopes of a personal access token; `None` for an interactive session, which may do anything
+    pub scopes: Option<Vec<String>>,
+    /// Login session of an access token; `None` for personal access tokens
+    pub session_id: Option<uuid::Uuid>,
+    /// Permissions granted by the user's roles; always empty for personal access tokens
+    pub permissions: Vec<String>,
+}
+
+impl AuthContext {
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.as_ref().is_none_or(|scopes| scopes.iter().any(|granted| granted == scope))
+    }
+
+    pub fn is_session(&self) -> bool {
+        self.scopes.is_none()
+    }
+
+    pub fn has_permission(&self, permission: &str) -> bool {
+        self.permissions.iter().any(|granted| granted == permission)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct LogoutRequest {
+    /// Refresh token of this device; its token family is revoked as well (the current session always is)
+    pub refresh_token: Option<String>,
+    /// Invalidate every outstanding access, refresh and personal access token of the user
+    #[serde(default)]
+    pub all_devices: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct ApiError {
     pub error: String,
     pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PasswordRuleViolation {
+    /// One of: min_length, max_length, contains_username, contains_email, breached, strength
+    pub rule: String,
+    pub message: String,
+}
+
+/// Returned with 400 when a new password breaks the password policy
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct PasswordPolicyError {
+    pub error: String,
+    pub message: String,
+    pub violations: Vec<PasswordRuleViolation>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct JwksResponse {
+    #[schema(value_type = Vec<Object>)]
+    pub keys: Vec<jsonwebtoken::jwk::Jwk>,
 }
\ No newline at end of file
@@ -1,3 +1,13 @@
 pub mod auth_model;
 pub mod notes_model;
-pub mod users_model;
\ No newline at end of file
+pub mod users_model;
+pub mod two_factor_model;
+pub mod token_model;
+pub mod oidc_model;
+pub mod session_model;
+pub mod role_model;
+pub mod admin_model;
+pub mod auth_event_model;
+pub mod tag_model;
+pub mod notebook_model;
+pub mod note_share_model;
\ No newline at end of file
@@ -0,0 +1,47 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+use chrono::{DateTime, Utc};
+
+/// Access granted to a collaborator
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SharePermission {
+    /// View the note
+    Read,
+    /// View the note and change its title and content
+    Write,
+}
+
+impl SharePermission {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SharePermission::Read => "read",
+            SharePermission::Write => "write",
+        }
+    }
+
+    pub fn from_db(permission: &str) -> Self {
+        match permission {
+            "write" => SharePermission::Write,
+            _ => SharePermission::Read,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+pub struct ShareNoteRequest {
+    /// Username of the user to share the note with
+    #[validate(length(min = 1, max = 100))]
+    pub username: String,
+    pub permission: SharePermission,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct NoteShareResponse {
+    pub user_id: i32,
+    pub username: String,
+    pub permission: SharePermission,
+    #[schema(value_type = Option<String>)]
+    pub created_at: Option<DateTime<Utc>>,
+}
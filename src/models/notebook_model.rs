@@ -0,0 +1,55 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+use chrono::{DateTime, Utc};
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct NotebookResponse {
+    pub id: i32,
+    /// Notebook this one is nested in; absent for top-level notebooks
+    pub parent_id: Option<i32>,
+    pub name: String,
+    /// Number of notes directly in the notebook, not counting nested notebooks
+    pub note_count: i64,
+    #[schema(value_type = Option<String>)]
+    pub created_at: Option<DateTime<Utc>>,
+    #[schema(value_type = Option<String>)]
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+pub struct CreateNotebookRequest {
+    #[validate(length(min = 1, max = 100))]
+    pub name: String,
+    /// Notebook to nest the new one in; omit for a top-level notebook
+    pub parent_id: Option<i32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+pub struct RenameNotebookRequest {
+    #[validate(length(min = 1, max = 100))]
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct MoveNotebookRequest {
+    /// New parent notebook; `null` moves the notebook to the top level
+    pub parent_id: Option<i32>,
+}
+
+/// What happens to the contents of a deleted notebook
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum NotebookDeleteMode {
+    /// Notes and nested notebooks move up to the deleted notebook's parent
+    #[default]
+    MoveUp,
+    /// Nested notebooks and every note in them are deleted too
+    Cascade,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct DeleteNotebookQuery {
+    /// `move_up` (default) or `cascade`
+    pub mode: Option<NotebookDeleteMode>,
+}
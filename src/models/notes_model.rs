@@ -1,7 +1,11 @@
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
-use validator::Validate;
+use validator::{Validate, ValidationError};
 use chrono::{DateTime, Utc};
+use crate::models::tag_model::validate_tag_name;
+
+/// Most tags a single note can carry
+pub const MAX_NOTE_TAGS: usize = 20;
 
 #[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
 pub struct CreateNoteRequest {
@@ -9,6 +13,12 @@ pub struct CreateNoteRequest {
     pub title: String,
     pub content: String,
     pub id: Option<i32>,
+    /// Tag names; tags the user does not have yet are created
+    #[serde(default)]
+    #[validate(custom(function = "validate_note_tags"))]
+    pub tags: Vec<String>,
+    /// Notebook to put the note in; omit to keep it outside of any notebook
+    pub notebook_id: Option<i32>,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
@@ -16,6 +26,15 @@ pub struct UpdateNoteRequest {
     #[validate(length(min = 1, max = 255))]
     pub title: String,
     pub content: String,
+    /// Replaces the note's tags; tags the user does not have yet are created. Omit to keep the
+    /// current tags, send `[]` to remove them all. Only the owner may send it.
+    #[validate(custom(function = "validate_note_tags"))]
+    pub tags: Option<Vec<String>>,
+    /// Notebook to move the note to, or `null` to take it out of its notebook. Omit to keep the
+    /// note where it is. Only the owner may send it.
+    #[serde(default, deserialize_with = "present", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<i32>)]
+    pub notebook_id: Option<Option<i32>>,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
@@ -27,9 +46,243 @@ pub struct NoteResponse {
     pub created_at: DateTime<Utc>,
     #[schema(value_type = String)]
     pub updated_at: DateTime<Utc>,
+    /// Tag names in alphabetical order
+    pub tags: Vec<String>,
+    /// Notebook the note is in; absent for notes outside of any notebook and for notes shared
+    /// with the caller, since notebooks are private to their owner
+    pub notebook_id: Option<i32>,
+    pub owner_id: i32,
+    /// What the caller may do with the note
+    pub permission: NotePermission,
 }
 
-#[derive(Debug, Serialize, Deserialize, ToSchema)]
+/// Access the caller has to a note: their own, or shared with them for writing or reading
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum NotePermission {
+    Owner,
+    Write,
+    Read,
+}
+
+impl NotePermission {
+    pub fn from_db(permission: &str) -> Self {
+        match permission {
+            "owner" => NotePermission::Owner,
+            "write" => NotePermission::Write,
+            _ => NotePermission::Read,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum NoteSortField {
+    Title,
+    CreatedAt,
+    #[default]
+    UpdatedAt,
+}
+
+impl NoteSortField {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            NoteSortField::Title => "title",
+            NoteSortField::CreatedAt => "created_at",
+            NoteSortField::UpdatedAt => "updated_at",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+/// Whether a tag filter needs every listed tag on a note or just one of them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TagMatch {
+    #[default]
+    All,
+    Any,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+#[validate(schema(function = "validate_note_list_ranges"))]
+pub struct NoteListQuery {
+    /// Notes per page, at most 100 (default 20)
+    #[validate(range(min = 1, max = 100))]
+    pub limit: Option<i64>,
+    /// `next_cursor` of the previous page
+    pub cursor: Option<String>,
+    /// Also count the notes across all pages
+    pub include_total: Option<bool>,
+    /// Defaults to `updated_at`
+    pub sort: Option<NoteSortField>,
+    /// Defaults to `asc` for `title` and `desc` for the timestamps
+    pub order: Option<SortOrder>,
+    /// Only notes created at or after this instant. Any UTC offset works; the bound is converted
+    /// to the Asia/Bangkok wall-clock time note timestamps are stored in before comparing.
+    #[schema(value_type = Option<String>)]
+    pub created_from: Option<DateTime<Utc>>,
+    /// Only notes created before this instant, on the same clock as `created_from`
+    #[schema(value_type = Option<String>)]
+    pub created_to: Option<DateTime<Utc>>,
+    /// Only notes last updated at or after this instant, on the same clock as `created_from`
+    #[schema(value_type = Option<String>)]
+    pub updated_from: Option<DateTime<Utc>>,
+    /// Only notes last updated before this instant, on the same clock as `created_from`
+    #[schema(value_type = Option<String>)]
+    pub updated_to: Option<DateTime<Utc>>,
+    /// Only notes with at least this many characters of content
+    #[validate(range(min = 0))]
+    pub min_length: Option<i32>,
+    /// Only notes with at most this many characters of content
+    #[validate(range(min = 0))]
+    pub max_length: Option<i32>,
+    /// Only notes with these tags, as comma-separated names
+    pub tag: Option<String>,
+    /// Whether notes need `all` of the tags (default) or `any` of them
+    pub tag_match: Option<TagMatch>,
+    /// Only notes in this notebook
+    pub notebook_id: Option<i32>,
+    /// With `notebook_id`, also notes in the notebooks nested below it
+    pub include_sub_notebooks: Option<bool>,
+}
+
+impl NoteListQuery {
+    pub fn sort(&self) -> NoteSortField {
+        self.sort.unwrap_or_default()
+    }
+
+    pub fn order(&self) -> SortOrder {
+        self.order.unwrap_or(match self.sort() {
+            NoteSortField::Title => SortOrder::Asc,
+            NoteSortField::CreatedAt | NoteSortField::UpdatedAt => SortOrder::Desc,
+        })
+    }
+
+    /// Lowercase names of the tags to filter by, without duplicates; `None` when not filtering
+    pub fn tag_filter(&self) -> Option<Vec<String>> {
+        let mut tags: Vec<String> = Vec::new();
+        for tag in self.tag.as_deref()?.split(',') {
+            let tag = tag.trim().to_lowercase();
+            if !tag.is_empty() && !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+
+        (!tags.is_empty()).then_some(tags)
+    }
+
+    pub fn tag_match(&self) -> TagMatch {
+        self.tag_match.unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
 pub struct SearchRequest {
     pub search_term: Option<String>,
+    /// Notes per page, at most 100 (default 20)
+    #[validate(range(min = 1, max = 100))]
+    pub limit: Option<i64>,
+    /// `next_cursor` of the previous page
+    pub cursor: Option<String>,
+    /// Also count the matching notes across all pages
+    pub include_total: Option<bool>,
+    /// Only notes in this notebook
+    pub notebook_id: Option<i32>,
+    /// With `notebook_id`, also notes in the notebooks nested below it
+    pub include_sub_notebooks: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+pub struct SharedNotesQuery {
+    /// Notes per page, at most 100 (default 20)
+    #[validate(range(min = 1, max = 100))]
+    pub limit: Option<i64>,
+    /// `next_cursor` of the previous page
+    pub cursor: Option<String>,
+}
+
+/// One page of notes
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct NoteListResponse {
+    pub items: Vec<NoteResponse>,
+    /// Pass as `cursor` to get the next page; absent on the last page
+    pub next_cursor: Option<String>,
+    /// Number of notes across all pages, when `include_total` was set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<i64>,
+}
+
+// Empty ranges are almost always swapped bounds; say so instead of returning an empty page
+fn validate_note_list_ranges(query: &NoteListQuery) -> Result<(), ValidationError> {
+    let ordered = |from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>| match (from, to) {
+        (Some(from), Some(to)) => from < to,
+        _ => true,
+    };
+
+    if !ordered(query.created_from, query.created_to) {
+        return Err(ValidationError::new("created_range").with_message("created_from must be before created_to".into()));
+    }
+    if !ordered(query.updated_from, query.updated_to) {
+        return Err(ValidationError::new("updated_range").with_message("updated_from must be before updated_to".into()));
+    }
+    if let (Some(min_length), Some(max_length)) = (query.min_length, query.max_length)
+        && min_length > max_length
+    {
+        return Err(ValidationError::new("length_range").with_message("min_length must not exceed max_length".into()));
+    }
+
+    Ok(())
+}
+
+// Tells a field sent as `null` (`Some(None)`) apart from one left out (`None`, via `default`)
+fn present<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+fn validate_note_tags(tags: &[String]) -> Result<(), ValidationError> {
+    if tags.len() > MAX_NOTE_TAGS {
+        return Err(ValidationError::new("tags").with_message(format!("a note can have at most {} tags", MAX_NOTE_TAGS).into()));
+    }
+
+    tags.iter().try_for_each(|tag| validate_tag_name(tag))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list_query(tag: Option<&str>) -> NoteListQuery {
+        serde_json::from_value(serde_json::json!({ "tag": tag })).unwrap()
+    }
+
+    #[test]
+    fn tag_filter_lowercases_trims_and_drops_duplicates() {
+        assert_eq!(
+            list_query(Some(" Work,urgent , WORK,work ,Urgent")).tag_filter(),
+            Some(vec!["work".to_string(), "urgent".to_string()])
+        );
+    }
+
+    #[test]
+    fn tag_filter_skips_empty_names() {
+        assert_eq!(list_query(Some(",home,, ,")).tag_filter(), Some(vec!["home".to_string()]));
+    }
+
+    #[test]
+    fn tag_filter_is_none_without_tags() {
+        assert_eq!(list_query(None).tag_filter(), None);
+        assert_eq!(list_query(Some("")).tag_filter(), None);
+        assert_eq!(list_query(Some(" , ,")).tag_filter(), None);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,47 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct OidcProvidersResponse {
+    /// Names of the configured providers, as used in the login URLs
+    pub providers: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct OidcAuthorizationResponse {
+    /// Send the user here; the provider redirects back to the configured redirect URI
+    pub authorization_url: String,
+    pub expires_in: u64, // Seconds until the login has to be completed
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+pub struct OidcCallbackRequest {
+    /// `code` query parameter of the redirect
+    #[validate(length(min = 1))]
+    pub code: String,
+    /// `state` query parameter of the redirect
+    #[validate(length(min = 1))]
+    pub state: String,
+}
+
+/// Claims of an OpenID Connect ID token that the login relies on
+#[derive(Debug, Deserialize)]
+pub struct IdTokenClaims {
+    pub sub: String,
+    pub nonce: Option<String>,
+    pub email: Option<String>,
+    /// Some providers send the flag as a string
+    pub email_verified: Option<serde_json::Value>,
+    pub preferred_username: Option<String>,
+}
+
+impl IdTokenClaims {
+    pub fn is_email_verified(&self) -> bool {
+        match &self.email_verified {
+            Some(serde_json::Value::Bool(verified)) => *verified,
+            Some(serde_json::Value::String(verified)) => verified == "true",
+            _ => false,
+        }
+    }
+}
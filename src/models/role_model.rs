@@ -0,0 +1,10 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RoleResponse {
+    pub name: String,
+    pub description: Option<String>,
+    /// Permissions the role grants, e.g. `users:admin`
+    pub permissions: Vec<String>,
+}
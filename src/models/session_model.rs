@@ -0,0 +1,26 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SessionResponse {
+    #[schema(value_type = String)]
+    pub id: Uuid,
+    /// `X-Device-Name` sent at login, or a description of the user agent
+    pub device_name: String,
+    pub user_agent: Option<String>,
+    /// Address of the login or the latest token refresh
+    pub ip_address: Option<String>,
+    #[schema(value_type = Option<String>)]
+    pub created_at: Option<DateTime<Utc>>,
+    #[schema(value_type = Option<String>)]
+    pub last_seen_at: Option<DateTime<Utc>>,
+    /// Whether this is the session making the request
+    pub current: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RevokedSessionsResponse {
+    pub revoked_sessions: i32,
+}
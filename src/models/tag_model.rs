@@ -0,0 +1,44 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::{Validate, ValidationError};
+use chrono::{DateTime, Utc};
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct TagResponse {
+    pub id: i32,
+    pub name: String,
+    /// Number of notes carrying the tag
+    pub note_count: i64,
+    #[schema(value_type = Option<String>)]
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+pub struct CreateTagRequest {
+    #[validate(custom(function = "validate_tag_name"))]
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+pub struct RenameTagRequest {
+    /// New name; may differ from the current one only in case
+    #[validate(custom(function = "validate_tag_name"))]
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct MergeTagRequest {
+    /// Tag that takes over the notes; the merged tag is deleted
+    pub into_tag_id: i32,
+}
+
+// Commas separate tags in the `tag` filter of the note listing, so names cannot contain them
+pub fn validate_tag_name(name: &str) -> Result<(), ValidationError> {
+    let length = name.chars().count();
+    if length == 0 || length > 50 || name.trim() != name || name.contains(',') {
+        return Err(ValidationError::new("tag")
+            .with_message("tags must be 1 to 50 characters without commas or surrounding spaces".into()));
+    }
+
+    Ok(())
+}
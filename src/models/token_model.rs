@@ -0,0 +1,42 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+use chrono::{DateTime, Utc};
+
+/// Every scope a personal access token can be granted
+pub const TOKEN_SCOPES: &[&str] = &["notes:read", "notes:write", "users:read"];
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+pub struct CreatePersonalAccessTokenRequest {
+    #[validate(length(min = 1, max = 100))]
+    pub name: String,
+    /// Any of: notes:read, notes:write, users:read
+    #[validate(length(min = 1))]
+    pub scopes: Vec<String>,
+    /// Days until the token expires; omit for a token that never expires
+    #[validate(range(min = 1, max = 3650))]
+    pub expires_in_days: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct PersonalAccessTokenResponse {
+    pub id: i32,
+    pub name: String,
+    /// First characters of the token, to tell tokens apart
+    pub token_prefix: String,
+    pub scopes: Vec<String>,
+    #[schema(value_type = Option<String>)]
+    pub expires_at: Option<DateTime<Utc>>,
+    #[schema(value_type = Option<String>)]
+    pub last_used_at: Option<DateTime<Utc>>,
+    #[schema(value_type = String)]
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CreatedPersonalAccessTokenResponse {
+    /// The token itself; it is only shown once
+    pub token: String,
+    #[serde(flatten)]
+    pub details: PersonalAccessTokenResponse,
+}
@@ -0,0 +1,24 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct TotpEnrollmentResponse {
+    /// Base32 encoded shared secret, for manual entry
+    pub secret: String,
+    /// `otpauth://` URI, usually rendered as a QR code
+    pub otpauth_uri: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+pub struct TwoFactorCodeRequest {
+    /// Current TOTP code; where noted, an unused recovery code is accepted as well
+    #[validate(length(min = 1))]
+    pub code: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RecoveryCodesResponse {
+    /// Single-use recovery codes; they are shown only once
+    pub recovery_codes: Vec<String>,
+}
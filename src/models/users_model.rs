@@ -1,12 +1,116 @@
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
+use validator::{Validate, ValidationError};
 use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct UserResponse {
     pub id: i32,
     pub username: String,
     pub email: String,
+    pub email_verified: bool,
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+    /// BCP 47 language tag, e.g. `en-US`
+    pub locale: Option<String>,
+    /// IANA time zone, e.g. `Europe/Berlin`
+    pub timezone: Option<String>,
+    /// Absent until the user uploads an avatar
+    pub avatar_urls: Option<AvatarUrls>,
     #[schema(value_type = String)]
     pub created_at: DateTime<Utc>,
-}
\ No newline at end of file
+    #[schema(value_type = String)]
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Square PNG thumbnails of the user's avatar
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AvatarUrls {
+    /// 64 x 64 pixels
+    pub small: String,
+    /// 128 x 128 pixels
+    pub medium: String,
+    /// 256 x 256 pixels
+    pub large: String,
+}
+
+/// Multipart form with the image in an `avatar` field
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AvatarUploadRequest {
+    /// PNG, JPEG, GIF or WebP image
+    #[schema(value_type = String, format = Binary)]
+    pub avatar: Vec<u8>,
+}
+
+/// Fields left out keep their value; an empty string clears the field
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+pub struct UpdateProfileRequest {
+    #[validate(length(max = 100))]
+    pub display_name: Option<String>,
+    #[validate(length(max = 500))]
+    pub bio: Option<String>,
+    /// BCP 47 language tag, e.g. `en-US`
+    #[validate(length(max = 35), custom(function = "validate_locale"))]
+    pub locale: Option<String>,
+    /// IANA time zone, e.g. `Europe/Berlin`
+    #[validate(custom(function = "validate_timezone"))]
+    pub timezone: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+pub struct ChangePasswordRequest {
+    #[validate(length(min = 1))]
+    pub current_password: String,
+    /// Checked against the password policy
+    #[validate(length(min = 1))]
+    pub new_password: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+pub struct ChangeEmailRequest {
+    #[validate(email, length(max = 255))]
+    pub new_email: String,
+    #[validate(length(min = 1))]
+    pub current_password: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+pub struct DeleteAccountRequest {
+    #[validate(length(min = 1))]
+    pub password: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AccountDeletionResponse {
+    /// When the account and all of its data will be purged; logging in before then cancels the deletion
+    #[schema(value_type = String)]
+    pub deletion_scheduled_at: DateTime<Utc>,
+}
+
+// Language, then optional script, region or variant subtags: `en`, `pt-BR`, `zh-Hant-TW`
+fn validate_locale(locale: &str) -> Result<(), ValidationError> {
+    if locale.is_empty() {
+        return Ok(());
+    }
+
+    let mut subtags = locale.split('-');
+    let language_valid = subtags
+        .next()
+        .is_some_and(|language| (2..=3).contains(&language.len()) && language.chars().all(|c| c.is_ascii_alphabetic()));
+    let subtags_valid = subtags.all(|subtag| (2..=8).contains(&subtag.len()) && subtag.chars().all(|c| c.is_ascii_alphanumeric()));
+
+    if language_valid && subtags_valid {
+        Ok(())
+    } else {
+        Err(ValidationError::new("locale").with_message("must be a language tag such as en-US".into()))
+    }
+}
+
+fn validate_timezone(timezone: &str) -> Result<(), ValidationError> {
+    if timezone.is_empty() || timezone.parse::<Tz>().is_ok() {
+        Ok(())
+    } else {
+        Err(ValidationError::new("timezone").with_message("must be an IANA time zone such as Europe/Berlin".into()))
+    }
+}
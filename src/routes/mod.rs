@@ -1,30 +1,244 @@
 use axum::{
+    extract::DefaultBodyLimit,
     middleware,
-    routing::{get, post, put, delete},
+    routing::{get, post, put, patch, delete},
     Router,
 };
-use crate::handlers::{auth_handler, notes_handler, users_handler};
-use crate::services::database::DatabasePool;
-use crate::utils::auth_middleware::auth_middleware;
+use crate::handlers::{
+    admin_handler, auth_handler, avatar_handler, note_share_handler, notebook_handler, notes_handler, oidc_handler,
+    role_handler, session_handler, tag_handler, token_handler, two_factor_handler, users_handler,
+};
+use crate::services::avatar_service;
+use crate::state::AppState;
+use crate::utils::auth_middleware::{
+    auth_middleware, require_permission, require_scope, require_session, verified_email_middleware,
+};
+
+pub fn create_routes(state: AppState) -> Router {
+    let db_pool = state.db_pool.clone();
 
-pub fn create_routes(db_pool: DatabasePool) -> Router {
     let auth_routes = Router::new()
         .route("/register", post(auth_handler::register))
         .route("/login", post(auth_handler::login))
-        .with_state(db_pool.clone());
+        .route("/login/2fa", post(auth_handler::login_two_factor))
+        .route("/refresh", post(auth_handler::refresh))
+        .route("/forgot-password", post(auth_handler::forgot_password))
+        .route("/reset-password", post(auth_handler::reset_password))
+        .route("/verify-email", post(auth_handler::verify_email))
+        .route("/resend-verification", post(auth_handler::resend_verification))
+        .route("/oidc/providers", get(oidc_handler::get_providers))
+        .route("/oidc/{provider}/authorize", get(oidc_handler::authorize))
+        .route("/oidc/{provider}/callback", post(oidc_handler::callback))
+        .route(
+            "/logout",
+            post(auth_handler::logout)
+                .layer(middleware::from_fn(require_session))
+                .layer(middleware::from_fn_with_state(db_pool.clone(), auth_middleware)),
+        )
+        .with_state(state.clone());
+
+    // Account security settings stay reachable for unverified accounts
+    let two_factor_routes = Router::new()
+        .route("/enroll", post(two_factor_handler::enroll))
+        .route("/confirm", post(two_factor_handler::confirm))
+        .route("/disable", post(two_factor_handler::disable))
+        .route("/recovery-codes", post(two_factor_handler::regenerate_recovery_codes))
+        .layer(middleware::from_fn(require_session))
+        .layer(middleware::from_fn_with_state(db_pool.clone(), auth_middleware))
+        .with_state(state.clone());
 
+    // Personal access tokens are accepted here, limited to the scope each route requires
     let protected_routes = Router::new()
-        .route("/users/{id}", get(users_handler::get_user_by_id))
-        .route("/notes", post(notes_handler::create_note))
-        .route("/notes", get(notes_handler::get_user_notes))
-        .route("/notes/search", get(notes_handler::search_notes))
-        .route("/notes/{id}", get(notes_handler::get_note_by_id))
-        .route("/notes/{id}", put(notes_handler::update_note))
-        .route("/notes/{id}", delete(notes_handler::delete_note))
-        .layer(middleware::from_fn(auth_middleware))
-        .with_state(db_pool);
+        .route(
+            "/users/{id}",
+            get(users_handler::get_user_by_id)
+                .layer(middleware::from_fn_with_state("users:read", require_scope)),
+        )
+        .route(
+            "/notes",
+            post(notes_handler::create_note)
+                .layer(middleware::from_fn_with_state("notes:write", require_scope)),
+        )
+        .route(
+            "/notes",
+            get(notes_handler::get_user_notes)
+                .layer(middleware::from_fn_with_state("notes:read", require_scope)),
+        )
+        .route(
+            "/notes/search",
+            get(notes_handler::search_notes)
+                .layer(middleware::from_fn_with_state("notes:read", require_scope)),
+        )
+        .route(
+            "/notes/shared-with-me",
+            get(notes_handler::get_shared_notes)
+                .layer(middleware::from_fn_with_state("notes:read", require_scope)),
+        )
+        .route(
+            "/notes/{id}",
+            get(notes_handler::get_note_by_id)
+                .layer(middleware::from_fn_with_state("notes:read", require_scope)),
+        )
+        .route(
+            "/notes/{id}",
+            put(notes_handler::update_note)
+                .layer(middleware::from_fn_with_state("notes:write", require_scope)),
+        )
+        .route(
+            "/notes/{id}",
+            delete(notes_handler::delete_note)
+                .layer(middleware::from_fn_with_state("notes:write", require_scope)),
+        )
+        .route(
+            "/notes/{id}/shares",
+            get(note_share_handler::get_note_shares)
+                .layer(middleware::from_fn_with_state("notes:read", require_scope)),
+        )
+        .route(
+            "/notes/{id}/shares",
+            post(note_share_handler::share_note)
+                .layer(middleware::from_fn_with_state("notes:write", require_scope)),
+        )
+        .route(
+            "/notes/{id}/shares/{user_id}",
+            delete(note_share_handler::unshare_note)
+                .layer(middleware::from_fn_with_state("notes:write", require_scope)),
+        )
+        .route(
+            "/notebooks",
+            get(notebook_handler::get_notebooks)
+                .layer(middleware::from_fn_with_state("notes:read", require_scope)),
+        )
+        .route(
+            "/notebooks",
+            post(notebook_handler::create_notebook)
+                .layer(middleware::from_fn_with_state("notes:write", require_scope)),
+        )
+        .route(
+            "/notebooks/{id}",
+            patch(notebook_handler::rename_notebook)
+                .delete(notebook_handler::delete_notebook)
+                .layer(middleware::from_fn_with_state("notes:write", require_scope)),
+        )
+        .route(
+            "/notebooks/{id}/move",
+            post(notebook_handler::move_notebook)
+                .layer(middleware::from_fn_with_state("notes:write", require_scope)),
+        )
+        .route(
+            "/tags",
+            get(tag_handler::get_tags)
+                .layer(middleware::from_fn_with_state("notes:read", require_scope)),
+        )
+        .route(
+            "/tags",
+            post(tag_handler::create_tag)
+                .layer(middleware::from_fn_with_state("notes:write", require_scope)),
+        )
+        .route(
+            "/tags/{id}",
+            patch(tag_handler::rename_tag)
+                .delete(tag_handler::delete_tag)
+                .layer(middleware::from_fn_with_state("notes:write", require_scope)),
+        )
+        .route(
+            "/tags/{id}/merge",
+            post(tag_handler::merge_tag)
+                .layer(middleware::from_fn_with_state("notes:write", require_scope)),
+        )
+        .route(
+            "/tokens",
+            post(token_handler::create_token)
+                .get(token_handler::get_tokens)
+                .layer(middleware::from_fn(require_session)),
+        )
+        .route(
+            "/tokens/{id}",
+            delete(token_handler::revoke_token).layer(middleware::from_fn(require_session)),
+        )
+        .route(
+            "/roles",
+            get(role_handler::get_roles)
+                .layer(middleware::from_fn_with_state("roles:read", require_permission)),
+        )
+        .route(
+            "/users/{id}/roles/{role}",
+            put(role_handler::assign_role)
+                .delete(role_handler::remove_role)
+                .layer(middleware::from_fn_with_state("roles:assign", require_permission)),
+        )
+        .route(
+            "/users/me",
+            patch(users_handler::update_profile)
+                .delete(users_handler::delete_account)
+                .layer(middleware::from_fn(require_session)),
+        )
+        .route(
+            "/users/me/avatar",
+            put(avatar_handler::upload_avatar)
+                .delete(avatar_handler::delete_avatar)
+                // Room for the multipart framing around the image
+                .layer(DefaultBodyLimit::max(avatar_service::max_upload_bytes() + 64 * 1024))
+                .layer(middleware::from_fn(require_session)),
+        )
+        .route(
+            "/users/me/export",
+            get(users_handler::export_data).layer(middleware::from_fn(require_session)),
+        )
+        .route(
+            "/users/me/password",
+            put(users_handler::change_password).layer(middleware::from_fn(require_session)),
+        )
+        .route(
+            "/users/me/email",
+            put(users_handler::change_email).layer(middleware::from_fn(require_session)),
+        )
+        .route(
+            "/users/me/security-events",
+            get(users_handler::get_security_events).layer(middleware::from_fn(require_session)),
+        )
+        .route(
+            "/users/me/sessions",
+            get(session_handler::get_sessions).layer(middleware::from_fn(require_session)),
+        )
+        .route(
+            "/users/me/sessions/logout-others",
+            post(session_handler::revoke_other_sessions).layer(middleware::from_fn(require_session)),
+        )
+        .route(
+            "/users/me/sessions/{id}",
+            delete(session_handler::revoke_session).layer(middleware::from_fn(require_session)),
+        )
+        .layer(middleware::from_fn(verified_email_middleware))
+        .layer(middleware::from_fn_with_state(db_pool.clone(), auth_middleware))
+        .with_state(state.clone());
+
+    // Avatars are loaded by <img> tags, which cannot send a bearer token
+    let avatar_routes = Router::new()
+        .route("/{user_id}/{version}/{size}", get(avatar_handler::get_avatar))
+        .with_state(state.clone());
+
+    // Every admin route requires the users:admin permission, which personal access tokens never carry
+    let admin_routes = Router::new()
+        .route("/users", get(admin_handler::get_users))
+        .route("/users/{id}", get(admin_handler::get_user).delete(admin_handler::delete_user))
+        .route("/users/{id}/suspend", post(admin_handler::suspend_user))
+        .route("/users/{id}/unsuspend", post(admin_handler::unsuspend_user))
+        .route("/users/{id}/force-password-reset", post(admin_handler::force_password_reset))
+        .route("/security-events", get(admin_handler::get_security_events))
+        .layer(middleware::from_fn_with_state("users:admin", require_permission))
+        .layer(middleware::from_fn_with_state(db_pool, auth_middleware))
+        .with_state(state);
 
     Router::new()
         .nest("/auth", auth_routes)
+        .nest("/auth/2fa", two_factor_routes)
+        .nest("/admin", admin_routes)
+        .nest("/avatars", avatar_routes)
         .merge(protected_routes)
-}
\ No newline at end of file
+}
+
+pub fn create_well_known_routes() -> Router {
+    Router::new()
+        .route("/jwks.json", get(auth_handler::jwks))
+}
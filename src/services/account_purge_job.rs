@@ -0,0 +1,50 @@
+use crate::services::avatar_service::AvatarService;
+use crate::services::database::DatabasePool;
+use crate::services::storage::Storage;
+use anyhow::Result;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::MissedTickBehavior;
+use tracing::{error, info};
+
+const DEFAULT_ACCOUNT_PURGE_INTERVAL_SECONDS: u64 = 60 * 60; // 1 hour
+
+/// Start the background job that deletes accounts whose deletion grace period has ended.
+/// It runs right away and then every `ACCOUNT_PURGE_INTERVAL_SECONDS`.
+pub fn spawn(db: DatabasePool, storage: Arc<dyn Storage>) {
+    let interval_seconds = std::env::var("ACCOUNT_PURGE_INTERVAL_SECONDS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .filter(|&seconds: &u64| seconds > 0)
+        .unwrap_or(DEFAULT_ACCOUNT_PURGE_INTERVAL_SECONDS);
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(interval_seconds));
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            interval.tick().await;
+            match purge_deleted_accounts(&db, &storage).await {
+                Ok(0) => {}
+                Ok(purged) => info!("Purged {} accounts at the end of their deletion grace period", purged),
+                // Try again on the next tick
+                Err(err) => error!("Failed to purge deleted accounts: {}", err),
+            }
+        }
+    });
+}
+
+async fn purge_deleted_accounts(db: &DatabasePool, storage: &Arc<dyn Storage>) -> Result<usize> {
+    let query = "SELECT * FROM sp_purge_deleted_users()";
+    let rows = db.execute_query(query, &[]).await?;
+
+    let avatar_service = AvatarService::new(db.clone(), storage.clone());
+    for row in &rows {
+        let user_id: i32 = row.get("userid");
+        if let Err(err) = avatar_service.delete_user_files(user_id).await {
+            error!("Failed to delete the avatars of purged user_id: {}: {}", user_id, err);
+        }
+    }
+
+    Ok(rows.len())
+}
@@ -0,0 +1,120 @@
+use crate::models::admin_model::*;
+use crate::models::auth_model::ForgotPasswordRequest;
+use crate::services::auth_service::AuthService;
+use crate::services::database::DatabasePool;
+use crate::services::mailer::Mailer;
+use crate::utils::password::UNUSABLE_PASSWORD_HASH;
+use anyhow::Result;
+use std::sync::Arc;
+use tokio_postgres::Row;
+
+pub const DEFAULT_PAGE_SIZE: i64 = 20;
+
+/// User management for admins. Suspending, resetting the password of or deleting an
+/// account takes effect on the user's next request; their tokens are rejected from then on.
+pub struct AdminService {
+    db: DatabasePool,
+}
+
+impl AdminService {
+    pub fn new(db: DatabasePool) -> Self {
+        Self { db }
+    }
+
+    pub async fn get_users(&self, query: AdminUserQuery) -> Result<AdminUserListResponse> {
+        let page = query.page.unwrap_or(1);
+        let per_page = query.per_page.unwrap_or(DEFAULT_PAGE_SIZE);
+        let search = query.search.filter(|search| !search.trim().is_empty());
+
+        let sql = "SELECT * FROM sp_admin_get_users($1, $2, $3)";
+        let offset = (page - 1) * per_page;
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[
+            &search,
+            &(per_page as i32),
+            &(offset as i32),
+        ];
+
+        let rows = self.db.execute_query(sql, params).await?;
+        let total = rows.first().map_or(0, |row| row.get("totalcount"));
+
+        Ok(AdminUserListResponse {
+            items: rows.iter().map(admin_user_from_row).collect(),
+            page,
+            per_page,
+            total,
+        })
+    }
+
+    pub async fn get_user(&self, user_id: i32) -> Result<Option<AdminUserDetailResponse>> {
+        let query = "SELECT * FROM sp_admin_get_user($1)";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[&user_id];
+
+        let row = self.db.execute_query_one(query, params).await?;
+
+        Ok(row.map(|row| AdminUserDetailResponse {
+            user: admin_user_from_row(&row),
+            two_factor_enabled: row.get("totpenabled"),
+            note_count: row.get("notecount"),
+            active_sessions: row.get("activesessions"),
+        }))
+    }
+
+    /// Suspend an account; returns false if the user does not exist
+    pub async fn suspend_user(&self, user_id: i32, reason: Option<String>) -> Result<bool> {
+        let query = "SELECT sp_suspend_user($1, $2) as updated";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[&user_id, &reason];
+
+        let row = self.db.execute_query_one(query, params).await?;
+        Ok(row.is_some_and(|row| row.get::<_, i32>("updated") == 1))
+    }
+
+    /// Lift a suspension; returns false if the user does not exist
+    pub async fn unsuspend_user(&self, user_id: i32) -> Result<bool> {
+        let query = "SELECT sp_unsuspend_user($1) as updated";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[&user_id];
+
+        let row = self.db.execute_query_one(query, params).await?;
+        Ok(row.is_some_and(|row| row.get::<_, i32>("updated") == 1))
+    }
+
+    /// Make the current password unusable, sign the user out everywhere and email them a
+    /// reset link. Returns false if the user does not exist.
+    pub async fn force_password_reset(&self, user_id: i32, mailer: Arc<dyn Mailer>) -> Result<bool> {
+        let query = "SELECT * FROM sp_force_password_reset($1, $2)";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[&user_id, &UNUSABLE_PASSWORD_HASH];
+
+        let Some(row) = self.db.execute_query_one(query, params).await? else {
+            return Ok(false);
+        };
+        let email: String = row.get("email");
+
+        AuthService::new(self.db.clone())
+            .request_password_reset(ForgotPasswordRequest { email }, mailer)
+            .await?;
+
+        Ok(true)
+    }
+
+    /// Delete an account with everything it owns; returns false if the user does not exist
+    pub async fn delete_user(&self, user_id: i32) -> Result<bool> {
+        let query = "SELECT sp_delete_user($1) as deleted";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[&user_id];
+
+        let row = self.db.execute_query_one(query, params).await?;
+        Ok(row.is_some_and(|row| row.get::<_, i32>("deleted") == 1))
+    }
+}
+
+fn admin_user_from_row(row: &Row) -> AdminUserResponse {
+    AdminUserResponse {
+        id: row.get("id"),
+        username: row.get("username"),
+        email: row.get("email"),
+        email_verified: row.get("emailverified"),
+        roles: row.get("roles"),
+        suspended_at: row.get("suspendedat"),
+        suspension_reason: row.get("suspensionreason"),
+        created_at: row.get("createdat"),
+        last_login_at: row.get("lastloginat"),
+    }
+}
@@ -0,0 +1,156 @@
+use crate::models::auth_event_model::{SecurityEventListResponse, SecurityEventResponse};
+use crate::services::database::DatabasePool;
+use crate::utils::client_device::ClientDevice;
+use anyhow::Result;
+use std::net::IpAddr;
+use tracing::error;
+
+pub const DEFAULT_PAGE_SIZE: i64 = 20;
+
+/// Events recorded by the handlers. Changes made inside stored procedures (password_changed,
+/// email_change_requested, email_changed, email_verified, deletion_scheduled,
+/// deletion_cancelled) are recorded by those procedures, in the same transaction.
+#[derive(Debug, Clone, Copy)]
+pub enum AuthEventType {
+    Register,
+    Login,
+    TwoFactorLogin,
+    TokenRefresh,
+    Logout,
+    PasswordReset,
+    TwoFactorEnabled,
+    TwoFactorDisabled,
+    RecoveryCodesRegenerated,
+    AccountSuspended,
+    AccountUnsuspended,
+    PasswordResetForced,
+    AccountDeleted,
+}
+
+impl AuthEventType {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AuthEventType::Register => "register",
+            AuthEventType::Login => "login",
+            AuthEventType::TwoFactorLogin => "two_factor_login",
+            AuthEventType::TokenRefresh => "token_refresh",
+            AuthEventType::Logout => "logout",
+            AuthEventType::PasswordReset => "password_reset",
+            AuthEventType::TwoFactorEnabled => "two_factor_enabled",
+            AuthEventType::TwoFactorDisabled => "two_factor_disabled",
+            AuthEventType::RecoveryCodesRegenerated => "recovery_codes_regenerated",
+            AuthEventType::AccountSuspended => "account_suspended",
+            AuthEventType::AccountUnsuspended => "account_unsuspended",
+            AuthEventType::PasswordResetForced => "password_reset_forced",
+            AuthEventType::AccountDeleted => "account_deleted",
+        }
+    }
+}
+
+/// One entry for the security event log
+#[derive(Debug)]
+pub struct AuthEvent<'a> {
+    pub event_type: AuthEventType,
+    pub user_id: Option<i32>,
+    /// Identifies the account when the user ID is not known, e.g. for a failed login
+    pub email: Option<&'a str>,
+    pub success: bool,
+    pub reason: Option<String>,
+}
+
+impl<'a> AuthEvent<'a> {
+    pub fn succeeded(event_type: AuthEventType, user_id: i32) -> Self {
+        Self { event_type, user_id: Some(user_id), email: None, success: true, reason: None }
+    }
+
+    pub fn failed(event_type: AuthEventType, reason: impl ToString) -> Self {
+        Self { event_type, user_id: None, email: None, success: false, reason: Some(reason.to_string()) }
+    }
+
+    pub fn for_user(self, user_id: i32) -> Self {
+        Self { user_id: Some(user_id), ..self }
+    }
+
+    pub fn for_email(self, email: &'a str) -> Self {
+        Self { email: Some(email), ..self }
+    }
+
+    pub fn with_reason(self, reason: impl ToString) -> Self {
+        Self { reason: Some(reason.to_string()), ..self }
+    }
+}
+
+/// Append-only log of security-relevant events, readable by the user they belong to and by admins
+pub struct AuthEventService {
+    db: DatabasePool,
+}
+
+impl AuthEventService {
+    pub fn new(db: DatabasePool) -> Self {
+        Self { db }
+    }
+
+    /// Append an event. Failing to record it is logged but never fails the request itself.
+    pub async fn record(&self, event: AuthEvent<'_>, device: &ClientDevice) {
+        let query = "SELECT sp_record_auth_event($1, $2, $3, $4, $5, $6, $7)";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[
+            &event.user_id,
+            &event.email,
+            &event.event_type.as_str(),
+            &event.success,
+            &event.reason,
+            &device.ip,
+            &device.user_agent,
+        ];
+
+        if let Err(err) = self.db.execute_command(query, params).await {
+            error!("Failed to record {} event: {}", event.event_type.as_str(), err);
+        }
+    }
+
+    /// A page of events, newest first; without a user ID the events of every user
+    pub async fn get_events(
+        &self,
+        user_id: Option<i32>,
+        event_type: Option<String>,
+        success: Option<bool>,
+        page: Option<i64>,
+        per_page: Option<i64>,
+    ) -> Result<SecurityEventListResponse> {
+        let page = page.unwrap_or(1);
+        let per_page = per_page.unwrap_or(DEFAULT_PAGE_SIZE);
+
+        let query = "SELECT * FROM sp_get_auth_events($1, $2, $3, $4, $5)";
+        let offset = (page - 1) * per_page;
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[
+            &user_id,
+            &event_type,
+            &success,
+            &(per_page as i32),
+            &(offset as i32),
+        ];
+
+        let rows = self.db.execute_query(query, params).await?;
+        let total = rows.first().map_or(0, |row| row.get("totalcount"));
+
+        let items = rows
+            .iter()
+            .map(|row| {
+                let ip_address: Option<IpAddr> = row.get("ipaddress");
+                SecurityEventResponse {
+                    id: row.get("id"),
+                    user_id: row.get("userid"),
+                    email: row.get("email"),
+                    event_type: row.get("eventtype"),
+                    success: row.get("success"),
+                    reason: row.get("reason"),
+                    ip_address: ip_address.map(|address| address.to_string()),
+                    user_agent: row.get("useragent"),
+                    created_at: row.get("createdat"),
+                }
+            })
+            .collect();
+
+        Ok(SecurityEventListResponse { items, page, per_page, total })
+    }
+}
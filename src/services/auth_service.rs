@@ -1,8 +1,32 @@
 use crate::models::auth_model::*;
 use crate::services::database::DatabasePool;
-use crate::utils::jwt::create_jwt;
+use crate::services::email_verification_service::EmailVerificationService;
+use crate::services::login_throttle_service::{AttemptKey, LoginThrottleService};
+use crate::services::mailer::{EmailMessage, Mailer};
+use crate::services::role_service::RoleService;
+use crate::services::two_factor_service::TwoFactorService;
+use crate::utils::jwt::{
+    access_token_ttl_seconds, create_jwt, create_two_factor_challenge, refresh_token_ttl_seconds,
+    validate_two_factor_challenge, TWO_FACTOR_CHALLENGE_TTL_SECONDS,
+};
+use crate::utils::password::{hash_password, needs_rehash, verify_password};
+use crate::utils::client_device::ClientDevice;
+use crate::utils::password_policy::check_password;
+use crate::utils::token::{generate_token, hash_token};
 use anyhow::Result;
-use bcrypt::{hash, verify, DEFAULT_COST};
+use chrono::{DateTime, Utc};
+use std::net::IpAddr;
+use std::sync::{Arc, LazyLock};
+use tokio_postgres::Row;
+use uuid::Uuid;
+use tracing::{error, info};
+
+const DEFAULT_PASSWORD_RESET_TOKEN_TTL_SECONDS: u64 = 60 * 60; // 1 hour
+
+// Verified against when the email is unknown, so those logins take as long as real ones
+static DUMMY_PASSWORD_HASH: LazyLock<String> = LazyLock::new(|| {
+    hash_password(&generate_token()).expect("Failed to hash dummy password")
+});
 
 pub struct AuthService {
     db: DatabasePool,
@@ -13,9 +37,11 @@ impl AuthService {
         Self { db }
     }
 
-    pub async fn register_user(&self, request: RegisterRequest) -> Result<AuthResponse> {
+    pub async fn register_user(&self, request: RegisterRequest, mailer: Arc<dyn Mailer>, device: &ClientDevice) -> Result<AuthResponse> {
+        check_password(&request.password, &request.username, &request.email)?;
+
         // Hash the password
-        let password_hash = hash(&request.password, DEFAULT_COST)?;
+        let password_hash = hash_password(&request.password)?;
 
         // Call the stored procedure
         let query = "SELECT sp_register_user($1, $2, $3) as user_id";
@@ -26,52 +52,379 @@ impl AuthService {
         ];
 
         let row = self.db.execute_query_one(query, params).await?;
-        
+
         match row {
             Some(row) => {
                 let user_id: i32 = row.get("user_id");
-                let token = create_jwt(user_id)?;
-                
+
+                // New accounts start unverified; mail them a verification link
+                EmailVerificationService::new(self.db.clone())
+                    .send_verification(user_id, &request.username, &request.email, mailer)
+                    .await?;
+
+                // New accounts start at token version 0
+                self.issue_tokens(user_id, 0, request.username, request.email, device).await
+            }
+            None => Err(anyhow::anyhow!("Failed to create user")),
+        }
+    }
+
+    pub async fn login_user(&self, request: LoginRequest, device: &ClientDevice) -> Result<LoginResponse> {
+        let throttle = LoginThrottleService::new(self.db.clone());
+        let attempt_keys = [AttemptKey::Account(request.email.clone()), AttemptKey::Ip(device.ip)];
+        throttle.check(&attempt_keys).await?;
+
+        let credentials = self.find_credentials_by_email(&request.email).await?;
+
+        // Unknown emails still pay for a hash verification so timing does not reveal which accounts exist
+        let password_hash = credentials
+            .as_ref()
+            .map_or(DUMMY_PASSWORD_HASH.as_str(), |credentials| credentials.password_hash.as_str());
+        let password_valid = verify_password(&request.password, password_hash)?;
+
+        let credentials = match credentials {
+            Some(credentials) if password_valid => credentials,
+            _ => {
+                throttle.record_failure(&attempt_keys).await?;
+                return Err(anyhow::anyhow!("Invalid email or password"));
+            }
+        };
+        throttle.clear(&attempt_keys[0]).await?;
+
+        if needs_rehash(&credentials.password_hash) {
+            // Upgrading the hash is best effort; the login itself already succeeded
+            if let Err(err) = self.rehash_password(&credentials, &request.password).await {
+                error!("Failed to rehash password for user_id: {}: {}", credentials.user_id, err);
+            }
+        }
+
+        self.complete_login(credentials, device).await
+    }
+
+    /// Log in a user whose identity an external provider already vouched for
+    pub async fn login_user_by_id(&self, user_id: i32, device: &ClientDevice) -> Result<LoginResponse> {
+        let credentials = self.find_credentials_by_id(user_id).await?
+            .ok_or_else(|| anyhow::anyhow!("User not found"))?;
+
+        self.complete_login(credentials, device).await
+    }
+
+    /// Second login step: exchange a challenge token and a TOTP or recovery code for tokens
+    pub async fn complete_two_factor_login(&self, request: TwoFactorLoginRequest, device: &ClientDevice) -> Result<AuthResponse> {
+        let user_id = validate_two_factor_challenge(&request.challenge_token)
+            .map_err(|_| anyhow::anyhow!("Invalid or expired challenge token"))?;
+
+        // Six digit codes are guessable, so failures count towards a lockout just like passwords
+        let throttle = LoginThrottleService::new(self.db.clone());
+        let attempt_keys = [AttemptKey::TwoFactor(user_id), AttemptKey::Ip(device.ip)];
+        throttle.check(&attempt_keys).await?;
+
+        if !TwoFactorService::new(self.db.clone()).verify_code(user_id, &request.code).await? {
+            throttle.record_failure(&attempt_keys).await?;
+            return Err(anyhow::anyhow!("Invalid two-factor code"));
+        }
+        throttle.clear(&attempt_keys[0]).await?;
+
+        // Read the account again so the tokens carry the current token version
+        let credentials = self.find_credentials_by_id(user_id).await?
+            .ok_or_else(|| anyhow::anyhow!("User not found"))?;
+        // The account may have been suspended between the two steps
+        if credentials.suspended {
+            return Err(anyhow::anyhow!("Account suspended"));
+        }
+
+        self.issue_tokens(
+            credentials.user_id,
+            credentials.token_version,
+            credentials.username,
+            credentials.email,
+            device,
+        ).await
+    }
+
+    pub async fn refresh_tokens(&self, request: RefreshRequest, client_ip: IpAddr) -> Result<AuthResponse> {
+        let new_refresh_token = generate_token();
+
+        // Rotate the presented token; the stored procedure revokes the whole
+        // token family when a token that was already rotated is replayed
+        let query = "SELECT * FROM sp_rotate_refresh_token($1, $2, $3, $4)";
+        let ttl_seconds = refresh_token_ttl_seconds() as i32;
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[
+            &hash_token(&request.refresh_token),
+            &hash_token(&new_refresh_token),
+            &ttl_seconds,
+            &client_ip,
+        ];
+
+        let row = self.db.execute_query_one(query, params).await?
+            .ok_or_else(|| anyhow::anyhow!("Invalid refresh token"))?;
+        let status: String = row.get("status");
+
+        match status.as_str() {
+            "rotated" => {
+                let user_id: i32 = row.get("userid");
+                let token_version: i32 = row.get("tokenversion");
+                let session_id: Uuid = row.get("sessionid");
+                let credentials = self.find_credentials_by_id(user_id).await?
+                    .ok_or_else(|| anyhow::anyhow!("User not found"))?;
+                // Role changes since the last refresh take effect here
+                let access = RoleService::new(self.db.clone()).get_user_access(user_id).await?;
+
                 Ok(AuthResponse {
                     user_id,
-                    username: request.username,
-                    email: request.email,
-                    token,
+                    username: credentials.username,
+                    email: credentials.email,
+                    token: create_jwt(user_id, token_version, session_id, &access)?,
+                    refresh_token: new_refresh_token,
+                    expires_in: access_token_ttl_seconds(),
                 })
             }
-            None => Err(anyhow::anyhow!("Failed to create user")),
+            "reused" => Err(anyhow::anyhow!("Refresh token reuse detected")),
+            "revoked" => Err(anyhow::anyhow!("Refresh token revoked")),
+            "suspended" => Err(anyhow::anyhow!("Account suspended")),
+            "expired" => Err(anyhow::anyhow!("Refresh token expired")),
+            _ => Err(anyhow::anyhow!("Invalid refresh token")),
         }
     }
 
-    pub async fn login_user(&self, request: LoginRequest) -> Result<AuthResponse> {
-        // Call the stored procedure
-        let query = "SELECT * FROM sp_login_user($1)";
-        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[&request.email];
+    pub async fn logout(&self, context: &AuthContext, request: LogoutRequest) -> Result<()> {
+        if request.all_devices {
+            // Bumping the token version invalidates every access token issued so far
+            let query = "SELECT sp_revoke_user_tokens($1)";
+            let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[&context.user_id];
+            self.db.execute_command(query, params).await?;
+            return Ok(());
+        }
 
-        let row = self.db.execute_query_one(query, params).await?;
-        
-        match row {
-            Some(row) => {
-                let user_id: i32 = row.get("id");
-                let username: String = row.get("username");
-                let email: String = row.get("email");
-                let password_hash: String = row.get("passwordhash");
-
-                // Verify password
-                if verify(&request.password, &password_hash)? {
-                    let token = create_jwt(user_id)?;
-                    
-                    Ok(AuthResponse {
-                        user_id,
-                        username,
-                        email,
-                        token,
-                    })
-                } else {
-                    Err(anyhow::anyhow!("Invalid credentials"))
+        let expires_at = DateTime::<Utc>::from_timestamp(context.expires_at as i64, 0)
+            .ok_or_else(|| anyhow::anyhow!("Invalid token expiry"))?;
+        let query = "SELECT sp_revoke_access_token($1, $2, $3)";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[
+            &context.token_id,
+            &context.user_id,
+            &expires_at,
+        ];
+        self.db.execute_command(query, params).await?;
+
+        // Signing the session out also revokes its refresh tokens
+        if let Some(session_id) = context.session_id {
+            let query = "SELECT sp_revoke_session($1, $2)";
+            let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[&session_id, &context.user_id];
+            self.db.execute_command(query, params).await?;
+        }
+
+        if let Some(refresh_token) = request.refresh_token {
+            let query = "SELECT sp_revoke_refresh_token($1, $2)";
+            let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[
+                &hash_token(&refresh_token),
+                &context.user_id,
+            ];
+            self.db.execute_command(query, params).await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn request_password_reset(&self, request: ForgotPasswordRequest, mailer: Arc<dyn Mailer>) -> Result<()> {
+        let token = generate_token();
+        let ttl_seconds = std::env::var("PASSWORD_RESET_TOKEN_TTL_SECONDS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(DEFAULT_PASSWORD_RESET_TOKEN_TTL_SECONDS) as i32;
+
+        let query = "SELECT * FROM sp_create_password_reset_token($1, $2, $3)";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[
+            &request.email,
+            &hash_token(&token),
+            &ttl_seconds,
+        ];
+
+        // Unknown emails are silently ignored so the endpoint cannot be used to probe for accounts
+        if let Some(row) = self.db.execute_query_one(query, params).await? {
+            let username: String = row.get("username");
+            let reset_url = std::env::var("PASSWORD_RESET_URL")
+                .unwrap_or_else(|_| "http://127.0.0.1:3000/reset-password".to_string());
+
+            let message = EmailMessage {
+                to: request.email,
+                subject: "Reset your password".to_string(),
+                body: format!(
+                    "Hi {},\n\nUse the link below to choose a new password. It expires in {} minutes and can only be used once.\n\n{}?token={}\n\nIf you did not ask for a password reset, you can ignore this email.\n",
+                    username, ttl_seconds / 60, reset_url, token
+                ),
+            };
+
+            // Send in the background so the response time does not reveal whether the account exists
+            tokio::spawn(async move {
+                if let Err(err) = mailer.send(message).await {
+                    error!("Failed to send password reset email: {}", err);
                 }
-            }
-            None => Err(anyhow::anyhow!("User not found")),
+            });
         }
+
+        Ok(())
     }
-}
\ No newline at end of file
+
+    /// Returns the ID of the user whose password was reset
+    pub async fn reset_password(&self, request: ResetPasswordRequest) -> Result<i32> {
+        // Look the account up first; the policy bans passwords containing its username or email
+        let query = "SELECT * FROM sp_get_password_reset_user($1)";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[&hash_token(&request.token)];
+
+        let row = self.db.execute_query_one(query, params).await?
+            .ok_or_else(|| anyhow::anyhow!("Invalid or expired reset token"))?;
+        let username: String = row.get("username");
+        let email: String = row.get("email");
+        check_password(&request.new_password, &username, &email)?;
+
+        let password_hash = hash_password(&request.new_password)?;
+
+        // Consumes the token, stores the new hash and revokes every outstanding token of the user
+        let query = "SELECT sp_reset_password($1, $2) as user_id";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[
+            &hash_token(&request.token),
+            &password_hash,
+        ];
+
+        let row = self.db.execute_query_one(query, params).await?;
+        let user_id: Option<i32> = row.and_then(|row| row.get("user_id"));
+
+        user_id.ok_or_else(|| anyhow::anyhow!("Invalid or expired reset token"))
+    }
+
+    /// Check an access token against the revocation store and the user's token version.
+    /// Returns the status (one of: valid, revoked, session_revoked, stale, suspended, unknown_user) and whether the
+    /// user's email address is verified.
+    pub async fn get_access_token_status(&self, claims: &Claims, user_id: i32) -> Result<(String, bool)> {
+        let query = "SELECT * FROM sp_get_access_token_status($1, $2, $3, $4)";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[
+            &claims.jti,
+            &user_id,
+            &claims.ver,
+            &claims.sid,
+        ];
+
+        let row = self.db.execute_query_one(query, params).await?
+            .ok_or_else(|| anyhow::anyhow!("Failed to check token status"))?;
+        let email_verified: Option<bool> = row.get("emailverified");
+        Ok((row.get("status"), email_verified.unwrap_or(false)))
+    }
+
+    // Once the first factor is verified: a two-factor challenge if the user enabled it, tokens otherwise
+    async fn complete_login(&self, credentials: UserCredentials, device: &ClientDevice) -> Result<LoginResponse> {
+        // Only reported once the first factor is verified, so it does not reveal which accounts exist
+        if credentials.suspended {
+            return Err(anyhow::anyhow!("Account suspended"));
+        }
+
+        if credentials.totp_enabled {
+            // Tokens are only issued once the second factor is checked at /auth/login/2fa
+            return Ok(LoginResponse::TwoFactorRequired(TwoFactorChallengeResponse {
+                two_factor_required: true,
+                challenge_token: create_two_factor_challenge(credentials.user_id)?,
+                expires_in: TWO_FACTOR_CHALLENGE_TTL_SECONDS,
+            }));
+        }
+
+        let response = self.issue_tokens(
+            credentials.user_id,
+            credentials.token_version,
+            credentials.username,
+            credentials.email,
+            device,
+        ).await?;
+        Ok(LoginResponse::Authenticated(response))
+    }
+
+    /// Start a session on `device` and issue its access token and first refresh token
+    async fn issue_tokens(
+        &self,
+        user_id: i32,
+        token_version: i32,
+        username: String,
+        email: String,
+        device: &ClientDevice,
+    ) -> Result<AuthResponse> {
+        let refresh_token = generate_token();
+
+        let query = "SELECT sp_create_session($1, $2, $3, $4, $5, $6) as session_id";
+        let ttl_seconds = refresh_token_ttl_seconds() as i32;
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[
+            &user_id,
+            &device.name,
+            &device.user_agent,
+            &device.ip,
+            &hash_token(&refresh_token),
+            &ttl_seconds,
+        ];
+
+        let row = self.db.execute_query_one(query, params).await?
+            .ok_or_else(|| anyhow::anyhow!("Failed to create session"))?;
+        let session_id: Uuid = row.get("session_id");
+
+        // Logging in during the grace period keeps an account that was scheduled for deletion
+        let query = "SELECT sp_cancel_user_deletion($1, $2, $3) as cancelled";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[&user_id, &device.ip, &device.user_agent];
+        let cancelled = self.db.execute_query_one(query, params).await?
+            .is_some_and(|row| row.get::<_, i32>("cancelled") == 1);
+        if cancelled {
+            info!("Cancelled the scheduled deletion of user_id: {} after login", user_id);
+        }
+
+        let access = RoleService::new(self.db.clone()).get_user_access(user_id).await?;
+        let token = create_jwt(user_id, token_version, session_id, &access)?;
+
+        Ok(AuthResponse {
+            user_id,
+            username,
+            email,
+            token,
+            refresh_token,
+            expires_in: access_token_ttl_seconds(),
+        })
+    }
+
+    /// Replace an outdated password hash (other algorithm or parameters) with one using the current configuration
+    async fn rehash_password(&self, credentials: &UserCredentials, password: &str) -> Result<()> {
+        let new_hash = hash_password(password)?;
+
+        // Only replaces the hash that was verified, so a concurrent password change wins
+        let query = "SELECT sp_update_password_hash($1, $2, $3)";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[
+            &credentials.user_id,
+            &credentials.password_hash,
+            &new_hash,
+        ];
+        self.db.execute_command(query, params).await?;
+
+        Ok(())
+    }
+
+    async fn find_credentials_by_email(&self, email: &str) -> Result<Option<UserCredentials>> {
+        let query = "SELECT * FROM sp_login_user($1)";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[&email];
+
+        let row = self.db.execute_query_one(query, params).await?;
+        Ok(row.as_ref().map(credentials_from_row))
+    }
+
+    pub async fn find_credentials_by_id(&self, user_id: i32) -> Result<Option<UserCredentials>> {
+        let query = "SELECT * FROM sp_get_user_credentials($1)";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[&user_id];
+
+        let row = self.db.execute_query_one(query, params).await?;
+        Ok(row.as_ref().map(credentials_from_row))
+    }
+}
+
+fn credentials_from_row(row: &Row) -> UserCredentials {
+    UserCredentials {
+        user_id: row.get("id"),
+        username: row.get("username"),
+        email: row.get("email"),
+        password_hash: row.get("passwordhash"),
+        token_version: row.get("tokenversion"),
+        totp_enabled: row.get("totpenabled"),
+        suspended: row.get("suspended"),
+    }
+}
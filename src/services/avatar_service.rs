@@ -0,0 +1,160 @@
+use crate::models::users_model::AvatarUrls;
+use crate::services::database::DatabasePool;
+use crate::services::storage::Storage;
+use anyhow::Result;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader, Limits};
+use std::io::Cursor;
+use std::sync::Arc;
+use tracing::error;
+use uuid::Uuid;
+
+const DEFAULT_AVATAR_MAX_BYTES: usize = 5 * 1024 * 1024; // 5 MiB
+// Bound the memory a decoded upload can take, whatever its file size
+const MAX_AVATAR_DIMENSION: u32 = 4096;
+const MAX_AVATAR_DECODE_BYTES: u64 = 64 * 1024 * 1024; // 64 MiB
+
+/// Edge lengths in pixels of the square thumbnails generated for every avatar
+pub const AVATAR_SIZES: [u32; 3] = [64, 128, 256];
+
+/// Largest accepted upload, from `AVATAR_MAX_BYTES`
+pub fn max_upload_bytes() -> usize {
+    std::env::var("AVATAR_MAX_BYTES")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_AVATAR_MAX_BYTES)
+}
+
+/// Public URLs of the thumbnails of an avatar version
+pub fn avatar_urls(user_id: i32, version: Uuid) -> AvatarUrls {
+    let url = |size: u32| format!("/api/v1/avatars/{}/{}/{}", user_id, version, size);
+
+    AvatarUrls {
+        small: url(AVATAR_SIZES[0]),
+        medium: url(AVATAR_SIZES[1]),
+        large: url(AVATAR_SIZES[2]),
+    }
+}
+
+pub struct AvatarService {
+    db: DatabasePool,
+    storage: Arc<dyn Storage>,
+}
+
+impl AvatarService {
+    pub fn new(db: DatabasePool, storage: Arc<dyn Storage>) -> Self {
+        Self { db, storage }
+    }
+
+    /// Replace the avatar of a user with an uploaded PNG, JPEG, GIF or WebP image.
+    ///
+    /// The type is taken from the file's magic bytes, never from what the client claims. Every
+    /// thumbnail is re-encoded from the decoded pixels, so EXIF and other metadata never reach storage.
+    pub async fn upload(&self, user_id: i32, data: Vec<u8>) -> Result<()> {
+        if data.len() > max_upload_bytes() {
+            anyhow::bail!("Avatar images can be at most {} bytes", max_upload_bytes());
+        }
+
+        match image::guess_format(&data) {
+            Ok(ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::Gif | ImageFormat::WebP) => {}
+            _ => anyhow::bail!("Unsupported image type; upload a PNG, JPEG, GIF or WebP image"),
+        }
+
+        // Decoding and resizing are CPU bound; keep them off the async workers
+        let thumbnails = tokio::task::spawn_blocking(move || build_thumbnails(&data)).await??;
+
+        let version = Uuid::new_v4();
+        for (size, thumbnail) in AVATAR_SIZES.into_iter().zip(thumbnails) {
+            self.storage.put(&avatar_key(user_id, version, size), thumbnail).await?;
+        }
+
+        let query = "SELECT sp_set_user_avatar($1, $2) as previous_version";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[&user_id, &Some(version)];
+
+        let row = self.db.execute_query_one(query, params).await?;
+        let previous_version: Option<Uuid> = row.and_then(|row| row.get("previous_version"));
+        if let Some(previous_version) = previous_version {
+            self.delete_version(user_id, previous_version).await;
+        }
+
+        Ok(())
+    }
+
+    /// Remove the avatar of a user; returns false if they had none
+    pub async fn remove(&self, user_id: i32) -> Result<bool> {
+        let query = "SELECT sp_set_user_avatar($1, $2) as previous_version";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[&user_id, &None::<Uuid>];
+
+        let row = self.db.execute_query_one(query, params).await?;
+        let previous_version: Option<Uuid> = row.and_then(|row| row.get("previous_version"));
+
+        match previous_version {
+            Some(previous_version) => {
+                self.delete_version(user_id, previous_version).await;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// PNG bytes of one thumbnail, or `None` for an unknown size or version
+    pub async fn get_thumbnail(&self, user_id: i32, version: Uuid, size: u32) -> Result<Option<Vec<u8>>> {
+        if !AVATAR_SIZES.contains(&size) {
+            return Ok(None);
+        }
+
+        self.storage.get(&avatar_key(user_id, version, size)).await
+    }
+
+    /// Delete every stored avatar of a user, once the user itself is deleted
+    pub async fn delete_user_files(&self, user_id: i32) -> Result<()> {
+        self.storage.delete(&format!("avatars/{}", user_id)).await
+    }
+
+    // The new avatar is already live; a leftover old version only costs disk space
+    async fn delete_version(&self, user_id: i32, version: Uuid) {
+        if let Err(err) = self.storage.delete(&format!("avatars/{}/{}", user_id, version)).await {
+            error!("Failed to delete avatar version {} of user_id: {}: {}", version, user_id, err);
+        }
+    }
+}
+
+fn avatar_key(user_id: i32, version: Uuid, size: u32) -> String {
+    format!("avatars/{}/{}/{}", user_id, version, size)
+}
+
+// One PNG per entry of AVATAR_SIZES, center-cropped to a square
+fn build_thumbnails(data: &[u8]) -> Result<Vec<Vec<u8>>> {
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_AVATAR_DIMENSION);
+    limits.max_image_height = Some(MAX_AVATAR_DIMENSION);
+    limits.max_alloc = Some(MAX_AVATAR_DECODE_BYTES);
+
+    let mut reader = ImageReader::new(Cursor::new(data)).with_guessed_format()?;
+    reader.limits(limits);
+
+    let invalid_image = |err: image::ImageError| match err {
+        image::ImageError::Limits(_) => anyhow::anyhow!(
+            "Avatar images can be at most {0}x{0} pixels and {1} MiB once decoded",
+            MAX_AVATAR_DIMENSION,
+            MAX_AVATAR_DECODE_BYTES / (1024 * 1024)
+        ),
+        err => anyhow::anyhow!("Invalid image: {}", err),
+    };
+    let mut decoder = reader.into_decoder().map_err(invalid_image)?;
+    // Phones store rotation in EXIF; apply it before the metadata is dropped
+    let orientation = decoder.orientation().map_err(invalid_image)?;
+    let mut image = DynamicImage::from_decoder(decoder).map_err(invalid_image)?;
+    image.apply_orientation(orientation);
+
+    AVATAR_SIZES
+        .into_iter()
+        .map(|size| {
+            let mut png = Vec::new();
+            image
+                .resize_to_fill(size, size, FilterType::Lanczos3)
+                .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+            Ok(png)
+        })
+        .collect()
+}
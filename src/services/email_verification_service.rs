@@ -0,0 +1,181 @@
+use crate::services::database::DatabasePool;
+use crate::services::login_throttle_service::{AttemptKey, LoginLocked, LoginThrottleService};
+use crate::services::mailer::{EmailMessage, Mailer};
+use crate::utils::client_device::ClientDevice;
+use crate::utils::token::{generate_token, hash_token};
+use anyhow::Result;
+use std::fmt;
+use std::sync::Arc;
+use tracing::error;
+
+const DEFAULT_EMAIL_VERIFICATION_TOKEN_TTL_SECONDS: u64 = 60 * 60 * 48; // 48 hours
+
+/// Returned when verification emails were requested too often for an address or from a client
+#[derive(Debug)]
+pub struct VerificationResendLocked {
+    pub retry_after_seconds: u64,
+}
+
+impl fmt::Display for VerificationResendLocked {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Too many verification emails requested; try again in {} seconds", self.retry_after_seconds)
+    }
+}
+
+impl std::error::Error for VerificationResendLocked {}
+
+pub struct EmailVerificationService {
+    db: DatabasePool,
+}
+
+impl EmailVerificationService {
+    pub fn new(db: DatabasePool) -> Self {
+        Self { db }
+    }
+
+    /// Create a verification token for `email` and mail the verification link to it
+    pub async fn send_verification(&self, user_id: i32, username: &str, email: &str, mailer: Arc<dyn Mailer>) -> Result<()> {
+        let token = generate_token();
+        let ttl_seconds = verification_token_ttl_seconds();
+
+        let query = "SELECT sp_create_email_verification_token($1, $2, $3, $4)";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[
+            &user_id,
+            &email,
+            &hash_token(&token),
+            &ttl_seconds,
+        ];
+        self.db.execute_command(query, params).await?;
+
+        let message = EmailMessage {
+            to: email.to_string(),
+            subject: "Verify your email address".to_string(),
+            body: format!(
+                "Hi {},\n\nPlease confirm that this is your email address by opening the link below. It expires in {} hours.\n\n{}\n",
+                username, ttl_seconds / 3600, verification_link(&token)
+            ),
+        };
+        send_in_background(message, mailer);
+
+        Ok(())
+    }
+
+    /// Mail a verification link to `new_email`; the account switches to it once the link is opened.
+    /// The current address is told about the request, in case someone else made it.
+    pub async fn request_email_change(
+        &self,
+        user_id: i32,
+        username: &str,
+        current_email: &str,
+        new_email: &str,
+        device: &ClientDevice,
+        mailer: Arc<dyn Mailer>,
+    ) -> Result<()> {
+        let token = generate_token();
+        let ttl_seconds = verification_token_ttl_seconds();
+
+        let query = "SELECT sp_request_email_change($1, $2, $3, $4, $5, $6) as status";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[
+            &user_id,
+            &new_email,
+            &hash_token(&token),
+            &ttl_seconds,
+            &device.ip,
+            &device.user_agent,
+        ];
+
+        let row = self.db.execute_query_one(query, params).await?
+            .ok_or_else(|| anyhow::anyhow!("Failed to request email change"))?;
+        let status: String = row.get("status");
+
+        match status.as_str() {
+            "requested" => {}
+            "unchanged" => return Err(anyhow::anyhow!("The new email address is the current one")),
+            "email_taken" => return Err(anyhow::anyhow!("An account with this email address already exists")),
+            _ => return Err(anyhow::anyhow!("Failed to request email change")),
+        }
+
+        send_in_background(EmailMessage {
+            to: new_email.to_string(),
+            subject: "Confirm your new email address".to_string(),
+            body: format!(
+                "Hi {},\n\nOpen the link below to use this address for your account from now on. It expires in {} hours.\n\n{}\n",
+                username, ttl_seconds / 3600, verification_link(&token)
+            ),
+        }, mailer.clone());
+
+        send_in_background(EmailMessage {
+            to: current_email.to_string(),
+            subject: "Your email address is being changed".to_string(),
+            body: format!(
+                "Hi {},\n\nSomeone asked to change the email address of your account to {}. It only changes once the new address is confirmed.\n\nIf this was not you, reset your password right away.\n",
+                username, new_email
+            ),
+        }, mailer);
+
+        Ok(())
+    }
+
+    /// Send a fresh verification link if `email` belongs to an unverified account.
+    /// Unknown or already verified addresses are silently ignored.
+    pub async fn resend_verification(&self, email: &str, device: &ClientDevice, mailer: Arc<dyn Mailer>) -> Result<()> {
+        // Anyone can ask, so every request counts towards a lockout, whatever the address
+        let throttle = LoginThrottleService::new(self.db.clone());
+        let attempt_keys = [AttemptKey::VerificationResend(email.to_string()), AttemptKey::VerificationResendIp(device.ip)];
+        if let Err(err) = throttle.check(&attempt_keys).await {
+            return Err(match err.downcast::<LoginLocked>() {
+                Ok(locked) => VerificationResendLocked { retry_after_seconds: locked.retry_after_seconds }.into(),
+                Err(err) => err,
+            });
+        }
+        throttle.record_failure(&attempt_keys).await?;
+
+        let query = "SELECT * FROM sp_login_user($1)";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[&email];
+
+        if let Some(row) = self.db.execute_query_one(query, params).await? {
+            let email_verified: bool = row.get("emailverified");
+            if !email_verified {
+                let user_id: i32 = row.get("id");
+                let username: String = row.get("username");
+                self.send_verification(user_id, &username, email, mailer).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn verify_email(&self, token: &str, device: &ClientDevice) -> Result<()> {
+        let query = "SELECT sp_verify_email($1, $2, $3) as user_id";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[&hash_token(token), &device.ip, &device.user_agent];
+
+        let row = self.db.execute_query_one(query, params).await?;
+        let user_id: Option<i32> = row.and_then(|row| row.get("user_id"));
+
+        match user_id {
+            Some(_) => Ok(()),
+            None => Err(anyhow::anyhow!("Invalid or expired verification token")),
+        }
+    }
+}
+
+fn verification_token_ttl_seconds() -> i32 {
+    std::env::var("EMAIL_VERIFICATION_TOKEN_TTL_SECONDS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_EMAIL_VERIFICATION_TOKEN_TTL_SECONDS) as i32
+}
+
+fn verification_link(token: &str) -> String {
+    let verification_url = std::env::var("EMAIL_VERIFICATION_URL")
+        .unwrap_or_else(|_| "http://127.0.0.1:3000/verify-email".to_string());
+    format!("{}?token={}", verification_url, token)
+}
+
+fn send_in_background(message: EmailMessage, mailer: Arc<dyn Mailer>) {
+    tokio::spawn(async move {
+        if let Err(err) = mailer.send(message).await {
+            error!("Failed to send verification email: {}", err);
+        }
+    });
+}
\ No newline at end of file
@@ -0,0 +1,121 @@
+use crate::services::database::DatabasePool;
+use anyhow::Result;
+use std::fmt;
+use std::net::IpAddr;
+
+const DEFAULT_MAX_FAILED_ATTEMPTS: i32 = 5;
+const DEFAULT_MAX_FAILED_ATTEMPTS_PER_IP: i32 = 20;
+const DEFAULT_LOCKOUT_BASE_SECONDS: i32 = 30;
+const DEFAULT_LOCKOUT_MAX_SECONDS: i32 = 60 * 60; // 1 hour
+const DEFAULT_MAX_VERIFICATION_RESENDS: i32 = 3;
+const DEFAULT_MAX_VERIFICATION_RESENDS_PER_IP: i32 = 10;
+
+/// What a failed login attempt is counted against
+pub enum AttemptKey {
+    /// The email address that was tried, whether or not an account exists for it
+    Account(String),
+    Ip(IpAddr),
+    /// Codes tried against the two-factor challenge of a user
+    TwoFactor(i32),
+    /// Verification emails requested for an address; every request counts
+    VerificationResend(String),
+    /// Verification emails requested from a client IP
+    VerificationResendIp(IpAddr),
+}
+
+impl AttemptKey {
+    fn key(&self) -> String {
+        match self {
+            AttemptKey::Account(email) => format!("email:{}", email.trim().to_lowercase()),
+            AttemptKey::Ip(address) => format!("ip:{}", address),
+            AttemptKey::TwoFactor(user_id) => format!("2fa:{}", user_id),
+            AttemptKey::VerificationResend(email) => format!("resend:email:{}", email.trim().to_lowercase()),
+            AttemptKey::VerificationResendIp(address) => format!("resend:ip:{}", address),
+        }
+    }
+
+    // A single address legitimately serves many users (NAT, offices), so it gets more leeway
+    fn max_attempts(&self) -> i32 {
+        match self {
+            AttemptKey::Ip(_) => env_or("LOGIN_MAX_FAILED_ATTEMPTS_PER_IP", DEFAULT_MAX_FAILED_ATTEMPTS_PER_IP),
+            AttemptKey::VerificationResend(_) => env_or("VERIFICATION_RESEND_MAX_REQUESTS", DEFAULT_MAX_VERIFICATION_RESENDS),
+            AttemptKey::VerificationResendIp(_) => env_or("VERIFICATION_RESEND_MAX_REQUESTS_PER_IP", DEFAULT_MAX_VERIFICATION_RESENDS_PER_IP),
+            _ => env_or("LOGIN_MAX_FAILED_ATTEMPTS", DEFAULT_MAX_FAILED_ATTEMPTS),
+        }
+    }
+}
+
+/// Returned when a login is attempted while one of its keys is locked out
+#[derive(Debug)]
+pub struct LoginLocked {
+    pub retry_after_seconds: u64,
+}
+
+impl fmt::Display for LoginLocked {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Too many failed login attempts; try again in {} seconds", self.retry_after_seconds)
+    }
+}
+
+impl std::error::Error for LoginLocked {}
+
+/// Failed login counters with exponential lockout, stored in Postgres so they hold across restarts and instances
+pub struct LoginThrottleService {
+    db: DatabasePool,
+}
+
+impl LoginThrottleService {
+    pub fn new(db: DatabasePool) -> Self {
+        Self { db }
+    }
+
+    /// Fail with `LoginLocked` if any of `keys` is currently locked out
+    pub async fn check(&self, keys: &[AttemptKey]) -> Result<()> {
+        let keys: Vec<String> = keys.iter().map(AttemptKey::key).collect();
+
+        let query = "SELECT sp_get_login_lockout($1) as retry_after";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[&keys];
+
+        let row = self.db.execute_query_one(query, params).await?;
+        let retry_after: Option<i32> = row.and_then(|row| row.get("retry_after"));
+
+        match retry_after {
+            Some(seconds) => Err(LoginLocked { retry_after_seconds: seconds.max(1) as u64 }.into()),
+            None => Ok(()),
+        }
+    }
+
+    pub async fn record_failure(&self, keys: &[AttemptKey]) -> Result<()> {
+        let base_seconds = env_or("LOGIN_LOCKOUT_BASE_SECONDS", DEFAULT_LOCKOUT_BASE_SECONDS);
+        let max_seconds = env_or("LOGIN_LOCKOUT_MAX_SECONDS", DEFAULT_LOCKOUT_MAX_SECONDS);
+
+        for key in keys {
+            let query = "SELECT sp_record_login_failure($1, $2, $3, $4)";
+            let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[
+                &key.key(),
+                &key.max_attempts(),
+                &base_seconds,
+                &max_seconds,
+            ];
+            self.db.execute_command(query, params).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Reset the counter of `key` after a successful login
+    pub async fn clear(&self, key: &AttemptKey) -> Result<()> {
+        let query = "SELECT sp_clear_login_failures($1)";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[&key.key()];
+        self.db.execute_command(query, params).await?;
+
+        Ok(())
+    }
+}
+
+fn env_or(name: &str, default: i32) -> i32 {
+    std::env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
@@ -0,0 +1,143 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use lettre::message::{header::ContentType, Mailbox};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tracing::info;
+
+#[derive(Debug, Clone)]
+pub struct EmailMessage {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Outgoing mail transport
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, message: EmailMessage) -> Result<()>;
+}
+
+/// Build the mailer selected by `MAIL_TRANSPORT` (`smtp`, `file` or `stdout`, the default)
+pub fn from_env() -> Result<Arc<dyn Mailer>> {
+    let from = std::env::var("MAIL_FROM").unwrap_or_else(|_| "Notes API <no-reply@localhost>".to_string());
+    let transport = std::env::var("MAIL_TRANSPORT").unwrap_or_else(|_| "stdout".to_string());
+
+    let mailer: Arc<dyn Mailer> = match transport.as_str() {
+        "smtp" => Arc::new(SmtpMailer::from_env(&from)?),
+        "file" => {
+            let dir = std::env::var("MAIL_FILE_DIR").unwrap_or_else(|_| "mail".to_string());
+            Arc::new(FileMailer::new(from, PathBuf::from(dir)))
+        }
+        "stdout" => Arc::new(StdoutMailer::new(from)),
+        other => anyhow::bail!("Unknown MAIL_TRANSPORT: {}", other),
+    };
+
+    info!("Using {} mail transport", transport);
+    Ok(mailer)
+}
+
+fn build_message(from: &str, message: EmailMessage) -> Result<Message> {
+    let from: Mailbox = from.parse().context("Invalid MAIL_FROM address")?;
+    let to: Mailbox = message.to.parse().context("Invalid recipient address")?;
+
+    let email = Message::builder()
+        .from(from)
+        .to(to)
+        .subject(message.subject)
+        .header(ContentType::TEXT_PLAIN)
+        .body(message.body)?;
+
+    Ok(email)
+}
+
+/// Sends mail through an SMTP relay
+pub struct SmtpMailer {
+    from: String,
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl SmtpMailer {
+    /// Configured through `SMTP_HOST`, `SMTP_PORT`, `SMTP_USERNAME`, `SMTP_PASSWORD`
+    /// and `SMTP_SECURITY` (`starttls`, the default, `tls` or `none`)
+    pub fn from_env(from: &str) -> Result<Self> {
+        let host = std::env::var("SMTP_HOST").context("SMTP_HOST must be set for the smtp mail transport")?;
+        let security = std::env::var("SMTP_SECURITY").unwrap_or_else(|_| "starttls".to_string());
+
+        let mut builder = match security.as_str() {
+            "starttls" => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&host)?,
+            "tls" => AsyncSmtpTransport::<Tokio1Executor>::relay(&host)?,
+            "none" => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&host),
+            other => anyhow::bail!("Unknown SMTP_SECURITY: {}", other),
+        };
+
+        if let Some(port) = std::env::var("SMTP_PORT").ok().and_then(|port| port.parse().ok()) {
+            builder = builder.port(port);
+        }
+        if let (Ok(username), Ok(password)) = (std::env::var("SMTP_USERNAME"), std::env::var("SMTP_PASSWORD")) {
+            builder = builder.credentials(Credentials::new(username, password));
+        }
+
+        Ok(Self {
+            from: from.to_string(),
+            transport: builder.build(),
+        })
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, message: EmailMessage) -> Result<()> {
+        let email = build_message(&self.from, message)?;
+        self.transport.send(email).await?;
+        Ok(())
+    }
+}
+
+/// Writes every message as an `.eml` file into a directory, for local development
+pub struct FileMailer {
+    from: String,
+    dir: PathBuf,
+}
+
+impl FileMailer {
+    pub fn new(from: String, dir: PathBuf) -> Self {
+        Self { from, dir }
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, message: EmailMessage) -> Result<()> {
+        let email = build_message(&self.from, message)?;
+
+        tokio::fs::create_dir_all(&self.dir).await?;
+        let path = self.dir.join(format!("{}.eml", uuid::Uuid::new_v4()));
+        tokio::fs::write(&path, email.formatted()).await?;
+
+        info!("Wrote email to {}", path.display());
+        Ok(())
+    }
+}
+
+/// Prints every message to stdout, for local development
+pub struct StdoutMailer {
+    from: String,
+}
+
+impl StdoutMailer {
+    pub fn new(from: String) -> Self {
+        Self { from }
+    }
+}
+
+#[async_trait]
+impl Mailer for StdoutMailer {
+    async fn send(&self, message: EmailMessage) -> Result<()> {
+        let email = build_message(&self.from, message)?;
+        println!("{}", String::from_utf8_lossy(&email.formatted()));
+        Ok(())
+    }
+}
\ No newline at end of file
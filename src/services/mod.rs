@@ -1,4 +1,20 @@
 pub mod database; 
 pub mod auth_service; 
 pub mod user_service; 
-pub mod note_service;
\ No newline at end of file
+pub mod note_service;
+pub mod mailer;
+pub mod email_verification_service;
+pub mod two_factor_service;
+pub mod login_throttle_service;
+pub mod personal_access_token_service;
+pub mod oidc_service;
+pub mod session_service;
+pub mod role_service;
+pub mod admin_service;
+pub mod account_purge_job;
+pub mod auth_event_service;
+pub mod storage;
+pub mod avatar_service;
+pub mod tag_service;
+pub mod notebook_service;
+pub mod note_share_service;
\ No newline at end of file
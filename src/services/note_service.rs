@@ -1,7 +1,54 @@
 use crate::models::notes_model::*;
 use crate::services::database::DatabasePool;
 use anyhow::Result;
-use chrono::{DateTime, Utc};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use tokio_postgres::Row;
+
+pub const DEFAULT_PAGE_SIZE: i64 = 20;
+
+/// Where a page of notes ends: the sort value and ID of its last note. Clients only see it
+/// encoded, and it is only valid for the sort order it was issued for.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct NoteCursor {
+    sort: NoteSortField,
+    order: SortOrder,
+    value: String,
+    id: i32,
+}
+
+impl NoteCursor {
+    fn after(note: &NoteResponse, sort: NoteSortField, order: SortOrder) -> Self {
+        let value = match sort {
+            NoteSortField::Title => note.title.clone(),
+            NoteSortField::CreatedAt => note.created_at.to_rfc3339_opts(SecondsFormat::Micros, true),
+            NoteSortField::UpdatedAt => note.updated_at.to_rfc3339_opts(SecondsFormat::Micros, true),
+        };
+
+        Self { sort, order, value, id: note.id }
+    }
+
+    fn encode(&self) -> Result<String> {
+        Ok(URL_SAFE_NO_PAD.encode(serde_json::to_vec(self)?))
+    }
+
+    fn decode(cursor: &str, sort: NoteSortField, order: SortOrder) -> Result<Self> {
+        let invalid = || anyhow::anyhow!("Invalid cursor");
+
+        let decoded = URL_SAFE_NO_PAD.decode(cursor).map_err(|_| invalid())?;
+        let cursor: Self = serde_json::from_slice(&decoded).map_err(|_| invalid())?;
+
+        // Sort values are compared as typed by the database; reject anything that would not cast
+        let value_valid = sort == NoteSortField::Title || DateTime::parse_from_rfc3339(&cursor.value).is_ok();
+        if cursor.sort != sort || cursor.order != order || !value_valid {
+            return Err(anyhow::anyhow!("Invalid cursor for this sort order"));
+        }
+
+        Ok(cursor)
+    }
+}
 
 pub struct NoteService {
     db: DatabasePool,
@@ -13,51 +60,99 @@ impl NoteService {
     }
 
     pub async fn create_note(&self, request: CreateNoteRequest, user_id: i32) -> Result<NoteResponse> {
-        let query = "SELECT * FROM sp_create_or_update_note($1, $2, $3, $4) as note_id";
+        let query = "SELECT * FROM sp_create_or_update_note($1, $2, $3, $4, $5, $6) as note_id";
         let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[
             &request.id,
             &request.title,
             &request.content,
             &user_id,
+            &distinct_tags(&request.tags),
+            &request.notebook_id,
         ];
 
         let row = self.db.execute_query_one(query, params).await?;
         println!("Row returned from database: {:?}", row);
         match row {
             Some(row) => {
-                let note_id: i32 = row.get("noteid");
+                let note_id: Option<i32> = row.get("noteid");
+                let operation: String = row.get("operation");
 
-                // Get the created note
-                self.get_note_by_id(note_id, user_id).await?
-                    .ok_or_else(|| anyhow::anyhow!("Failed to retrieve created note"))
+                match (note_id, operation.as_str()) {
+                    // Get the created note
+                    (Some(note_id), _) => self.get_note_by_id(note_id, user_id).await?
+                        .ok_or_else(|| anyhow::anyhow!("Failed to retrieve created note")),
+                    (None, "notebook_not_found") => Err(anyhow::anyhow!("Notebook not found")),
+                    (None, _) => Err(anyhow::anyhow!("Note not found")),
+                }
             }
             None => Err(anyhow::anyhow!("Failed to create note")),
         }
     }
 
-    pub async fn get_user_notes(&self, user_id: i32) -> Result<Vec<NoteResponse>> {
+    /// One page of the user's notes, in the requested order and narrowed by the query's filters
+    pub async fn get_user_notes(&self, user_id: i32, query: &NoteListQuery) -> Result<NoteListResponse> {
+        let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+        let (sort, order) = (query.sort(), query.order());
+        let after = query.cursor.as_deref().map(|cursor| NoteCursor::decode(cursor, sort, order)).transpose()?;
+        let tags = query.tag_filter();
+        let match_all = query.tag_match() == TagMatch::All;
+        let include_sub_notebooks = query.include_sub_notebooks.unwrap_or(false);
+
+        // One extra row tells whether another page follows
+        let sql = "SELECT * FROM sp_get_user_notes($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16)";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[
+            &user_id,
+            &((limit + 1) as i32),
+            &sort.as_str(),
+            &(order == SortOrder::Desc),
+            &after.as_ref().map(|cursor| cursor.value.as_str()),
+            &after.as_ref().map(|cursor| cursor.id),
+            &query.created_from,
+            &query.created_to,
+            &query.updated_from,
+            &query.updated_to,
+            &query.min_length,
+            &query.max_length,
+            &tags,
+            &match_all,
+            &query.notebook_id,
+            &include_sub_notebooks,
+        ];
+        let rows = self.db.execute_query(sql, params).await?;
+
+        let total = match query.include_total {
+            Some(true) => {
+                let sql = "SELECT sp_count_user_notes($1, NULL, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11) as total";
+                let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[
+                    &user_id,
+                    &query.created_from,
+                    &query.created_to,
+                    &query.updated_from,
+                    &query.updated_to,
+                    &query.min_length,
+                    &query.max_length,
+                    &tags,
+                    &match_all,
+                    &query.notebook_id,
+                    &include_sub_notebooks,
+                ];
+                let row = self.db.execute_query_one(sql, params).await?;
+                Some(row.map_or(0, |row| row.get("total")))
+            }
+            _ => None,
+        };
+
+        note_page(&rows, limit, total, sort, order)
+    }
+
+    /// Every note of the user at once, e.g. for the data export
+    pub async fn get_all_user_notes(&self, user_id: i32) -> Result<Vec<NoteResponse>> {
         let query = "SELECT * FROM sp_get_user_notes($1)";
         let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[&user_id];
 
         let rows = self.db.execute_query(query, params).await?;
-        
-        let notes = rows.into_iter().map(|row| {
-            let id: i32 = row.get("id");
-            let title: String = row.get("title");
-            let content: String = row.get("content");
-            let created_at: DateTime<Utc> = row.get("createdat");
-            let updated_at: DateTime<Utc> = row.get("updatedat");
-
-            NoteResponse {
-                id,
-                title,
-                content,
-                created_at,
-                updated_at,
-            }
-        }).collect();
 
-        Ok(notes)
+        Ok(rows.iter().map(note_from_row).collect())
     }
 
     pub async fn get_note_by_id(&self, note_id: i32, user_id: i32) -> Result<Option<NoteResponse>> {
@@ -65,34 +160,20 @@ impl NoteService {
         let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[&note_id, &user_id];
 
         let row = self.db.execute_query_one(query, params).await?;
-        
-        match row {
-            Some(row) => {
-                let id: i32 = row.get("id");
-                let title: String = row.get("title");
-                let content: String = row.get("content");
-                let created_at: DateTime<Utc> = row.get("createdat");
-                let updated_at: DateTime<Utc> = row.get("updatedat");
-
-                Ok(Some(NoteResponse {
-                    id,
-                    title,
-                    content,
-                    created_at,
-                    updated_at,
-                }))
-            }
-            None => Ok(None),
-        }
+
+        Ok(row.as_ref().map(note_from_row))
     }
 
     pub async fn update_note(&self, note_id: i32, request: UpdateNoteRequest, user_id: i32) -> Result<Option<NoteResponse>> {
-        let query = "SELECT sp_update_note($1, $2, $3, $4) as updated";
+        let query = "SELECT sp_update_note($1, $2, $3, $4, $5, $6, $7) as updated";
         let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[
             &note_id,
             &request.title,
             &request.content,
             &user_id,
+            &request.tags.as_deref().map(distinct_tags),
+            &request.notebook_id.flatten(),
+            &request.notebook_id.is_some(),
         ];
 
         let row = self.db.execute_query_one(query, params).await?;
@@ -100,10 +181,12 @@ impl NoteService {
         match row {
             Some(row) => {
                 let updated: i32 = row.get("updated");
-                if updated == 1 {
-                    self.get_note_by_id(note_id, user_id).await
-                } else {
-                    Ok(None)
+                match updated {
+                    1 => self.get_note_by_id(note_id, user_id).await,
+                    -1 => Err(anyhow::anyhow!("Notebook not found")),
+                    -2 => Err(anyhow::anyhow!("The note is shared with you read-only")),
+                    -3 => Err(anyhow::anyhow!("Only the owner of a shared note can change its tags or notebook")),
+                    _ => Ok(None),
                 }
             }
             None => Ok(None),
@@ -125,28 +208,173 @@ impl NoteService {
         }
     }
 
-    pub async fn search_notes(&self, user_id: i32, search_term: Option<String>) -> Result<Vec<NoteResponse>> {
-        let query = "SELECT * FROM sp_search_notes($1, $2)";
-        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[&user_id, &search_term];
+    /// One page of the user's own and shared notes whose title or content contains the search
+    /// term, newest first
+    pub async fn search_notes(&self, user_id: i32, request: &SearchRequest) -> Result<NoteListResponse> {
+        let limit = request.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+        let (sort, order) = (NoteSortField::UpdatedAt, SortOrder::Desc);
+        let after = request.cursor.as_deref().map(|cursor| NoteCursor::decode(cursor, sort, order)).transpose()?;
+        let after_updated_at = after
+            .as_ref()
+            .and_then(|cursor| DateTime::parse_from_rfc3339(&cursor.value).ok())
+            .map(|updated_at| updated_at.with_timezone(&Utc));
+        let include_sub_notebooks = request.include_sub_notebooks.unwrap_or(false);
 
+        let query = "SELECT * FROM sp_search_notes($1, $2, $3, $4, $5, $6, $7)";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[
+            &user_id,
+            &request.search_term,
+            &((limit + 1) as i32),
+            &after_updated_at,
+            &after.as_ref().map(|cursor| cursor.id),
+            &request.notebook_id,
+            &include_sub_notebooks,
+        ];
         let rows = self.db.execute_query(query, params).await?;
-        
-        let notes = rows.into_iter().map(|row| {
-            let id: i32 = row.get("id");
-            let title: String = row.get("title");
-            let content: String = row.get("content");
-            let created_at: DateTime<Utc> = row.get("createdat");
-            let updated_at: DateTime<Utc> = row.get("updatedat");
-
-            NoteResponse {
-                id,
-                title,
-                content,
-                created_at,
-                updated_at,
+
+        let total = match request.include_total {
+            Some(true) => {
+                let query = "SELECT sp_count_user_notes($1, $2, p_notebook_id => $3, p_include_sub_notebooks => $4, p_include_shared => TRUE) as total";
+                let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[
+                    &user_id,
+                    &request.search_term,
+                    &request.notebook_id,
+                    &include_sub_notebooks,
+                ];
+                let row = self.db.execute_query_one(query, params).await?;
+                Some(row.map_or(0, |row| row.get("total")))
+            }
+            _ => None,
+        };
+
+        note_page(&rows, limit, total, sort, order)
+    }
+
+    /// One page of the notes other users shared with the user, newest first
+    pub async fn get_shared_notes(&self, user_id: i32, query: &SharedNotesQuery) -> Result<NoteListResponse> {
+        let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+        let (sort, order) = (NoteSortField::UpdatedAt, SortOrder::Desc);
+        let after = query.cursor.as_deref().map(|cursor| NoteCursor::decode(cursor, sort, order)).transpose()?;
+        let after_updated_at = after
+            .as_ref()
+            .and_then(|cursor| DateTime::parse_from_rfc3339(&cursor.value).ok())
+            .map(|updated_at| updated_at.with_timezone(&Utc));
+
+        let sql = "SELECT * FROM sp_get_shared_notes($1, $2, $3, $4)";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[
+            &user_id,
+            &((limit + 1) as i32),
+            &after_updated_at,
+            &after.as_ref().map(|cursor| cursor.id),
+        ];
+        let rows = self.db.execute_query(sql, params).await?;
+
+        note_page(&rows, limit, None, sort, order)
+    }
+}
+
+fn note_from_row(row: &Row) -> NoteResponse {
+    NoteResponse {
+        id: row.get("id"),
+        title: row.get("title"),
+        content: row.get("content"),
+        created_at: row.get("createdat"),
+        updated_at: row.get("updatedat"),
+        tags: row.get("tags"),
+        notebook_id: row.get("notebookid"),
+        owner_id: row.get("ownerid"),
+        permission: NotePermission::from_db(row.get("permission")),
+    }
+}
+
+// Tag names match regardless of case; the first spelling of each tag wins
+fn distinct_tags(tags: &[String]) -> Vec<String> {
+    let mut distinct: Vec<String> = Vec::new();
+    for tag in tags {
+        if !distinct.iter().any(|seen| seen.to_lowercase() == tag.to_lowercase()) {
+            distinct.push(tag.clone());
+        }
+    }
+
+    distinct
+}
+
+// `rows` holds up to one note more than the page; its presence means there is a next page
+fn note_page(rows: &[Row], limit: i64, total: Option<i64>, sort: NoteSortField, order: SortOrder) -> Result<NoteListResponse> {
+    let mut items: Vec<NoteResponse> = rows.iter().map(note_from_row).collect();
+
+    let next_cursor = if items.len() as i64 > limit {
+        items.truncate(limit as usize);
+        items.last().map(|note| NoteCursor::after(note, sort, order).encode()).transpose()?
+    } else {
+        None
+    };
+
+    Ok(NoteListResponse { items, next_cursor, total })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cursor(sort: NoteSortField, order: SortOrder, value: &str) -> NoteCursor {
+        NoteCursor { sort, order, value: value.to_string(), id: 42 }
+    }
+
+    #[test]
+    fn cursor_round_trips() {
+        for (sort, order, value) in [
+            (NoteSortField::Title, SortOrder::Asc, "Groceries, \"urgent\" / ünïcode"),
+            (NoteSortField::CreatedAt, SortOrder::Desc, "2026-03-01T08:15:00.123456Z"),
+            (NoteSortField::UpdatedAt, SortOrder::Asc, "2026-03-01T15:15:00+07:00"),
+        ] {
+            let encoded = cursor(sort, order, value).encode().unwrap();
+            assert_eq!(NoteCursor::decode(&encoded, sort, order).unwrap(), cursor(sort, order, value));
+        }
+    }
+
+    #[test]
+    fn cursor_from_another_sort_or_order_is_rejected() {
+        let encoded = cursor(NoteSortField::CreatedAt, SortOrder::Desc, "2026-03-01T08:15:00Z").encode().unwrap();
+
+        let err = NoteCursor::decode(&encoded, NoteSortField::UpdatedAt, SortOrder::Desc).unwrap_err();
+        assert_eq!(err.to_string(), "Invalid cursor for this sort order");
+        let err = NoteCursor::decode(&encoded, NoteSortField::CreatedAt, SortOrder::Asc).unwrap_err();
+        assert_eq!(err.to_string(), "Invalid cursor for this sort order");
+    }
+
+    #[test]
+    fn garbage_cursor_is_rejected() {
+        for garbage in ["not a cursor!", "", &URL_SAFE_NO_PAD.encode(b"{\"sort\": 1}")] {
+            let err = NoteCursor::decode(garbage, NoteSortField::Title, SortOrder::Asc).unwrap_err();
+            assert_eq!(err.to_string(), "Invalid cursor");
+        }
+    }
+
+    #[test]
+    fn timestamp_cursor_needs_an_rfc3339_value() {
+        for sort in [NoteSortField::CreatedAt, NoteSortField::UpdatedAt] {
+            for value in ["yesterday", "2026-03-01 08:15:00", "1772352900"] {
+                let encoded = cursor(sort, SortOrder::Desc, value).encode().unwrap();
+                assert!(NoteCursor::decode(&encoded, sort, SortOrder::Desc).is_err(), "{}", value);
             }
-        }).collect();
+        }
+
+        // Any text is a valid title
+        let encoded = cursor(NoteSortField::Title, SortOrder::Asc, "yesterday").encode().unwrap();
+        assert!(NoteCursor::decode(&encoded, NoteSortField::Title, SortOrder::Asc).is_ok());
+    }
+
+    #[test]
+    fn distinct_tags_drops_case_insensitive_duplicates_keeping_the_first_spelling() {
+        let tags: Vec<String> = ["Work", "urgent", "work", "WORK", "Urgent", "home"].map(String::from).to_vec();
+        assert_eq!(distinct_tags(&tags), vec!["Work", "urgent", "home"]);
+    }
 
-        Ok(notes)
+    #[test]
+    fn distinct_tags_keeps_distinct_tags_in_order() {
+        let tags: Vec<String> = ["b", "a", "c"].map(String::from).to_vec();
+        assert_eq!(distinct_tags(&tags), tags);
+        assert!(distinct_tags(&[]).is_empty());
     }
 }
\ No newline at end of file
@@ -0,0 +1,74 @@
+use crate::models::note_share_model::*;
+use crate::models::notes_model::NotePermission;
+use crate::services::database::DatabasePool;
+use crate::services::note_service::NoteService;
+use anyhow::Result;
+use tokio_postgres::Row;
+
+pub struct NoteShareService {
+    db: DatabasePool,
+}
+
+impl NoteShareService {
+    pub fn new(db: DatabasePool) -> Self {
+        Self { db }
+    }
+
+    /// Users the note is shared with; only its owner may see them
+    pub async fn get_shares(&self, note_id: i32, user_id: i32) -> Result<Vec<NoteShareResponse>> {
+        let note = NoteService::new(self.db.clone()).get_note_by_id(note_id, user_id).await?;
+        if !note.is_some_and(|note| note.permission == NotePermission::Owner) {
+            return Err(anyhow::anyhow!("Note not found"));
+        }
+
+        let query = "SELECT * FROM sp_get_note_shares($1, $2)";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[&note_id, &user_id];
+
+        let rows = self.db.execute_query(query, params).await?;
+
+        Ok(rows.iter().map(share_from_row).collect())
+    }
+
+    /// Grant a user access to the owner's note, or change the access they have. The flag tells
+    /// whether the note was newly shared with them.
+    pub async fn share_note(&self, note_id: i32, request: ShareNoteRequest, owner_id: i32) -> Result<(bool, NoteShareResponse)> {
+        let query = "SELECT * FROM sp_share_note($1, $2, $3, $4)";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[
+            &note_id,
+            &owner_id,
+            &request.username,
+            &request.permission.as_str(),
+        ];
+
+        let row = self.db.execute_query_one(query, params).await?
+            .ok_or_else(|| anyhow::anyhow!("Failed to share note"))?;
+        let status: String = row.get("status");
+
+        match status.as_str() {
+            "shared" => Ok((true, share_from_row(&row))),
+            "updated" => Ok((false, share_from_row(&row))),
+            "note_not_found" => Err(anyhow::anyhow!("Note not found")),
+            "user_not_found" => Err(anyhow::anyhow!("User not found: {}", request.username)),
+            "own_note" => Err(anyhow::anyhow!("A note cannot be shared with its owner")),
+            _ => Err(anyhow::anyhow!("Failed to share note")),
+        }
+    }
+
+    /// Revoke a user's access to a note. Owners can revoke anyone; collaborators only themselves.
+    pub async fn unshare_note(&self, note_id: i32, user_id: i32, caller_id: i32) -> Result<bool> {
+        let query = "SELECT sp_unshare_note($1, $2, $3) as unshared";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[&note_id, &user_id, &caller_id];
+
+        let row = self.db.execute_query_one(query, params).await?;
+        Ok(row.is_some_and(|row| row.get::<_, i32>("unshared") == 1))
+    }
+}
+
+fn share_from_row(row: &Row) -> NoteShareResponse {
+    NoteShareResponse {
+        user_id: row.get("userid"),
+        username: row.get("username"),
+        permission: SharePermission::from_db(row.get("permission")),
+        created_at: row.get("createdat"),
+    }
+}
@@ -0,0 +1,83 @@
+use crate::models::notebook_model::*;
+use crate::services::database::DatabasePool;
+use anyhow::Result;
+use tokio_postgres::Row;
+
+pub struct NotebookService {
+    db: DatabasePool,
+}
+
+impl NotebookService {
+    pub fn new(db: DatabasePool) -> Self {
+        Self { db }
+    }
+
+    /// Every notebook of the user; `parent_id` links them into a tree
+    pub async fn get_notebooks(&self, user_id: i32) -> Result<Vec<NotebookResponse>> {
+        let query = "SELECT * FROM sp_get_notebooks($1)";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[&user_id];
+
+        let rows = self.db.execute_query(query, params).await?;
+
+        Ok(rows.iter().map(notebook_from_row).collect())
+    }
+
+    pub async fn create_notebook(&self, request: CreateNotebookRequest, user_id: i32) -> Result<NotebookResponse> {
+        let query = "SELECT * FROM sp_create_notebook($1, $2, $3)";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[&user_id, &request.name, &request.parent_id];
+
+        let row = self.db.execute_query_one(query, params).await?
+            .ok_or_else(|| anyhow::anyhow!("Parent notebook not found"))?;
+
+        Ok(notebook_from_row(&row))
+    }
+
+    pub async fn rename_notebook(&self, notebook_id: i32, request: RenameNotebookRequest, user_id: i32) -> Result<bool> {
+        let query = "SELECT sp_rename_notebook($1, $2, $3) as renamed";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[&notebook_id, &user_id, &request.name];
+
+        let row = self.db.execute_query_one(query, params).await?;
+        Ok(row.is_some_and(|row| row.get::<_, i32>("renamed") == 1))
+    }
+
+    /// Nest a notebook, with everything inside it, below another one or move it to the top level
+    pub async fn move_notebook(&self, notebook_id: i32, request: MoveNotebookRequest, user_id: i32) -> Result<()> {
+        let query = "SELECT sp_move_notebook($1, $2, $3) as status";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[&notebook_id, &user_id, &request.parent_id];
+
+        let row = self.db.execute_query_one(query, params).await?
+            .ok_or_else(|| anyhow::anyhow!("Failed to move notebook"))?;
+        let status: String = row.get("status");
+
+        match status.as_str() {
+            "moved" => Ok(()),
+            "not_found" => Err(anyhow::anyhow!("Notebook not found")),
+            "parent_not_found" => Err(anyhow::anyhow!("Parent notebook not found")),
+            "cycle" => Err(anyhow::anyhow!("A notebook cannot be moved into itself or a notebook nested in it")),
+            _ => Err(anyhow::anyhow!("Failed to move notebook")),
+        }
+    }
+
+    pub async fn delete_notebook(&self, notebook_id: i32, mode: NotebookDeleteMode, user_id: i32) -> Result<bool> {
+        let query = "SELECT sp_delete_notebook($1, $2, $3) as deleted";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[
+            &notebook_id,
+            &user_id,
+            &(mode == NotebookDeleteMode::Cascade),
+        ];
+
+        let row = self.db.execute_query_one(query, params).await?;
+        Ok(row.is_some_and(|row| row.get::<_, i32>("deleted") == 1))
+    }
+}
+
+fn notebook_from_row(row: &Row) -> NotebookResponse {
+    NotebookResponse {
+        id: row.get("id"),
+        parent_id: row.get("parentid"),
+        name: row.get("name"),
+        note_count: row.get("notecount"),
+        created_at: row.get("createdat"),
+        updated_at: row.get("updatedat"),
+    }
+}
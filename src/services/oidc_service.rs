@@ -0,0 +1,221 @@
+use crate::models::auth_model::LoginResponse;
+use crate::models::oidc_model::*;
+use crate::services::auth_service::AuthService;
+use crate::services::database::DatabasePool;
+use crate::utils::client_device::ClientDevice;
+use crate::utils::oidc::{self, http_client, OidcProvider};
+use crate::utils::password::UNUSABLE_PASSWORD_HASH;
+use crate::utils::token::{generate_token, hash_token};
+use anyhow::{Context, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use tracing::info;
+
+const STATE_TTL_SECONDS: u64 = 60 * 10; // 10 minutes
+
+// ID tokens must be signed with the provider's published keys; HMAC would mean the client secret
+const ID_TOKEN_ALGORITHMS: &[Algorithm] = &[
+    Algorithm::RS256,
+    Algorithm::RS384,
+    Algorithm::RS512,
+    Algorithm::PS256,
+    Algorithm::PS384,
+    Algorithm::PS512,
+    Algorithm::ES256,
+    Algorithm::ES384,
+    Algorithm::EdDSA,
+];
+
+#[derive(Debug, Deserialize)]
+struct TokenEndpointResponse {
+    id_token: String,
+}
+
+/// Login through external OpenID Connect providers (authorization code flow with PKCE)
+pub struct OidcService {
+    db: DatabasePool,
+}
+
+impl OidcService {
+    pub fn new(db: DatabasePool) -> Self {
+        Self { db }
+    }
+
+    /// Start a login: remember the PKCE verifier and nonce under a fresh state and
+    /// build the URL that sends the user to the provider
+    pub async fn begin_login(&self, provider_name: &str) -> Result<OidcAuthorizationResponse> {
+        let provider = find_provider(provider_name)?;
+        let metadata = provider.metadata().await.context("Identity provider request failed")?;
+
+        let state = generate_token();
+        let nonce = generate_token();
+        let code_verifier = generate_token();
+        let code_challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()));
+
+        let query = "SELECT sp_create_oauth_state($1, $2, $3, $4, $5)";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[
+            &hash_token(&state),
+            &provider.name,
+            &code_verifier,
+            &nonce,
+            &(STATE_TTL_SECONDS as i32),
+        ];
+        self.db.execute_command(query, params).await?;
+
+        let authorization_url = reqwest::Url::parse_with_params(
+            &metadata.authorization_endpoint,
+            &[
+                ("response_type", "code"),
+                ("client_id", provider.client_id.as_str()),
+                ("redirect_uri", provider.redirect_uri.as_str()),
+                ("scope", provider.scopes.as_str()),
+                ("state", state.as_str()),
+                ("nonce", nonce.as_str()),
+                ("code_challenge", code_challenge.as_str()),
+                ("code_challenge_method", "S256"),
+            ],
+        )
+        .context("Invalid authorization endpoint")?;
+
+        Ok(OidcAuthorizationResponse {
+            authorization_url: authorization_url.into(),
+            expires_in: STATE_TTL_SECONDS,
+        })
+    }
+
+    /// Finish a login with the code and state the provider redirected back with.
+    /// The identity is linked to a local user, who is created on first login.
+    pub async fn complete_login(
+        &self,
+        provider_name: &str,
+        request: OidcCallbackRequest,
+        device: &ClientDevice,
+    ) -> Result<LoginResponse> {
+        let provider = find_provider(provider_name)?;
+
+        // States are single use, so a replayed callback fails here
+        let query = "SELECT * FROM sp_consume_oauth_state($1, $2)";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[&hash_token(&request.state), &provider.name];
+        let row = self.db.execute_query_one(query, params).await?
+            .ok_or_else(|| anyhow::anyhow!("Invalid or expired state"))?;
+        let code_verifier: String = row.get("codeverifier");
+        let nonce: String = row.get("nonce");
+
+        let id_token = exchange_code(provider, &request.code, &code_verifier)
+            .await
+            .context("Identity provider request failed")?;
+        let claims = validate_id_token(provider, &id_token, &nonce).await?;
+
+        let email = claims.email.as_deref()
+            .filter(|email| !email.trim().is_empty())
+            .ok_or_else(|| anyhow::anyhow!("The identity provider did not share an email address"))?;
+        let username = username_for(&claims, email);
+
+        let query = "SELECT * FROM sp_find_or_create_oidc_user($1, $2, $3, $4, $5, $6)";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[
+            &provider.name,
+            &claims.sub,
+            &email,
+            &claims.is_email_verified(),
+            &username,
+            &UNUSABLE_PASSWORD_HASH,
+        ];
+        let row = self.db.execute_query_one(query, params).await?
+            .ok_or_else(|| anyhow::anyhow!("Failed to link identity"))?;
+        let status: String = row.get("status");
+
+        let user_id: i32 = match status.as_str() {
+            "linked" | "linked_email" | "created" => row.get("userid"),
+            // Linking on an unverified email would let anyone claim an account by typing its address at the provider
+            "email_taken" => return Err(anyhow::anyhow!(
+                "An account with this email address already exists and the identity provider has not verified it"
+            )),
+            _ => return Err(anyhow::anyhow!("Failed to link identity")),
+        };
+        if status != "linked" {
+            info!("Identity {} of {} {} user_id: {}", claims.sub, provider.name, status, user_id);
+        }
+
+        AuthService::new(self.db.clone()).login_user_by_id(user_id, device).await
+    }
+}
+
+fn find_provider(name: &str) -> Result<&'static OidcProvider> {
+    oidc::provider(name).ok_or_else(|| anyhow::anyhow!("Unknown OIDC provider: {}", name))
+}
+
+/// Redeem the authorization code at the token endpoint; returns the ID token
+async fn exchange_code(provider: &OidcProvider, code: &str, code_verifier: &str) -> Result<String> {
+    let metadata = provider.metadata().await?;
+
+    let mut form = vec![
+        ("grant_type", "authorization_code"),
+        ("code", code),
+        ("redirect_uri", provider.redirect_uri.as_str()),
+        ("client_id", provider.client_id.as_str()),
+        ("code_verifier", code_verifier),
+    ];
+    if let Some(client_secret) = &provider.client_secret {
+        form.push(("client_secret", client_secret.as_str()));
+    }
+
+    let response: TokenEndpointResponse = http_client()
+        .post(&metadata.token_endpoint)
+        .form(&form)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await
+        .context("Invalid token endpoint response")?;
+
+    Ok(response.id_token)
+}
+
+/// Check signature, issuer, audience, expiry and nonce of an ID token (OpenID Connect Core 1.0, 3.1.3.7)
+async fn validate_id_token(provider: &OidcProvider, id_token: &str, nonce: &str) -> Result<IdTokenClaims> {
+    let header = decode_header(id_token).map_err(|err| anyhow::anyhow!("Invalid ID token: {}", err))?;
+    if !ID_TOKEN_ALGORITHMS.contains(&header.alg) {
+        return Err(anyhow::anyhow!("Invalid ID token: unsupported algorithm {:?}", header.alg));
+    }
+
+    let metadata = provider.metadata().await.context("Identity provider request failed")?;
+    let jwk = provider.signing_key(header.kid.as_deref())
+        .await
+        .map_err(|err| anyhow::anyhow!("Invalid ID token: {}", err))?;
+    let key = DecodingKey::from_jwk(&jwk)?;
+
+    let mut validation = Validation::new(header.alg);
+    validation.set_audience(&[&provider.client_id]);
+    validation.set_issuer(&[&metadata.issuer]);
+    validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+
+    let claims = decode::<IdTokenClaims>(id_token, &key, &validation)
+        .map_err(|err| anyhow::anyhow!("Invalid ID token: {}", err))?
+        .claims;
+
+    if claims.nonce.as_deref() != Some(nonce) {
+        return Err(anyhow::anyhow!("Invalid ID token: nonce mismatch"));
+    }
+
+    Ok(claims)
+}
+
+// Username for a new account: the provider's preferred username, else the local part of the email.
+// The stored procedure makes it unique.
+fn username_for(claims: &IdTokenClaims, email: &str) -> String {
+    let candidate = claims.preferred_username.as_deref()
+        .unwrap_or_else(|| email.split('@').next().unwrap_or_default());
+    let username: String = candidate
+        .chars()
+        .filter(|c| c.is_alphanumeric() || matches!(c, '.' | '_' | '-'))
+        .collect();
+
+    if username.chars().count() >= 3 {
+        username
+    } else {
+        format!("user_{}", username)
+    }
+}
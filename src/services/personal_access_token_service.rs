@@ -0,0 +1,112 @@
+use crate::models::auth_model::AuthContext;
+use crate::models::token_model::*;
+use crate::services::database::DatabasePool;
+use crate::utils::token::{generate_token, hash_token};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use tokio_postgres::Row;
+
+/// Marks a bearer token as a personal access token rather than a JWT
+pub const PERSONAL_ACCESS_TOKEN_PREFIX: &str = "pat_";
+
+// Characters of the token kept in clear text for display
+const DISPLAY_PREFIX_LENGTH: usize = 12;
+
+pub struct PersonalAccessTokenService {
+    db: DatabasePool,
+}
+
+impl PersonalAccessTokenService {
+    pub fn new(db: DatabasePool) -> Self {
+        Self { db }
+    }
+
+    pub async fn create_token(&self, request: CreatePersonalAccessTokenRequest, user_id: i32) -> Result<CreatedPersonalAccessTokenResponse> {
+        if let Some(scope) = request.scopes.iter().find(|scope| !TOKEN_SCOPES.contains(&scope.as_str())) {
+            return Err(anyhow::anyhow!("Unknown scope: {}", scope));
+        }
+
+        let token = format!("{}{}", PERSONAL_ACCESS_TOKEN_PREFIX, generate_token());
+        let token_prefix = &token[..DISPLAY_PREFIX_LENGTH];
+        let ttl_seconds = request.expires_in_days.map(|days| (days * 24 * 60 * 60) as i32);
+
+        let query = "SELECT * FROM sp_create_personal_access_token($1, $2, $3, $4, $5, $6)";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[
+            &user_id,
+            &request.name,
+            &hash_token(&token),
+            &token_prefix,
+            &request.scopes,
+            &ttl_seconds,
+        ];
+
+        let row = self.db.execute_query_one(query, params).await?
+            .ok_or_else(|| anyhow::anyhow!("Failed to create personal access token"))?;
+
+        Ok(CreatedPersonalAccessTokenResponse {
+            token,
+            details: token_from_row(&row),
+        })
+    }
+
+    pub async fn get_user_tokens(&self, user_id: i32) -> Result<Vec<PersonalAccessTokenResponse>> {
+        let query = "SELECT * FROM sp_get_personal_access_tokens($1)";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[&user_id];
+
+        let rows = self.db.execute_query(query, params).await?;
+
+        Ok(rows.iter().map(token_from_row).collect())
+    }
+
+    pub async fn revoke_token(&self, token_id: i32, user_id: i32) -> Result<bool> {
+        let query = "SELECT sp_revoke_personal_access_token($1, $2) as revoked";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[&token_id, &user_id];
+
+        let row = self.db.execute_query_one(query, params).await?;
+        Ok(row.is_some_and(|row| row.get::<_, i32>("revoked") == 1))
+    }
+
+    /// Look up a presented personal access token. Returns the status (one of: valid, expired,
+    /// revoked, suspended, invalid) and, for valid tokens, the caller's identity.
+    pub async fn authenticate(&self, token: &str) -> Result<(String, Option<AuthContext>)> {
+        let query = "SELECT * FROM sp_authenticate_personal_access_token($1)";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[&hash_token(token)];
+
+        let row = self.db.execute_query_one(query, params).await?
+            .ok_or_else(|| anyhow::anyhow!("Failed to check personal access token"))?;
+        let status: String = row.get("status");
+
+        if status != "valid" {
+            return Ok((status, None));
+        }
+
+        let token_id: i32 = row.get("id");
+        let email_verified: Option<bool> = row.get("emailverified");
+        let context = AuthContext {
+            user_id: row.get("userid"),
+            token_id: format!("pat:{}", token_id),
+            // Personal access tokens are not JWTs; logout and other session-only endpoints reject them
+            expires_at: 0,
+            email_verified: email_verified.unwrap_or(false),
+            scopes: Some(row.get("scopes")),
+            session_id: None,
+            permissions: Vec::new(),
+        };
+
+        Ok((status, Some(context)))
+    }
+}
+
+fn token_from_row(row: &Row) -> PersonalAccessTokenResponse {
+    let created_at: DateTime<Utc> = row.get("createdat");
+
+    PersonalAccessTokenResponse {
+        id: row.get("id"),
+        name: row.get("name"),
+        token_prefix: row.get("tokenprefix"),
+        scopes: row.get("scopes"),
+        expires_at: row.get("expiresat"),
+        last_used_at: row.get("lastusedat"),
+        created_at,
+    }
+}
@@ -0,0 +1,71 @@
+use crate::models::auth_model::UserAccess;
+use crate::models::role_model::RoleResponse;
+use crate::services::database::DatabasePool;
+use anyhow::Result;
+
+/// Roles and the permissions they grant. Changing a user's roles bumps their token version,
+/// so access tokens carrying the old roles are rejected and re-issued on the next refresh.
+pub struct RoleService {
+    db: DatabasePool,
+}
+
+impl RoleService {
+    pub fn new(db: DatabasePool) -> Self {
+        Self { db }
+    }
+
+    /// Roles and permissions to embed in the user's access tokens
+    pub async fn get_user_access(&self, user_id: i32) -> Result<UserAccess> {
+        let query = "SELECT * FROM sp_get_user_access($1)";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[&user_id];
+
+        let row = self.db.execute_query_one(query, params).await?;
+
+        Ok(row.map_or_else(UserAccess::default, |row| UserAccess {
+            roles: row.get("roles"),
+            permissions: row.get("permissions"),
+        }))
+    }
+
+    pub async fn get_roles(&self) -> Result<Vec<RoleResponse>> {
+        let query = "SELECT * FROM sp_get_roles()";
+        let rows = self.db.execute_query(query, &[]).await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| RoleResponse {
+                name: row.get("name"),
+                description: row.get("description"),
+                permissions: row.get("permissions"),
+            })
+            .collect())
+    }
+
+    /// Grant a role; returns false if the user already held it
+    pub async fn assign_role(&self, user_id: i32, role: &str) -> Result<bool> {
+        let query = "SELECT sp_assign_user_role($1, $2) as status";
+        self.change_role(query, user_id, role, "assigned").await
+    }
+
+    /// Revoke a role; returns false if the user did not hold it
+    pub async fn remove_role(&self, user_id: i32, role: &str) -> Result<bool> {
+        let query = "SELECT sp_remove_user_role($1, $2) as status";
+        self.change_role(query, user_id, role, "removed").await
+    }
+
+    async fn change_role(&self, query: &str, user_id: i32, role: &str, changed_status: &str) -> Result<bool> {
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[&user_id, &role];
+
+        let row = self.db.execute_query_one(query, params).await?
+            .ok_or_else(|| anyhow::anyhow!("Failed to change role"))?;
+        let status: String = row.get("status");
+
+        match status.as_str() {
+            "unknown_user" => Err(anyhow::anyhow!("User not found")),
+            "unknown_role" => Err(anyhow::anyhow!("Role not found: {}", role)),
+            "unchanged" => Ok(false),
+            status if status == changed_status => Ok(true),
+            _ => Err(anyhow::anyhow!("Failed to change role")),
+        }
+    }
+}
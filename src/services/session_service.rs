@@ -0,0 +1,63 @@
+use crate::models::session_model::*;
+use crate::services::database::DatabasePool;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use std::net::IpAddr;
+use tokio_postgres::Row;
+use uuid::Uuid;
+
+/// Login sessions of a user: one per device, started at login and kept alive by token refreshes
+pub struct SessionService {
+    db: DatabasePool,
+}
+
+impl SessionService {
+    pub fn new(db: DatabasePool) -> Self {
+        Self { db }
+    }
+
+    /// Active sessions of the user, most recently used first; `current_session_id` is flagged
+    pub async fn get_user_sessions(&self, user_id: i32, current_session_id: Option<Uuid>) -> Result<Vec<SessionResponse>> {
+        let query = "SELECT * FROM sp_get_user_sessions($1)";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[&user_id];
+
+        let rows = self.db.execute_query(query, params).await?;
+
+        Ok(rows.iter().map(|row| session_from_row(row, current_session_id)).collect())
+    }
+
+    /// Sign a session out. Its access tokens stop working immediately and its refresh token can no longer be used.
+    pub async fn revoke_session(&self, session_id: Uuid, user_id: i32) -> Result<bool> {
+        let query = "SELECT sp_revoke_session($1, $2) as revoked";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[&session_id, &user_id];
+
+        let row = self.db.execute_query_one(query, params).await?;
+        Ok(row.is_some_and(|row| row.get::<_, i32>("revoked") == 1))
+    }
+
+    /// Sign out every session of the user except `current_session_id`; returns how many were signed out
+    pub async fn revoke_other_sessions(&self, user_id: i32, current_session_id: Uuid) -> Result<i32> {
+        let query = "SELECT sp_revoke_other_sessions($1, $2) as revoked_sessions";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[&user_id, &current_session_id];
+
+        let row = self.db.execute_query_one(query, params).await?;
+        Ok(row.map_or(0, |row| row.get("revoked_sessions")))
+    }
+}
+
+fn session_from_row(row: &Row, current_session_id: Option<Uuid>) -> SessionResponse {
+    let id: Uuid = row.get("id");
+    let ip_address: Option<IpAddr> = row.get("ipaddress");
+    let created_at: Option<DateTime<Utc>> = row.get("createdat");
+    let last_seen_at: Option<DateTime<Utc>> = row.get("lastseenat");
+
+    SessionResponse {
+        id,
+        device_name: row.get("devicename"),
+        user_agent: row.get("useragent"),
+        ip_address: ip_address.map(|address| address.to_string()),
+        created_at,
+        last_seen_at,
+        current: current_session_id == Some(id),
+    }
+}
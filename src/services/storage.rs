@@ -0,0 +1,90 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use std::io::ErrorKind;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use tracing::info;
+
+/// Blob storage for uploaded files, addressed by `/` separated keys such as `avatars/7/<version>/128`
+#[async_trait]
+pub trait Storage: Send + Sync {
+    async fn put(&self, key: &str, data: Vec<u8>) -> Result<()>;
+    /// `None` when nothing is stored under the key
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>>;
+    /// Delete the object stored under `key` together with everything below it, e.g. `avatars/7`.
+    /// Deleting a key that does not exist is not an error.
+    async fn delete(&self, key: &str) -> Result<()>;
+}
+
+/// Build the storage backend selected by `STORAGE_BACKEND` (only `local`, the default, for now)
+pub fn from_env() -> Result<Arc<dyn Storage>> {
+    let backend = std::env::var("STORAGE_BACKEND").unwrap_or_else(|_| "local".to_string());
+
+    let storage: Arc<dyn Storage> = match backend.as_str() {
+        "local" => {
+            let dir = std::env::var("STORAGE_LOCAL_DIR").unwrap_or_else(|_| "uploads".to_string());
+            Arc::new(LocalStorage::new(PathBuf::from(dir)))
+        }
+        other => anyhow::bail!("Unknown STORAGE_BACKEND: {}", other),
+    };
+
+    info!("Using {} storage backend", backend);
+    Ok(storage)
+}
+
+/// Stores every object as a file below a directory
+pub struct LocalStorage {
+    dir: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    // Keys come from the application, but never let one point outside the storage directory
+    fn path(&self, key: &str) -> Result<PathBuf> {
+        let relative = Path::new(key);
+        if key.is_empty() || !relative.components().all(|component| matches!(component, Component::Normal(_))) {
+            anyhow::bail!("Invalid storage key: {}", key);
+        }
+
+        Ok(self.dir.join(relative))
+    }
+}
+
+#[async_trait]
+impl Storage for LocalStorage {
+    async fn put(&self, key: &str, data: Vec<u8>) -> Result<()> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        tokio::fs::write(&path, data).await?;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        match tokio::fs::read(self.path(key)?).await {
+            Ok(data) => Ok(Some(data)),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        let path = self.path(key)?;
+
+        let result = if tokio::fs::metadata(&path).await.is_ok_and(|metadata| metadata.is_dir()) {
+            tokio::fs::remove_dir_all(&path).await
+        } else {
+            tokio::fs::remove_file(&path).await
+        };
+
+        match result {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+}
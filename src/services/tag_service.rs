@@ -0,0 +1,85 @@
+use crate::models::tag_model::*;
+use crate::services::database::DatabasePool;
+use anyhow::Result;
+use tokio_postgres::Row;
+
+pub struct TagService {
+    db: DatabasePool,
+}
+
+impl TagService {
+    pub fn new(db: DatabasePool) -> Self {
+        Self { db }
+    }
+
+    /// The user's tags by name, with how many notes carry each
+    pub async fn get_tags(&self, user_id: i32) -> Result<Vec<TagResponse>> {
+        let query = "SELECT * FROM sp_get_tags($1)";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[&user_id];
+
+        let rows = self.db.execute_query(query, params).await?;
+
+        Ok(rows.iter().map(tag_from_row).collect())
+    }
+
+    pub async fn create_tag(&self, request: CreateTagRequest, user_id: i32) -> Result<TagResponse> {
+        let query = "SELECT * FROM sp_create_tag($1, $2)";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[&user_id, &request.name];
+
+        let row = self.db.execute_query_one(query, params).await?
+            .ok_or_else(|| anyhow::anyhow!("A tag named {} already exists", request.name))?;
+
+        Ok(tag_from_row(&row))
+    }
+
+    pub async fn rename_tag(&self, tag_id: i32, request: RenameTagRequest, user_id: i32) -> Result<()> {
+        let query = "SELECT sp_rename_tag($1, $2, $3) as status";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[&tag_id, &user_id, &request.name];
+
+        let status = self.tag_status(query, params).await?;
+        match status.as_str() {
+            "renamed" => Ok(()),
+            "not_found" => Err(anyhow::anyhow!("Tag not found")),
+            "name_taken" => Err(anyhow::anyhow!("A tag named {} already exists", request.name)),
+            _ => Err(anyhow::anyhow!("Failed to rename tag")),
+        }
+    }
+
+    /// Move the notes of one tag onto another and delete the first
+    pub async fn merge_tags(&self, tag_id: i32, request: MergeTagRequest, user_id: i32) -> Result<()> {
+        let query = "SELECT sp_merge_tags($1, $2, $3) as status";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[&tag_id, &request.into_tag_id, &user_id];
+
+        let status = self.tag_status(query, params).await?;
+        match status.as_str() {
+            "merged" => Ok(()),
+            "not_found" => Err(anyhow::anyhow!("Tag not found")),
+            "same_tag" => Err(anyhow::anyhow!("A tag cannot be merged into itself")),
+            _ => Err(anyhow::anyhow!("Failed to merge tags")),
+        }
+    }
+
+    pub async fn delete_tag(&self, tag_id: i32, user_id: i32) -> Result<bool> {
+        let query = "SELECT sp_delete_tag($1, $2) as deleted";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[&tag_id, &user_id];
+
+        let row = self.db.execute_query_one(query, params).await?;
+        Ok(row.is_some_and(|row| row.get::<_, i32>("deleted") == 1))
+    }
+
+    async fn tag_status(&self, query: &str, params: &[&(dyn tokio_postgres::types::ToSql + Sync)]) -> Result<String> {
+        let row = self.db.execute_query_one(query, params).await?
+            .ok_or_else(|| anyhow::anyhow!("Failed to change tag"))?;
+
+        Ok(row.get("status"))
+    }
+}
+
+fn tag_from_row(row: &Row) -> TagResponse {
+    TagResponse {
+        id: row.get("id"),
+        name: row.get("name"),
+        note_count: row.get("notecount"),
+        created_at: row.get("createdat"),
+    }
+}
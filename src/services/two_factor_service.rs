@@ -0,0 +1,199 @@
+use crate::models::two_factor_model::*;
+use crate::services::database::DatabasePool;
+use crate::services::login_throttle_service::{AttemptKey, LoginThrottleService};
+use crate::utils::client_device::ClientDevice;
+use crate::utils::token::hash_token;
+use anyhow::Result;
+use std::time::{SystemTime, UNIX_EPOCH};
+use totp_rs::{Algorithm, Secret, TOTP};
+
+// RFC 6238 defaults, which is what every authenticator app expects
+const TOTP_DIGITS: usize = 6;
+const TOTP_STEP_SECONDS: u64 = 30;
+const TOTP_SECRET_BYTES: usize = 20;
+const RECOVERY_CODE_COUNT: usize = 10;
+
+pub struct TwoFactorService {
+    db: DatabasePool,
+}
+
+impl TwoFactorService {
+    pub fn new(db: DatabasePool) -> Self {
+        Self { db }
+    }
+
+    /// Start enrollment with a fresh secret. Two-factor authentication stays off until
+    /// the first code generated from it is confirmed.
+    pub async fn begin_enrollment(&self, user_id: i32) -> Result<TotpEnrollmentResponse> {
+        let secret_bytes: [u8; TOTP_SECRET_BYTES] = rand::random();
+        let secret = Secret::Raw(secret_bytes.to_vec()).to_encoded().to_string();
+
+        let query = "SELECT * FROM sp_begin_totp_enrollment($1, $2)";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[&user_id, &secret];
+
+        let row = self.db.execute_query_one(query, params).await?
+            .ok_or_else(|| anyhow::anyhow!("Two-factor authentication is already enabled"))?;
+        let email: String = row.get("email");
+        let totp = build_totp(&secret, email)?;
+
+        Ok(TotpEnrollmentResponse {
+            secret,
+            otpauth_uri: totp.get_url(),
+        })
+    }
+
+    /// Enable two-factor authentication once the user proved their authenticator works.
+    /// Returns the initial set of recovery codes.
+    pub async fn confirm_enrollment(&self, user_id: i32, code: &str) -> Result<RecoveryCodesResponse> {
+        let secret = match self.get_totp_state(user_id).await? {
+            (Some(secret), false) => secret,
+            _ => return Err(anyhow::anyhow!("No pending two-factor enrollment")),
+        };
+        let step = matching_step(&secret, code)?
+            .ok_or_else(|| anyhow::anyhow!("Invalid two-factor code"))?;
+
+        let recovery_codes = generate_recovery_codes();
+        let code_hashes: Vec<String> = recovery_codes
+            .iter()
+            .map(|code| hash_token(&normalize_recovery_code(code)))
+            .collect();
+
+        let query = "SELECT sp_enable_totp($1, $2, $3) as enabled";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[
+            &user_id,
+            &(step as i64),
+            &code_hashes,
+        ];
+
+        let row = self.db.execute_query_one(query, params).await?;
+        match row.map(|row| row.get::<_, bool>("enabled")) {
+            Some(true) => Ok(RecoveryCodesResponse { recovery_codes }),
+            _ => Err(anyhow::anyhow!("No pending two-factor enrollment")),
+        }
+    }
+
+    /// Turn two-factor authentication off; requires a valid TOTP or recovery code
+    pub async fn disable(&self, user_id: i32, code: &str, device: &ClientDevice) -> Result<()> {
+        self.require_valid_code(user_id, code, device).await?;
+
+        let query = "SELECT sp_disable_totp($1)";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[&user_id];
+        self.db.execute_command(query, params).await?;
+
+        Ok(())
+    }
+
+    /// Replace every recovery code with a fresh set; requires a valid TOTP or recovery code
+    pub async fn regenerate_recovery_codes(&self, user_id: i32, code: &str, device: &ClientDevice) -> Result<RecoveryCodesResponse> {
+        self.require_valid_code(user_id, code, device).await?;
+
+        let recovery_codes = generate_recovery_codes();
+        let code_hashes: Vec<String> = recovery_codes
+            .iter()
+            .map(|code| hash_token(&normalize_recovery_code(code)))
+            .collect();
+
+        let query = "SELECT sp_replace_recovery_codes($1, $2)";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[&user_id, &code_hashes];
+        self.db.execute_command(query, params).await?;
+
+        Ok(RecoveryCodesResponse { recovery_codes })
+    }
+
+    /// Check a TOTP code or recovery code of a user with two-factor authentication enabled.
+    /// Accepted codes are consumed, so the same code never works twice.
+    pub async fn verify_code(&self, user_id: i32, code: &str) -> Result<bool> {
+        let secret = match self.get_totp_state(user_id).await? {
+            (Some(secret), true) => secret,
+            _ => return Err(anyhow::anyhow!("Two-factor authentication is not enabled")),
+        };
+
+        if let Some(step) = matching_step(&secret, code)? {
+            let query = "SELECT sp_record_totp_step($1, $2) as accepted";
+            let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[&user_id, &(step as i64)];
+
+            let row = self.db.execute_query_one(query, params).await?;
+            return Ok(row.is_some_and(|row| row.get("accepted")));
+        }
+
+        let query = "SELECT sp_use_recovery_code($1, $2) as accepted";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[
+            &user_id,
+            &hash_token(&normalize_recovery_code(code)),
+        ];
+
+        let row = self.db.execute_query_one(query, params).await?;
+        Ok(row.is_some_and(|row| row.get("accepted")))
+    }
+
+    // Shares the lockout with the login step, so a stolen access token cannot be used to guess codes
+    async fn require_valid_code(&self, user_id: i32, code: &str, device: &ClientDevice) -> Result<()> {
+        let throttle = LoginThrottleService::new(self.db.clone());
+        let attempt_keys = [AttemptKey::TwoFactor(user_id), AttemptKey::Ip(device.ip)];
+        throttle.check(&attempt_keys).await?;
+
+        if !self.verify_code(user_id, code).await? {
+            throttle.record_failure(&attempt_keys).await?;
+            return Err(anyhow::anyhow!("Invalid two-factor code"));
+        }
+        throttle.clear(&attempt_keys[0]).await?;
+
+        Ok(())
+    }
+
+    async fn get_totp_state(&self, user_id: i32) -> Result<(Option<String>, bool)> {
+        let query = "SELECT * FROM sp_get_totp_state($1)";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[&user_id];
+
+        match self.db.execute_query_one(query, params).await? {
+            Some(row) => Ok((row.get("totpsecret"), row.get("totpenabled"))),
+            None => Err(anyhow::anyhow!("User not found")),
+        }
+    }
+}
+
+fn build_totp(secret: &str, account_name: String) -> Result<TOTP> {
+    let issuer = std::env::var("TOTP_ISSUER").unwrap_or_else(|_| "Notes API".to_string());
+    let secret = Secret::Encoded(secret.to_string())
+        .to_bytes()
+        .map_err(|err| anyhow::anyhow!("Invalid TOTP secret: {}", err))?;
+
+    TOTP::new(Algorithm::SHA1, TOTP_DIGITS, 0, TOTP_STEP_SECONDS, secret, Some(issuer), account_name)
+        .map_err(|err| anyhow::anyhow!("Invalid TOTP configuration: {}", err))
+}
+
+/// Return the time step `code` belongs to, allowing one step of clock drift either way
+fn matching_step(secret: &str, code: &str) -> Result<Option<u64>> {
+    let code = code.trim();
+    if code.len() != TOTP_DIGITS || !code.bytes().all(|byte| byte.is_ascii_digit()) {
+        return Ok(None);
+    }
+
+    let totp = build_totp(secret, String::new())?;
+    let current_step = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() / TOTP_STEP_SECONDS;
+
+    let step = [current_step - 1, current_step, current_step + 1]
+        .into_iter()
+        .find(|step| totp.check(code, step * TOTP_STEP_SECONDS));
+
+    Ok(step)
+}
+
+/// Recovery codes look like `3f9a1-c07e2`
+fn generate_recovery_codes() -> Vec<String> {
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let bytes: [u8; 5] = rand::random();
+            let code: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+            format!("{}-{}", &code[..5], &code[5..])
+        })
+        .collect()
+}
+
+// Accept recovery codes regardless of case, dashes or surrounding whitespace
+fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
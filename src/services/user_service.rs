@@ -1,7 +1,28 @@
-use crate::models::users_model::UserResponse;
+use crate::models::auth_model::{AuthContext, UserCredentials};
+use crate::models::notebook_model::NotebookResponse;
+use crate::models::notes_model::NoteResponse;
+use crate::models::users_model::*;
+use crate::services::auth_service::AuthService;
+use crate::services::avatar_service::avatar_urls;
 use crate::services::database::DatabasePool;
+use crate::services::email_verification_service::EmailVerificationService;
+use crate::services::login_throttle_service::{AttemptKey, LoginThrottleService};
+use crate::services::mailer::{EmailMessage, Mailer};
+use crate::services::note_service::NoteService;
+use crate::services::notebook_service::NotebookService;
+use crate::utils::client_device::ClientDevice;
+use crate::utils::password::{hash_password, verify_password};
+use crate::utils::password_policy::check_password;
 use anyhow::Result;
 use chrono::{DateTime, Utc};
+use std::io::{Cursor, Write};
+use std::net::IpAddr;
+use std::sync::Arc;
+use tracing::error;
+use uuid::Uuid;
+use zip::write::SimpleFileOptions;
+
+const DEFAULT_ACCOUNT_DELETION_GRACE_PERIOD_SECONDS: i32 = 60 * 60 * 24 * 30; // 30 days
 
 pub struct UserService {
     db: DatabasePool,
@@ -23,16 +44,227 @@ impl UserService {
                 let id: i32 = row.get("id");
                 let username: String = row.get("username");
                 let email: String = row.get("email");
+                let email_verified: bool = row.get("emailverified");
                 let created_at: DateTime<Utc> = row.get("createdat");
+                let updated_at: DateTime<Utc> = row.get("updatedat");
+                let avatar_version: Option<Uuid> = row.get("avatarversion");
 
                 Ok(Some(UserResponse {
                     id,
                     username,
                     email,
+                    email_verified,
+                    display_name: row.get("displayname"),
+                    bio: row.get("bio"),
+                    locale: row.get("locale"),
+                    timezone: row.get("timezone"),
+                    avatar_urls: avatar_version.map(|version| avatar_urls(id, version)),
                     created_at,
+                    updated_at,
                 }))
             }
             None => Ok(None),
         }
     }
-}
\ No newline at end of file
+
+    /// Update the profile fields given in the request and return the updated user
+    pub async fn update_profile(&self, user_id: i32, request: UpdateProfileRequest) -> Result<UserResponse> {
+        let query = "SELECT sp_update_user($1, $2, $3, $4, $5) as updated";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[
+            &user_id,
+            &request.display_name.as_deref().map(str::trim),
+            &request.bio.as_deref().map(str::trim),
+            &request.locale,
+            &request.timezone,
+        ];
+
+        let row = self.db.execute_query_one(query, params).await?;
+        let updated = row.is_some_and(|row| row.get::<_, i32>("updated") == 1);
+
+        if !updated {
+            return Err(anyhow::anyhow!("User not found"));
+        }
+
+        self.get_user_by_id(user_id).await?
+            .ok_or_else(|| anyhow::anyhow!("User not found"))
+    }
+
+    /// Replace the password of the current user, sign out their other sessions and revoke their
+    /// personal access tokens
+    pub async fn change_password(&self, context: &AuthContext, request: ChangePasswordRequest, device: &ClientDevice) -> Result<()> {
+        let credentials = self.check_current_password(context.user_id, &request.current_password, device.ip).await?;
+        check_password(&request.new_password, &credentials.username, &credentials.email)?;
+
+        let new_hash = hash_password(&request.new_password)?;
+
+        let query = "SELECT sp_change_password($1, $2, $3, $4, $5, $6) as changed";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[
+            &context.user_id,
+            &credentials.password_hash,
+            &new_hash,
+            &context.session_id,
+            &device.ip,
+            &device.user_agent,
+        ];
+
+        // The hash only changes if nobody changed the password since it was verified
+        let row = self.db.execute_query_one(query, params).await?;
+        let changed = row.is_some_and(|row| row.get::<_, i32>("changed") == 1);
+
+        if !changed {
+            return Err(anyhow::anyhow!("The password was changed concurrently; try again"));
+        }
+
+        Ok(())
+    }
+
+    /// Start moving the current user to a new email address; it takes effect once the
+    /// verification link sent to the new address is opened
+    pub async fn change_email(
+        &self,
+        context: &AuthContext,
+        request: ChangeEmailRequest,
+        device: &ClientDevice,
+        mailer: Arc<dyn Mailer>,
+    ) -> Result<()> {
+        let credentials = self.check_current_password(context.user_id, &request.current_password, device.ip).await?;
+
+        EmailVerificationService::new(self.db.clone())
+            .request_email_change(
+                credentials.user_id,
+                &credentials.username,
+                &credentials.email,
+                &request.new_email,
+                device,
+                mailer,
+            )
+            .await
+    }
+
+    /// Zip archive of everything the user stored: `profile.json`, `notebooks.json`, `notes.json`
+    /// and every note as Markdown under `notes/`
+    pub async fn export_data(&self, user_id: i32) -> Result<Vec<u8>> {
+        let user = self.get_user_by_id(user_id).await?
+            .ok_or_else(|| anyhow::anyhow!("User not found"))?;
+        let notebooks = NotebookService::new(self.db.clone()).get_notebooks(user_id).await?;
+        let notes = NoteService::new(self.db.clone()).get_all_user_notes(user_id).await?;
+
+        // Compressing is CPU bound; keep it off the async workers
+        tokio::task::spawn_blocking(move || build_export_archive(&user, &notebooks, &notes)).await?
+    }
+
+    /// Sign the current user out everywhere and schedule their account for deletion once the
+    /// grace period (`ACCOUNT_DELETION_GRACE_PERIOD_SECONDS`) ends
+    pub async fn schedule_deletion(
+        &self,
+        context: &AuthContext,
+        request: DeleteAccountRequest,
+        device: &ClientDevice,
+        mailer: Arc<dyn Mailer>,
+    ) -> Result<AccountDeletionResponse> {
+        let credentials = self.check_current_password(context.user_id, &request.password, device.ip).await?;
+        // A value that does not fit a positive INT would otherwise purge the account right away
+        let grace_seconds = std::env::var("ACCOUNT_DELETION_GRACE_PERIOD_SECONDS")
+            .ok()
+            .and_then(|value| value.parse::<i32>().ok())
+            .filter(|seconds| *seconds > 0)
+            .unwrap_or(DEFAULT_ACCOUNT_DELETION_GRACE_PERIOD_SECONDS);
+
+        let query = "SELECT sp_schedule_user_deletion($1, $2, $3, $4) as scheduled_at";
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[
+            &context.user_id,
+            &grace_seconds,
+            &device.ip,
+            &device.user_agent,
+        ];
+
+        let row = self.db.execute_query_one(query, params).await?;
+        let deletion_scheduled_at: DateTime<Utc> = row
+            .and_then(|row| row.get("scheduled_at"))
+            .ok_or_else(|| anyhow::anyhow!("User not found"))?;
+
+        let message = EmailMessage {
+            to: credentials.email,
+            subject: "Your account will be deleted".to_string(),
+            body: format!(
+                "Hi {},\n\nYour account and all of your notes will be deleted on {}. Until then you can keep your account by logging in again.\n",
+                credentials.username,
+                deletion_scheduled_at.format("%Y-%m-%d %H:%M UTC")
+            ),
+        };
+        tokio::spawn(async move {
+            if let Err(err) = mailer.send(message).await {
+                error!("Failed to send account deletion email: {}", err);
+            }
+        });
+
+        Ok(AccountDeletionResponse { deletion_scheduled_at })
+    }
+
+    // A stolen access token must not be enough to take over the account, so account changes
+    // need the password again; wrong guesses count towards the login lockout
+    async fn check_current_password(&self, user_id: i32, password: &str, ip: IpAddr) -> Result<UserCredentials> {
+        let credentials = AuthService::new(self.db.clone())
+            .find_credentials_by_id(user_id).await?
+            .ok_or_else(|| anyhow::anyhow!("User not found"))?;
+
+        let throttle = LoginThrottleService::new(self.db.clone());
+        let attempt_keys = [AttemptKey::Account(credentials.email.clone()), AttemptKey::Ip(ip)];
+        throttle.check(&attempt_keys).await?;
+
+        if !verify_password(password, &credentials.password_hash)? {
+            throttle.record_failure(&attempt_keys).await?;
+            return Err(anyhow::anyhow!("Current password is incorrect"));
+        }
+        throttle.clear(&attempt_keys[0]).await?;
+
+        Ok(credentials)
+    }
+}
+
+fn build_export_archive(user: &UserResponse, notebooks: &[NotebookResponse], notes: &[NoteResponse]) -> Result<Vec<u8>> {
+    let mut archive = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default();
+
+    archive.start_file("profile.json", options)?;
+    archive.write_all(&serde_json::to_vec_pretty(user)?)?;
+
+    archive.start_file("notebooks.json", options)?;
+    archive.write_all(&serde_json::to_vec_pretty(notebooks)?)?;
+
+    archive.start_file("notes.json", options)?;
+    archive.write_all(&serde_json::to_vec_pretty(notes)?)?;
+
+    for note in notes {
+        archive.start_file(format!("notes/{}-{}.md", note.id, slugify(&note.title)), options)?;
+        write!(
+            archive,
+            "# {}\n\n_Created {}, last updated {}_\n\n",
+            note.title,
+            note.created_at.to_rfc3339(),
+            note.updated_at.to_rfc3339()
+        )?;
+        if !note.tags.is_empty() {
+            write!(archive, "Tags: {}\n\n", note.tags.join(", "))?;
+        }
+        writeln!(archive, "{}", note.content)?;
+    }
+
+    Ok(archive.finish()?.into_inner())
+}
+
+// File name friendly version of a note title, e.g. "Groceries & Errands" becomes "groceries-errands"
+fn slugify(title: &str) -> String {
+    let slug = title
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join("-");
+
+    match slug.char_indices().nth(50) {
+        Some((index, _)) => slug[..index].trim_end_matches('-').to_string(),
+        None if slug.is_empty() => "untitled".to_string(),
+        None => slug,
+    }
+}
@@ -0,0 +1,31 @@
+use crate::services::database::DatabasePool;
+use crate::services::mailer::Mailer;
+use crate::services::storage::Storage;
+use axum::extract::FromRef;
+use std::sync::Arc;
+
+/// Shared application state; handlers extract the parts they need through `FromRef`
+#[derive(Clone)]
+pub struct AppState {
+    pub db_pool: DatabasePool,
+    pub mailer: Arc<dyn Mailer>,
+    pub storage: Arc<dyn Storage>,
+}
+
+impl FromRef<AppState> for DatabasePool {
+    fn from_ref(state: &AppState) -> Self {
+        state.db_pool.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<dyn Mailer> {
+    fn from_ref(state: &AppState) -> Self {
+        state.mailer.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<dyn Storage> {
+    fn from_ref(state: &AppState) -> Self {
+        state.storage.clone()
+    }
+}
\ No newline at end of file
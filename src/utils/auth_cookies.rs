@@ -0,0 +1,142 @@
+use crate::models::auth_model::{AuthResponse, LoginResponse};
+use crate::utils::jwt::{access_token_ttl_seconds, refresh_token_ttl_seconds};
+use crate::utils::token::generate_token;
+use axum::{
+    extract::FromRequestParts,
+    http::{header, request::Parts, HeaderMap, HeaderValue, Method},
+    response::{IntoResponse, Json, Response},
+};
+use std::convert::Infallible;
+
+pub const ACCESS_TOKEN_COOKIE: &str = "access_token";
+pub const REFRESH_TOKEN_COOKIE: &str = "refresh_token";
+/// Readable by the frontend, which echoes it in the `X-CSRF-Token` header
+pub const CSRF_COOKIE: &str = "csrf_token";
+pub const CSRF_HEADER: &str = "x-csrf-token";
+
+const AUTH_MODE_HEADER: &str = "x-auth-mode";
+// Refresh tokens are only sent to the endpoints that redeem or revoke them
+const REFRESH_TOKEN_COOKIE_PATH: &str = "/api/v1/auth";
+
+/// How a client receives its tokens: in the JSON body (the default), or, when it sends
+/// `X-Auth-Mode: cookie`, as HttpOnly cookies the frontend's scripts cannot read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenDelivery {
+    Body,
+    Cookies,
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for TokenDelivery {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let wants_cookies = parts
+            .headers
+            .get(AUTH_MODE_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.trim().eq_ignore_ascii_case("cookie"));
+
+        Ok(if wants_cookies { TokenDelivery::Cookies } else { TokenDelivery::Body })
+    }
+}
+
+impl TokenDelivery {
+    /// Respond with freshly issued tokens. In cookie mode the access token, refresh token and a
+    /// new CSRF token are set as cookies and the tokens are left out of the body.
+    pub fn auth_response(self, mut response: AuthResponse) -> Response {
+        if self == TokenDelivery::Body {
+            return Json(response).into_response();
+        }
+
+        let cookies = [
+            cookie(ACCESS_TOKEN_COOKIE, &response.token, "/", access_token_ttl_seconds(), true),
+            cookie(REFRESH_TOKEN_COOKIE, &response.refresh_token, REFRESH_TOKEN_COOKIE_PATH, refresh_token_ttl_seconds(), true),
+            cookie(CSRF_COOKIE, &generate_token(), "/", refresh_token_ttl_seconds(), false),
+        ];
+        response.token.clear();
+        response.refresh_token.clear();
+
+        let mut http_response = Json(response).into_response();
+        for value in cookies {
+            http_response.headers_mut().append(header::SET_COOKIE, value);
+        }
+        http_response
+    }
+
+    /// Like `auth_response`; a two-factor challenge carries no tokens and is sent as is
+    pub fn login_response(self, response: LoginResponse) -> Response {
+        match response {
+            LoginResponse::Authenticated(auth) => self.auth_response(auth),
+            challenge @ LoginResponse::TwoFactorRequired(_) => Json(challenge).into_response(),
+        }
+    }
+}
+
+/// Expire every authentication cookie, e.g. on logout
+pub fn clear_auth_cookies(headers: &mut HeaderMap) {
+    for (name, path, http_only) in [
+        (ACCESS_TOKEN_COOKIE, "/", true),
+        (REFRESH_TOKEN_COOKIE, REFRESH_TOKEN_COOKIE_PATH, true),
+        (CSRF_COOKIE, "/", false),
+    ] {
+        headers.append(header::SET_COOKIE, cookie(name, "", path, 0, http_only));
+    }
+}
+
+/// Value of the named cookie sent with the request
+pub fn cookie_value<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(cookie_name, _)| *cookie_name == name)
+        .map(|(_, value)| value)
+        .filter(|value| !value.is_empty())
+}
+
+/// Double-submit check: a cross-site page can make the browser send our cookies, but it cannot
+/// read the CSRF cookie to copy it into the `X-CSRF-Token` header
+pub fn csrf_token_valid(headers: &HeaderMap) -> bool {
+    let cookie = cookie_value(headers, CSRF_COOKIE);
+    let header = headers.get(CSRF_HEADER).and_then(|value| value.to_str().ok());
+
+    match (cookie, header) {
+        (Some(cookie), Some(header)) => constant_time_eq(cookie.as_bytes(), header.as_bytes()),
+        _ => false,
+    }
+}
+
+/// Methods that must not change state, and so do not need a CSRF token
+pub fn is_safe_method(method: &Method) -> bool {
+    matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+}
+
+// Cookie attributes come from AUTH_COOKIE_SECURE (default true), AUTH_COOKIE_SAME_SITE
+// (Strict, Lax or None; default Lax) and AUTH_COOKIE_DOMAIN (default: the API host only)
+fn cookie(name: &str, value: &str, path: &str, max_age: u64, http_only: bool) -> HeaderValue {
+    let secure = std::env::var("AUTH_COOKIE_SECURE").map_or(true, |value| value != "false");
+    let same_site = std::env::var("AUTH_COOKIE_SAME_SITE")
+        .ok()
+        .filter(|value| matches!(value.as_str(), "Strict" | "Lax" | "None"))
+        .unwrap_or_else(|| "Lax".to_string());
+
+    let mut cookie = format!("{}={}; Path={}; Max-Age={}; SameSite={}", name, value, path, max_age, same_site);
+    if let Ok(domain) = std::env::var("AUTH_COOKIE_DOMAIN") {
+        cookie.push_str(&format!("; Domain={}", domain));
+    }
+    if secure {
+        cookie.push_str("; Secure");
+    }
+    if http_only {
+        cookie.push_str("; HttpOnly");
+    }
+
+    // Tokens are URL-safe base64 or JWTs, so the value is always a valid header value
+    HeaderValue::from_str(&cookie).expect("Invalid cookie")
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
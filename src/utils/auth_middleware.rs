@@ -1,31 +1,244 @@
-use crate::utils::jwt::extract_user_id_from_token;
+use crate::models::auth_model::{ApiError, AuthContext};
+use crate::services::auth_service::AuthService;
+use crate::services::database::DatabasePool;
+use crate::services::personal_access_token_service::{PersonalAccessTokenService, PERSONAL_ACCESS_TOKEN_PREFIX};
+use crate::utils::auth_cookies::{cookie_value, csrf_token_valid, is_safe_method, ACCESS_TOKEN_COOKIE};
+use crate::utils::jwt::validate_jwt;
+use anyhow::Result;
 use axum::{
-    extract::Request,
-    http::{HeaderMap, StatusCode},
+    extract::{Request, State},
+    http::{HeaderMap, Method, StatusCode},
     middleware::Next,
-    response::Response,
+    response::{Json, Response},
 };
+use std::sync::OnceLock;
+use tracing::{error, warn};
 
+static UNVERIFIED_ACCOUNT_POLICY: OnceLock<UnverifiedAccountPolicy> = OnceLock::new();
+
+/// What accounts whose email address is not verified yet may do
+#[derive(Debug, Clone, Copy)]
+enum UnverifiedAccountPolicy {
+    Allow,
+    ReadOnly,
+    Block,
+}
+
+/// Load `UNVERIFIED_ACCOUNT_POLICY`: `allow` (the default), `read_only` or `block`.
+/// Must be called once at startup; any other value is an error.
+pub fn load_unverified_account_policy() -> Result<()> {
+    let policy = match std::env::var("UNVERIFIED_ACCOUNT_POLICY").as_deref() {
+        Err(_) | Ok("allow") => UnverifiedAccountPolicy::Allow,
+        Ok("read_only") => UnverifiedAccountPolicy::ReadOnly,
+        Ok("block") => UnverifiedAccountPolicy::Block,
+        Ok(other) => anyhow::bail!("Unknown UNVERIFIED_ACCOUNT_POLICY: {} (expected allow, read_only or block)", other),
+    };
+
+    UNVERIFIED_ACCOUNT_POLICY
+        .set(policy)
+        .map_err(|_| anyhow::anyhow!("Unverified account policy is already loaded"))
+}
+
+/// Authenticate the bearer token, which is either a JWT access token or a personal access token.
+/// Browser clients in cookie mode send the access token as a cookie instead; their
+/// state-changing requests must also carry the CSRF token.
 pub async fn auth_middleware(
+    State(db_pool): State<DatabasePool>,
     headers: HeaderMap,
     mut request: Request,
     next: Next,
 ) -> Result<Response, StatusCode> {
-    let auth_header = headers
+    let bearer_token = headers
         .get("Authorization")
         .and_then(|header| header.to_str().ok())
         .and_then(|header| header.strip_prefix("Bearer "));
 
-    match auth_header {
-        Some(token) => {
-            match extract_user_id_from_token(token) {
-                Ok(user_id) => {
-                    request.extensions_mut().insert(user_id);
-                    Ok(next.run(request).await)
-                }
-                Err(_) => Err(StatusCode::UNAUTHORIZED),
+    let token = match bearer_token {
+        Some(token) => token,
+        None => {
+            let token = cookie_value(&headers, ACCESS_TOKEN_COOKIE).ok_or(StatusCode::UNAUTHORIZED)?;
+            // Browsers attach cookies to requests other sites trigger; headers they cannot forge
+            if !is_safe_method(request.method()) && !csrf_token_valid(&headers) {
+                warn!("Rejected {} {} with a missing or mismatched CSRF token", request.method(), request.uri().path());
+                return Err(StatusCode::FORBIDDEN);
             }
+            token
+        }
+    };
+
+    let context = if token.starts_with(PERSONAL_ACCESS_TOKEN_PREFIX) {
+        authenticate_personal_access_token(db_pool, token).await?
+    } else {
+        authenticate_access_token(db_pool, token).await?
+    };
+
+    request.extensions_mut().insert(context.user_id);
+    request.extensions_mut().insert(context);
+    Ok(next.run(request).await)
+}
+
+async fn authenticate_access_token(db_pool: DatabasePool, token: &str) -> Result<AuthContext, StatusCode> {
+    let claims = validate_jwt(token).map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let user_id = claims.sub.parse::<i32>().map_err(|_| StatusCode::UNAUTHORIZED)?;
+
+    // Signature and expiry are fine; make sure the token was not revoked server-side
+    let auth_service = AuthService::new(db_pool);
+
+    match auth_service.get_access_token_status(&claims, user_id).await {
+        Ok((status, email_verified)) if status == "valid" => Ok(AuthContext {
+            user_id,
+            token_id: claims.jti,
+            expires_at: claims.exp,
+            email_verified,
+            scopes: None,
+            session_id: claims.sid,
+            permissions: claims.permissions,
+        }),
+        Ok((status, _)) => {
+            warn!("Rejected {} access token for user_id: {}", status, user_id);
+            Err(rejection_status(&status))
+        }
+        Err(err) => {
+            error!("Failed to check access token status: {}", err);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn authenticate_personal_access_token(db_pool: DatabasePool, token: &str) -> Result<AuthContext, StatusCode> {
+    let token_service = PersonalAccessTokenService::new(db_pool);
+
+    match token_service.authenticate(token).await {
+        Ok((_, Some(context))) => Ok(context),
+        Ok((status, None)) => {
+            warn!("Rejected {} personal access token", status);
+            Err(rejection_status(&status))
+        }
+        Err(err) => {
+            error!("Failed to check personal access token: {}", err);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+// Suspended accounts are authenticated but not allowed in, so clients do not just refresh and retry
+fn rejection_status(status: &str) -> StatusCode {
+    if status == "suspended" {
+        StatusCode::FORBIDDEN
+    } else {
+        StatusCode::UNAUTHORIZED
+    }
+}
+
+/// Reject personal access tokens that were not granted `scope`; sessions always pass.
+/// Layer it on individual routes with `from_fn_with_state("notes:read", require_scope)`.
+/// Must run after `auth_middleware`.
+pub async fn require_scope(
+    State(scope): State<&'static str>,
+    request: Request,
+    next: Next,
+) -> Result<Response, (StatusCode, Json<ApiError>)> {
+    let allowed = request
+        .extensions()
+        .get::<AuthContext>()
+        .is_some_and(|context| context.has_scope(scope));
+
+    if !allowed {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(ApiError {
+                error: "Insufficient Scope".to_string(),
+                message: format!("This endpoint requires the {} scope", scope),
+            }),
+        ));
+    }
+
+    Ok(next.run(request).await)
+}
+
+/// Reject callers whose roles do not grant `permission`. Layer it on individual routes with
+/// `from_fn_with_state("users:admin", require_permission)`. Personal access tokens carry no
+/// permissions, so they never pass. Must run after `auth_middleware`.
+pub async fn require_permission(
+    State(permission): State<&'static str>,
+    request: Request,
+    next: Next,
+) -> Result<Response, (StatusCode, Json<ApiError>)> {
+    let allowed = request
+        .extensions()
+        .get::<AuthContext>()
+        .is_some_and(|context| context.has_permission(permission));
+
+    if !allowed {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(ApiError {
+                error: "Insufficient Permissions".to_string(),
+                message: format!("This endpoint requires the {} permission", permission),
+            }),
+        ));
+    }
+
+    Ok(next.run(request).await)
+}
+
+/// Reject personal access tokens on endpoints that manage the account itself (logout,
+/// two-factor settings, token management). Must run after `auth_middleware`.
+pub async fn require_session(
+    request: Request,
+    next: Next,
+) -> Result<Response, (StatusCode, Json<ApiError>)> {
+    let is_session = request
+        .extensions()
+        .get::<AuthContext>()
+        .is_some_and(AuthContext::is_session);
+
+    if !is_session {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(ApiError {
+                error: "Session Required".to_string(),
+                message: "This endpoint cannot be used with a personal access token".to_string(),
+            }),
+        ));
+    }
+
+    Ok(next.run(request).await)
+}
+
+/// Apply `UNVERIFIED_ACCOUNT_POLICY` to accounts whose email address is not verified yet:
+/// `allow` (the default) lets them through, `read_only` only allows safe methods and
+/// `block` rejects every request. Must run after `auth_middleware`, and the policy must have
+/// been loaded with `load_unverified_account_policy`.
+pub async fn verified_email_middleware(
+    request: Request,
+    next: Next,
+) -> Result<Response, (StatusCode, Json<ApiError>)> {
+    let email_verified = request
+        .extensions()
+        .get::<AuthContext>()
+        .is_some_and(|context| context.email_verified);
+
+    if !email_verified {
+        let policy = UNVERIFIED_ACCOUNT_POLICY
+            .get()
+            .expect("Unverified account policy is not loaded; call auth_middleware::load_unverified_account_policy at startup");
+        let allowed = match policy {
+            UnverifiedAccountPolicy::Allow => true,
+            UnverifiedAccountPolicy::ReadOnly => matches!(*request.method(), Method::GET | Method::HEAD | Method::OPTIONS),
+            UnverifiedAccountPolicy::Block => false,
+        };
+
+        if !allowed {
+            return Err((
+                StatusCode::FORBIDDEN,
+                Json(ApiError {
+                    error: "Email Not Verified".to_string(),
+                    message: "Verify your email address to use this endpoint".to_string(),
+                }),
+            ));
         }
-        None => Err(StatusCode::UNAUTHORIZED),
     }
-}
\ No newline at end of file
+
+    Ok(next.run(request).await)
+}
@@ -0,0 +1,88 @@
+use crate::utils::client_ip::ClientIp;
+use axum::{
+    extract::FromRequestParts,
+    http::{header, request::Parts, StatusCode},
+};
+use std::net::IpAddr;
+
+const MAX_DEVICE_NAME_LENGTH: usize = 100;
+const MAX_USER_AGENT_LENGTH: usize = 500;
+
+/// The device a login comes from, recorded on the session it starts.
+///
+/// The name is taken from the `X-Device-Name` header when the client sends one (e.g.
+/// "Alice's phone"), and otherwise guessed from the `User-Agent`, e.g. "Firefox on Windows".
+#[derive(Debug, Clone)]
+pub struct ClientDevice {
+    pub name: String,
+    pub user_agent: Option<String>,
+    pub ip: IpAddr,
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for ClientDevice {
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let ClientIp(ip) = ClientIp::from_request_parts(parts, state).await?;
+
+        let header_value = |name| {
+            parts
+                .headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+        };
+
+        let user_agent = header_value(header::USER_AGENT).map(|agent| truncate(agent, MAX_USER_AGENT_LENGTH));
+        let name = match header_value(header::HeaderName::from_static("x-device-name")) {
+            Some(name) => truncate(name, MAX_DEVICE_NAME_LENGTH),
+            None => describe_user_agent(user_agent.as_deref()),
+        };
+
+        Ok(ClientDevice { name, user_agent, ip })
+    }
+}
+
+/// Human readable device description from a `User-Agent` header
+fn describe_user_agent(user_agent: Option<&str>) -> String {
+    let Some(user_agent) = user_agent else {
+        return "Unknown device".to_string();
+    };
+
+    // Order matters: Edge and Opera also claim to be Chrome, Chrome claims to be Safari
+    let browser = [
+        ("Edg/", "Edge"),
+        ("OPR/", "Opera"),
+        ("Firefox/", "Firefox"),
+        ("Chrome/", "Chrome"),
+        ("Safari/", "Safari"),
+        ("curl/", "curl"),
+    ]
+    .into_iter()
+    .find(|(marker, _)| user_agent.contains(marker))
+    .map(|(_, name)| name);
+
+    // Android and iOS user agents also mention Linux and Mac OS X
+    let platform = [
+        ("Android", "Android"),
+        ("iPhone", "iOS"),
+        ("iPad", "iPadOS"),
+        ("Windows", "Windows"),
+        ("Mac OS X", "macOS"),
+        ("Linux", "Linux"),
+    ]
+    .into_iter()
+    .find(|(marker, _)| user_agent.contains(marker))
+    .map(|(_, name)| name);
+
+    match (browser, platform) {
+        (Some(browser), Some(platform)) => format!("{} on {}", browser, platform),
+        (Some(name), None) | (None, Some(name)) => name.to_string(),
+        (None, None) => truncate(user_agent, MAX_DEVICE_NAME_LENGTH),
+    }
+}
+
+fn truncate(value: &str, max_chars: usize) -> String {
+    value.chars().take(max_chars).collect()
+}
@@ -0,0 +1,41 @@
+use axum::{
+    extract::{ConnectInfo, FromRequestParts},
+    http::{request::Parts, StatusCode},
+};
+use std::net::{IpAddr, SocketAddr};
+
+/// Address of the client that sent the request.
+///
+/// This is the peer address of the connection unless `TRUST_PROXY_HEADERS` is `true`, in
+/// which case the last entry of `X-Forwarded-For` (the one appended by our own reverse
+/// proxy) wins. Only enable that behind a proxy, since clients can set the header freely.
+#[derive(Debug, Clone, Copy)]
+pub struct ClientIp(pub IpAddr);
+
+impl<S: Send + Sync> FromRequestParts<S> for ClientIp {
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let trust_proxy_headers = std::env::var("TRUST_PROXY_HEADERS").is_ok_and(|value| value == "true");
+
+        if trust_proxy_headers {
+            let forwarded = parts
+                .headers
+                .get("X-Forwarded-For")
+                .and_then(|header| header.to_str().ok())
+                .and_then(|header| header.rsplit(',').next())
+                .and_then(|address| address.trim().parse::<IpAddr>().ok());
+
+            if let Some(address) = forwarded {
+                return Ok(ClientIp(address));
+            }
+        }
+
+        // Requires the server to be started with `into_make_service_with_connect_info`
+        parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(address)| ClientIp(address.ip()))
+            .ok_or(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+}
@@ -1,41 +1,284 @@
-use crate::models::auth_model::Claims;
-use anyhow::Result;
-use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use crate::models::auth_model::{Claims, TwoFactorChallengeClaims, UserAccess};
+use anyhow::{Context, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::jwk::{
+    AlgorithmParameters, CommonParameters, EllipticCurve, Jwk, KeyAlgorithm, OctetKeyPairParameters,
+    OctetKeyPairType, PublicKeyUse, RSAKeyParameters, RSAKeyType,
+};
+use jsonwebtoken::{decode, decode_header, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use simple_asn1::ASN1Block;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::OnceLock;
 use std::time::{SystemTime, UNIX_EPOCH};
 
-const JWT_SECRET: &str = "your_secret_key_here"; // In production, use environment variable
+const DEFAULT_ACCESS_TOKEN_TTL_SECONDS: u64 = 60 * 15; // 15 minutes
+const DEFAULT_REFRESH_TOKEN_TTL_SECONDS: u64 = 60 * 60 * 24 * 30; // 30 days
+pub const TWO_FACTOR_CHALLENGE_TTL_SECONDS: u64 = 60 * 5; // 5 minutes
 
-pub fn create_jwt(user_id: i32) -> Result<String> {
+// Audience of two-factor challenge tokens. Access token validation sets no audience,
+// so jsonwebtoken rejects any token that carries one and a challenge cannot be used as an access token.
+const TWO_FACTOR_CHALLENGE_AUDIENCE: &str = "2fa";
+
+// Key id under which the HS256 secret is registered; HS256 tokens carry no `kid`
+const SHARED_SECRET_KEY_ID: &str = "";
+
+static KEYS: OnceLock<JwtKeys> = OnceLock::new();
+
+struct JwtKeys {
+    signing_header: Header,
+    signing_key: EncodingKey,
+    verification_keys: HashMap<String, (Algorithm, DecodingKey)>,
+    public_keys: Vec<Jwk>,
+}
+
+/// Load the token signing and verification keys. Must be called once at startup.
+///
+/// With `JWT_KEYS_DIR` set, every `<kid>.pub.pem` file in that directory (RSA or Ed25519)
+/// is an active verification key and is published in the JWKS. Tokens are signed with
+/// `<kid>.pem`, where the kid is `JWT_SIGNING_KEY_ID` or, if unset, the last key id in
+/// sort order. Without `JWT_KEYS_DIR`, tokens are signed with HS256 using `JWT_SECRET`.
+pub fn load_keys() -> Result<()> {
+    let keys = match std::env::var("JWT_KEYS_DIR") {
+        Ok(dir) => load_key_directory(Path::new(&dir))?,
+        Err(_) => {
+            let secret = std::env::var("JWT_SECRET")
+                .context("Either JWT_KEYS_DIR or JWT_SECRET must be set")?;
+            let verification_keys = HashMap::from([(
+                SHARED_SECRET_KEY_ID.to_string(),
+                (Algorithm::HS256, DecodingKey::from_secret(secret.as_ref())),
+            )]);
+
+            JwtKeys {
+                signing_header: Header::new(Algorithm::HS256),
+                signing_key: EncodingKey::from_secret(secret.as_ref()),
+                verification_keys,
+                public_keys: Vec::new(),
+            }
+        }
+    };
+
+    KEYS.set(keys).map_err(|_| anyhow::anyhow!("JWT keys are already loaded"))
+}
+
+fn keys() -> &'static JwtKeys {
+    KEYS.get().expect("JWT keys are not loaded; call jwt::load_keys at startup")
+}
+
+/// Public verification keys, as published at `/.well-known/jwks.json`
+pub fn public_keys() -> &'static [Jwk] {
+    &keys().public_keys
+}
+
+/// Lifetime of an access token, configurable through `JWT_ACCESS_TOKEN_TTL_SECONDS`
+pub fn access_token_ttl_seconds() -> u64 {
+    std::env::var("JWT_ACCESS_TOKEN_TTL_SECONDS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_ACCESS_TOKEN_TTL_SECONDS)
+}
+
+/// Lifetime of a refresh token, configurable through `JWT_REFRESH_TOKEN_TTL_SECONDS`
+pub fn refresh_token_ttl_seconds() -> u64 {
+    std::env::var("JWT_REFRESH_TOKEN_TTL_SECONDS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_REFRESH_TOKEN_TTL_SECONDS)
+}
+
+pub fn create_jwt(user_id: i32, token_version: i32, session_id: uuid::Uuid, access: &UserAccess) -> Result<String> {
     let expiration = SystemTime::now()
         .duration_since(UNIX_EPOCH)?
-        .as_secs() + 60 * 60 * 24; // 24 hours
+        .as_secs() + access_token_ttl_seconds();
 
     let claims = Claims {
         sub: user_id.to_string(),
         exp: expiration as usize,
+        jti: uuid::Uuid::new_v4().to_string(),
+        ver: token_version,
+        sid: Some(session_id),
+        roles: access.roles.clone(),
+        permissions: access.permissions.clone(),
     };
 
-    let token = encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(JWT_SECRET.as_ref()),
-    )?;
+    let keys = keys();
+    let token = encode(&keys.signing_header, &claims, &keys.signing_key)?;
 
     Ok(token)
 }
 
 pub fn validate_jwt(token: &str) -> Result<Claims> {
-    let token_data = decode::<Claims>(
-        token,
-        &DecodingKey::from_secret(JWT_SECRET.as_ref()),
-        &Validation::new(Algorithm::HS256),
-    )?;
+    let (algorithm, key) = verification_key(token)?;
+    let token_data = decode::<Claims>(token, key, &Validation::new(algorithm))?;
 
     Ok(token_data.claims)
 }
 
-pub fn extract_user_id_from_token(token: &str) -> Result<i32> {
-    let claims = validate_jwt(token)?;
-    let user_id = claims.sub.parse::<i32>()?;
-    Ok(user_id)
+/// Create the short-lived token handed out by login when the user still has to pass two-factor authentication
+pub fn create_two_factor_challenge(user_id: i32) -> Result<String> {
+    let expiration = SystemTime::now()
+        .duration_since(UNIX_EPOCH)?
+        .as_secs() + TWO_FACTOR_CHALLENGE_TTL_SECONDS;
+
+    let claims = TwoFactorChallengeClaims {
+        sub: user_id.to_string(),
+        exp: expiration as usize,
+        aud: TWO_FACTOR_CHALLENGE_AUDIENCE.to_string(),
+    };
+
+    let keys = keys();
+    let token = encode(&keys.signing_header, &claims, &keys.signing_key)?;
+
+    Ok(token)
+}
+
+/// Validate a two-factor challenge token and return the user ID it was issued for
+pub fn validate_two_factor_challenge(token: &str) -> Result<i32> {
+    let (algorithm, key) = verification_key(token)?;
+    let mut validation = Validation::new(algorithm);
+    validation.set_audience(&[TWO_FACTOR_CHALLENGE_AUDIENCE]);
+
+    let token_data = decode::<TwoFactorChallengeClaims>(token, key, &validation)?;
+
+    Ok(token_data.claims.sub.parse()?)
+}
+
+// Pick the verification key by `kid`; the key also pins the accepted algorithm
+fn verification_key(token: &str) -> Result<(Algorithm, &'static DecodingKey)> {
+    let header = decode_header(token)?;
+    let kid = header.kid.unwrap_or_else(|| SHARED_SECRET_KEY_ID.to_string());
+    let (algorithm, key) = keys()
+        .verification_keys
+        .get(&kid)
+        .ok_or_else(|| anyhow::anyhow!("Unknown signing key: {}", kid))?;
+
+    Ok((*algorithm, key))
+}
+
+fn load_key_directory(dir: &Path) -> Result<JwtKeys> {
+    let mut key_ids: Vec<String> = std::fs::read_dir(dir)
+        .with_context(|| format!("Failed to read JWT key directory {}", dir.display()))?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let file_name = entry.file_name().into_string().ok()?;
+            file_name.strip_suffix(".pub.pem").map(str::to_string)
+        })
+        .collect();
+    key_ids.sort();
+
+    let mut verification_keys = HashMap::new();
+    let mut public_keys = Vec::new();
+
+    for kid in &key_ids {
+        let pem = std::fs::read(dir.join(format!("{}.pub.pem", kid)))?;
+        let (algorithm, jwk) = public_key_to_jwk(kid, &pem)
+            .with_context(|| format!("Invalid JWT public key: {}", kid))?;
+
+        verification_keys.insert(kid.clone(), (algorithm, DecodingKey::from_jwk(&jwk)?));
+        public_keys.push(jwk);
+    }
+
+    let signing_kid = match std::env::var("JWT_SIGNING_KEY_ID") {
+        Ok(kid) => kid,
+        Err(_) => key_ids
+            .iter()
+            .rev()
+            .find(|kid| dir.join(format!("{}.pem", kid)).exists())
+            .cloned()
+            .with_context(|| format!("No JWT signing key found in {}", dir.display()))?,
+    };
+    let (algorithm, _) = verification_keys
+        .get(&signing_kid)
+        .with_context(|| format!("JWT signing key {} has no public key in {}", signing_kid, dir.display()))?;
+
+    let private_pem = std::fs::read(dir.join(format!("{}.pem", signing_kid)))
+        .with_context(|| format!("Failed to read JWT signing key {}", signing_kid))?;
+    let signing_key = match algorithm {
+        Algorithm::EdDSA => EncodingKey::from_ed_pem(&private_pem)?,
+        _ => EncodingKey::from_rsa_pem(&private_pem)?,
+    };
+
+    let mut signing_header = Header::new(*algorithm);
+    signing_header.kid = Some(signing_kid);
+
+    Ok(JwtKeys {
+        signing_header,
+        signing_key,
+        verification_keys,
+        public_keys,
+    })
+}
+
+/// Convert a PEM encoded RSA or Ed25519 public key into a JWK
+fn public_key_to_jwk(kid: &str, pem: &[u8]) -> Result<(Algorithm, Jwk)> {
+    let pem = pem::parse(pem)?;
+    let blocks = simple_asn1::from_der(pem.contents())?;
+
+    let (algorithm, parameters) = match (pem.tag(), blocks.as_slice()) {
+        // PKCS#1 RSAPublicKey
+        ("RSA PUBLIC KEY", [rsa_key]) => (Algorithm::RS256, rsa_key_parameters(rsa_key)?),
+        // SubjectPublicKeyInfo ::= SEQUENCE { algorithm AlgorithmIdentifier, subjectPublicKey BIT STRING }
+        ("PUBLIC KEY", [ASN1Block::Sequence(_, info)]) => match info.as_slice() {
+            [ASN1Block::Sequence(_, key_algorithm), ASN1Block::BitString(_, _, key)] => {
+                let oid = match key_algorithm.first() {
+                    Some(ASN1Block::ObjectIdentifier(_, oid)) => oid.as_vec::<u64>()?,
+                    _ => anyhow::bail!("Missing key algorithm identifier"),
+                };
+
+                match oid.as_slice() {
+                    // rsaEncryption
+                    [1, 2, 840, 113549, 1, 1, 1] => match simple_asn1::from_der(key)?.as_slice() {
+                        [rsa_key] => (Algorithm::RS256, rsa_key_parameters(rsa_key)?),
+                        _ => anyhow::bail!("Malformed RSA public key"),
+                    },
+                    // id-Ed25519
+                    [1, 3, 101, 112] => (
+                        Algorithm::EdDSA,
+                        AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                            key_type: OctetKeyPairType::OctetKeyPair,
+                            curve: EllipticCurve::Ed25519,
+                            x: URL_SAFE_NO_PAD.encode(key),
+                        }),
+                    ),
+                    _ => anyhow::bail!("Unsupported key algorithm; expected RSA or Ed25519"),
+                }
+            }
+            _ => anyhow::bail!("Malformed SubjectPublicKeyInfo"),
+        },
+        (tag, _) => anyhow::bail!("Unsupported PEM block: {}", tag),
+    };
+
+    let key_algorithm = match algorithm {
+        Algorithm::EdDSA => KeyAlgorithm::EdDSA,
+        _ => KeyAlgorithm::RS256,
+    };
+
+    let jwk = Jwk {
+        common: CommonParameters {
+            public_key_use: Some(PublicKeyUse::Signature),
+            key_algorithm: Some(key_algorithm),
+            key_id: Some(kid.to_string()),
+            ..Default::default()
+        },
+        algorithm: parameters,
+    };
+
+    Ok((algorithm, jwk))
+}
+
+// RSAPublicKey ::= SEQUENCE { modulus INTEGER, publicExponent INTEGER }
+fn rsa_key_parameters(block: &ASN1Block) -> Result<AlgorithmParameters> {
+    match block {
+        ASN1Block::Sequence(_, items) => match items.as_slice() {
+            [ASN1Block::Integer(_, modulus), ASN1Block::Integer(_, exponent)] => {
+                Ok(AlgorithmParameters::RSA(RSAKeyParameters {
+                    key_type: RSAKeyType::RSA,
+                    n: URL_SAFE_NO_PAD.encode(modulus.to_bytes_be().1),
+                    e: URL_SAFE_NO_PAD.encode(exponent.to_bytes_be().1),
+                }))
+            }
+            _ => anyhow::bail!("Malformed RSA public key"),
+        },
+        _ => anyhow::bail!("Malformed RSA public key"),
+    }
 }
\ No newline at end of file
@@ -1,2 +1,9 @@
 pub mod jwt;
-pub mod auth_middleware;
\ No newline at end of file
+pub mod auth_middleware;
+pub mod token;
+pub mod client_ip;
+pub mod password;
+pub mod password_policy;
+pub mod oidc;
+pub mod client_device;
+pub mod auth_cookies;
\ No newline at end of file
@@ -0,0 +1,160 @@
+use anyhow::{Context, Result};
+use jsonwebtoken::jwk::{Jwk, JwkSet};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::sync::{LazyLock, OnceLock, RwLock};
+use std::time::{Duration, Instant};
+use tokio::sync::OnceCell;
+
+const DEFAULT_SCOPES: &str = "openid email profile";
+const HTTP_TIMEOUT_SECONDS: u64 = 10;
+
+// An unknown `kid` triggers a JWKS refetch (the provider rotated its keys), but not more often than this
+const JWKS_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+static PROVIDERS: OnceLock<BTreeMap<String, OidcProvider>> = OnceLock::new();
+
+static HTTP_CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(HTTP_TIMEOUT_SECONDS))
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .expect("Failed to build HTTP client")
+});
+
+/// An OpenID Connect provider users can log in with
+pub struct OidcProvider {
+    pub name: String,
+    pub issuer: String,
+    pub client_id: String,
+    /// Unset for public clients, which rely on PKCE alone
+    pub client_secret: Option<String>,
+    pub redirect_uri: String,
+    pub scopes: String,
+    metadata: OnceCell<ProviderMetadata>,
+    jwks: RwLock<Option<(JwkSet, Instant)>>,
+}
+
+/// The parts of the discovery document (`/.well-known/openid-configuration`) the login flow uses
+#[derive(Debug, Deserialize)]
+pub struct ProviderMetadata {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub jwks_uri: String,
+}
+
+/// Load the OpenID Connect providers. Must be called once at startup.
+///
+/// `OIDC_PROVIDERS` is a comma separated list of provider names, e.g. `google,keycloak`.
+/// Each provider is configured through `OIDC_<NAME>_ISSUER`, `OIDC_<NAME>_CLIENT_ID`,
+/// `OIDC_<NAME>_CLIENT_SECRET` (optional), `OIDC_<NAME>_REDIRECT_URI` and
+/// `OIDC_<NAME>_SCOPES` (defaulting to `openid email profile`). Endpoints and signing keys
+/// are discovered from the issuer on first use.
+pub fn load_providers() -> Result<()> {
+    let mut providers = BTreeMap::new();
+
+    let names = std::env::var("OIDC_PROVIDERS").unwrap_or_default();
+    for name in names.split(',').map(|name| name.trim().to_lowercase()).filter(|name| !name.is_empty()) {
+        let prefix = format!("OIDC_{}", name.to_uppercase().replace('-', "_"));
+        let setting = |key: &str| std::env::var(format!("{}_{}", prefix, key));
+
+        let provider = OidcProvider {
+            issuer: setting("ISSUER")
+                .with_context(|| format!("{}_ISSUER must be set", prefix))?
+                .trim_end_matches('/')
+                .to_string(),
+            client_id: setting("CLIENT_ID").with_context(|| format!("{}_CLIENT_ID must be set", prefix))?,
+            client_secret: setting("CLIENT_SECRET").ok().filter(|secret| !secret.is_empty()),
+            redirect_uri: setting("REDIRECT_URI").with_context(|| format!("{}_REDIRECT_URI must be set", prefix))?,
+            scopes: setting("SCOPES").unwrap_or_else(|_| DEFAULT_SCOPES.to_string()),
+            name: name.clone(),
+            metadata: OnceCell::new(),
+            jwks: RwLock::new(None),
+        };
+        providers.insert(name, provider);
+    }
+
+    PROVIDERS.set(providers).map_err(|_| anyhow::anyhow!("OIDC providers are already loaded"))
+}
+
+fn providers() -> &'static BTreeMap<String, OidcProvider> {
+    PROVIDERS.get().expect("OIDC providers are not loaded; call oidc::load_providers at startup")
+}
+
+pub fn provider(name: &str) -> Option<&'static OidcProvider> {
+    providers().get(name)
+}
+
+pub fn provider_names() -> Vec<String> {
+    providers().keys().cloned().collect()
+}
+
+/// Client for calls to the providers; redirects are not followed
+pub fn http_client() -> &'static reqwest::Client {
+    &HTTP_CLIENT
+}
+
+impl OidcProvider {
+    /// Discovery document of the provider, fetched once and cached
+    pub async fn metadata(&self) -> Result<&ProviderMetadata> {
+        self.metadata
+            .get_or_try_init(|| async {
+                let url = format!("{}/.well-known/openid-configuration", self.issuer);
+                let metadata: ProviderMetadata = http_client()
+                    .get(&url)
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await
+                    .with_context(|| format!("Invalid discovery document at {}", url))?;
+
+                // OpenID Connect Discovery 1.0, section 4.3
+                if metadata.issuer.trim_end_matches('/') != self.issuer {
+                    anyhow::bail!("Discovery document of {} names another issuer: {}", self.issuer, metadata.issuer);
+                }
+                Ok(metadata)
+            })
+            .await
+    }
+
+    /// Public key the provider signs ID tokens with under `kid`. Tokens without a `kid`
+    /// are only accepted while the provider publishes a single key.
+    pub async fn signing_key(&self, kid: Option<&str>) -> Result<Jwk> {
+        let refetch = match self.jwks.read().expect("JWKS lock poisoned").as_ref() {
+            Some((keys, fetched_at)) => match find_key(keys, kid) {
+                Some(key) => return Ok(key),
+                None => fetched_at.elapsed() >= JWKS_REFRESH_INTERVAL,
+            },
+            None => true,
+        };
+
+        if refetch {
+            let keys: JwkSet = http_client()
+                .get(&self.metadata().await?.jwks_uri)
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await
+                .context("Invalid JWKS document")?;
+            *self.jwks.write().expect("JWKS lock poisoned") = Some((keys, Instant::now()));
+        }
+
+        self.jwks
+            .read()
+            .expect("JWKS lock poisoned")
+            .as_ref()
+            .and_then(|(keys, _)| find_key(keys, kid))
+            .ok_or_else(|| anyhow::anyhow!("Unknown signing key: {}", kid.unwrap_or("<none>")))
+    }
+}
+
+fn find_key(keys: &JwkSet, kid: Option<&str>) -> Option<Jwk> {
+    match (kid, keys.keys.as_slice()) {
+        (Some(kid), _) => keys.find(kid).cloned(),
+        (None, [key]) => Some(key.clone()),
+        (None, _) => None,
+    }
+}
@@ -0,0 +1,181 @@
+use anyhow::{Context, Result};
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::{Algorithm, Argon2, Params, Version};
+use std::sync::OnceLock;
+
+const DEFAULT_BCRYPT_COST: u32 = bcrypt::DEFAULT_COST;
+
+/// Stored for accounts without a password (for example created through OpenID Connect); never verifies
+pub const UNUSABLE_PASSWORD_HASH: &str = "!";
+
+static SCHEME: OnceLock<HashScheme> = OnceLock::new();
+
+/// How new password hashes are created
+#[derive(Debug)]
+enum HashScheme {
+    Argon2id(Params),
+    Bcrypt { cost: u32 },
+}
+
+/// Load the password hashing configuration. Must be called once at startup.
+///
+/// `PASSWORD_HASH_ALGORITHM` selects `argon2id` (the default) or `bcrypt`. Argon2id is tuned
+/// through `ARGON2_MEMORY_KIB`, `ARGON2_ITERATIONS` and `ARGON2_PARALLELISM` (defaulting to
+/// 19 MiB, 2 and 1), bcrypt through `BCRYPT_COST`. Hashes of either algorithm always verify,
+/// whatever the configuration.
+pub fn load_config() -> Result<()> {
+    let algorithm = std::env::var("PASSWORD_HASH_ALGORITHM").unwrap_or_else(|_| "argon2id".to_string());
+
+    let scheme = match algorithm.as_str() {
+        "argon2id" => {
+            let params = Params::new(
+                env_or("ARGON2_MEMORY_KIB", Params::DEFAULT_M_COST),
+                env_or("ARGON2_ITERATIONS", Params::DEFAULT_T_COST),
+                env_or("ARGON2_PARALLELISM", Params::DEFAULT_P_COST),
+                None,
+            )
+            .context("Invalid Argon2 parameters")?;
+            HashScheme::Argon2id(params)
+        }
+        "bcrypt" => HashScheme::Bcrypt {
+            cost: env_or("BCRYPT_COST", DEFAULT_BCRYPT_COST),
+        },
+        other => anyhow::bail!("Unknown PASSWORD_HASH_ALGORITHM: {}", other),
+    };
+
+    SCHEME.set(scheme).map_err(|_| anyhow::anyhow!("Password hashing is already configured"))
+}
+
+fn scheme() -> &'static HashScheme {
+    SCHEME.get().expect("Password hashing is not configured; call password::load_config at startup")
+}
+
+/// Hash a password with the configured algorithm and parameters
+pub fn hash_password(password: &str) -> Result<String> {
+    match scheme() {
+        HashScheme::Argon2id(params) => {
+            let salt_bytes: [u8; 16] = rand::random();
+            let salt = SaltString::encode_b64(&salt_bytes)?;
+            let hash = argon2id(params.clone()).hash_password(password.as_bytes(), &salt)?;
+            Ok(hash.to_string())
+        }
+        HashScheme::Bcrypt { cost } => Ok(bcrypt::hash(password, *cost)?),
+    }
+}
+
+/// Verify a password against an argon2 (PHC string) or bcrypt hash.
+/// Anything else, such as `UNUSABLE_PASSWORD_HASH`, never matches.
+pub fn verify_password(password: &str, hash: &str) -> Result<bool> {
+    if hash.starts_with("$argon2") {
+        let parsed = PasswordHash::new(hash)?;
+        match Argon2::default().verify_password(password.as_bytes(), &parsed) {
+            Ok(()) => Ok(true),
+            Err(argon2::password_hash::Error::Password) => Ok(false),
+            Err(err) => Err(err.into()),
+        }
+    } else if hash.starts_with("$2") {
+        Ok(bcrypt::verify(password, hash)?)
+    } else {
+        Ok(false)
+    }
+}
+
+/// Whether `hash` was created with another algorithm or other parameters than the
+/// configured ones and should be replaced on the next successful login
+pub fn needs_rehash(hash: &str) -> bool {
+    scheme().needs_rehash(hash)
+}
+
+impl HashScheme {
+    fn needs_rehash(&self, hash: &str) -> bool {
+        match self {
+            HashScheme::Argon2id(params) => match PasswordHash::new(hash) {
+                Ok(parsed) => {
+                    parsed.algorithm != Algorithm::Argon2id.ident()
+                        || parsed.version != Some(Version::V0x13.into())
+                        || Params::try_from(&parsed).map_or(true, |current| {
+                            current.m_cost() != params.m_cost()
+                                || current.t_cost() != params.t_cost()
+                                || current.p_cost() != params.p_cost()
+                        })
+                }
+                Err(_) => true,
+            },
+            // bcrypt hashes look like `$2b$<cost>$<salt and hash>`
+            HashScheme::Bcrypt { cost } => hash
+                .split('$')
+                .nth(2)
+                .and_then(|current| current.parse::<u32>().ok())
+                .is_none_or(|current| current != *cost),
+        }
+    }
+}
+
+fn argon2id(params: Params) -> Argon2<'static> {
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+}
+
+fn env_or(name: &str, default: u32) -> u32 {
+    std::env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Small costs keep the tests fast; only the parameters matter here
+    fn argon2_hash(algorithm: Algorithm, m_cost: u32) -> String {
+        let params = Params::new(m_cost, 1, 1, None).unwrap();
+        let salt = SaltString::encode_b64(&[7u8; 16]).unwrap();
+        Argon2::new(algorithm, Version::V0x13, params)
+            .hash_password(b"correct horse", &salt)
+            .unwrap()
+            .to_string()
+    }
+
+    fn argon2id_scheme(m_cost: u32) -> HashScheme {
+        HashScheme::Argon2id(Params::new(m_cost, 1, 1, None).unwrap())
+    }
+
+    #[test]
+    fn argon2id_hash_with_current_params_is_kept() {
+        assert!(!argon2id_scheme(1024).needs_rehash(&argon2_hash(Algorithm::Argon2id, 1024)));
+    }
+
+    #[test]
+    fn argon2id_hash_with_other_params_is_replaced() {
+        assert!(argon2id_scheme(2048).needs_rehash(&argon2_hash(Algorithm::Argon2id, 1024)));
+    }
+
+    #[test]
+    fn other_argon2_variants_are_replaced() {
+        assert!(argon2id_scheme(1024).needs_rehash(&argon2_hash(Algorithm::Argon2i, 1024)));
+    }
+
+    #[test]
+    fn bcrypt_hash_is_replaced_when_argon2id_is_configured() {
+        let hash = bcrypt::hash("correct horse", 4).unwrap();
+        assert!(argon2id_scheme(1024).needs_rehash(&hash));
+    }
+
+    #[test]
+    fn bcrypt_hash_with_current_cost_is_kept() {
+        let hash = bcrypt::hash("correct horse", 4).unwrap();
+        assert!(!HashScheme::Bcrypt { cost: 4 }.needs_rehash(&hash));
+        assert!(HashScheme::Bcrypt { cost: 5 }.needs_rehash(&hash));
+    }
+
+    #[test]
+    fn argon2id_hash_is_replaced_when_bcrypt_is_configured() {
+        assert!(HashScheme::Bcrypt { cost: 4 }.needs_rehash(&argon2_hash(Algorithm::Argon2id, 1024)));
+    }
+
+    #[test]
+    fn unusable_hash_is_always_replaced() {
+        assert!(argon2id_scheme(1024).needs_rehash(UNUSABLE_PASSWORD_HASH));
+        assert!(HashScheme::Bcrypt { cost: 4 }.needs_rehash(UNUSABLE_PASSWORD_HASH));
+    }
+}
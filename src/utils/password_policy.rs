@@ -0,0 +1,350 @@
+use crate::models::auth_model::PasswordRuleViolation;
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::OnceLock;
+
+const DEFAULT_MIN_LENGTH: usize = 8;
+const DEFAULT_MAX_LENGTH: usize = 128;
+const DEFAULT_MIN_STRENGTH: u8 = 2;
+
+// Passwords and fragments attackers try first. Matching one costs a guesser almost nothing.
+const COMMON_WORDS: &[&str] = &[
+    "password", "passwort", "qwerty", "letmein", "welcome", "admin", "login", "master",
+    "monkey", "dragon", "iloveyou", "princess", "sunshine", "football", "baseball", "soccer",
+    "shadow", "superman", "batman", "secret", "starwars", "whatever", "freedom",
+    "hello", "charlie", "michael", "jordan", "hunter", "killer", "summer", "winter", "spring",
+    "autumn", "changeme", "default", "access", "computer", "internet", "server", "notes",
+];
+
+// Keyboard rows; runs along them are as guessable as dictionary words. Digit runs are
+// already cheap as sequences.
+const KEYBOARD_ROWS: &[&str] = &["qwertyuiop", "asdfghjkl", "zxcvbnm"];
+
+static POLICY: OnceLock<PasswordPolicy> = OnceLock::new();
+
+struct PasswordPolicy {
+    min_length: usize,
+    max_length: usize,
+    min_strength: u8,
+    breached_passwords: HashSet<String>,
+}
+
+/// Returned when a password breaks one or more policy rules
+#[derive(Debug)]
+pub struct PasswordPolicyViolations(pub Vec<PasswordRuleViolation>);
+
+impl fmt::Display for PasswordPolicyViolations {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let messages: Vec<&str> = self.0.iter().map(|violation| violation.message.as_str()).collect();
+        write!(f, "Password does not meet the policy: {}", messages.join("; "))
+    }
+}
+
+impl std::error::Error for PasswordPolicyViolations {}
+
+/// Load the password policy. Must be called once at startup.
+///
+/// Configured through `PASSWORD_MIN_LENGTH`, `PASSWORD_MAX_LENGTH`, `PASSWORD_MIN_STRENGTH`
+/// (a zxcvbn-style score from 0 to 4) and `PASSWORD_BREACHED_LIST_FILE`, a local file with one
+/// known breached password per line.
+pub fn load_policy() -> Result<()> {
+    let breached_passwords = match std::env::var("PASSWORD_BREACHED_LIST_FILE") {
+        Ok(path) => std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read breached password list {}", path))?
+            .lines()
+            .map(|line| line.trim_end_matches('\r'))
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect(),
+        Err(_) => HashSet::new(),
+    };
+
+    let policy = PasswordPolicy {
+        min_length: env_or("PASSWORD_MIN_LENGTH", DEFAULT_MIN_LENGTH),
+        max_length: env_or("PASSWORD_MAX_LENGTH", DEFAULT_MAX_LENGTH),
+        min_strength: env_or("PASSWORD_MIN_STRENGTH", DEFAULT_MIN_STRENGTH as usize).min(4) as u8,
+        breached_passwords,
+    };
+
+    POLICY.set(policy).map_err(|_| anyhow::anyhow!("Password policy is already loaded"))
+}
+
+fn policy() -> &'static PasswordPolicy {
+    POLICY.get().expect("Password policy is not loaded; call password_policy::load_policy at startup")
+}
+
+/// Check a new password against every rule of the policy.
+/// Fails with `PasswordPolicyViolations` listing each broken rule.
+pub fn check_password(password: &str, username: &str, email: &str) -> Result<()> {
+    policy().check(password, username, email)
+}
+
+impl PasswordPolicy {
+    fn check(&self, password: &str, username: &str, email: &str) -> Result<()> {
+        let mut violations = Vec::new();
+        let length = password.chars().count();
+
+        if length < self.min_length {
+            violations.push(violation("min_length", format!("Must be at least {} characters long", self.min_length)));
+        }
+        if length > self.max_length {
+            violations.push(violation("max_length", format!("Must be at most {} characters long", self.max_length)));
+        }
+
+        let lowercase = password.to_lowercase();
+        let username = username.trim().to_lowercase();
+        let email = email.trim().to_lowercase();
+        let email_local_part = email.split('@').next().unwrap_or_default();
+
+        if username.chars().count() >= 3 && lowercase.contains(&username) {
+            violations.push(violation("contains_username", "Must not contain your username".to_string()));
+        }
+        if email_local_part.chars().count() >= 3 && lowercase.contains(email_local_part) {
+            violations.push(violation("contains_email", "Must not contain your email address".to_string()));
+        }
+
+        if self.breached_passwords.contains(password) {
+            violations.push(violation("breached", "Appears in a list of breached passwords".to_string()));
+        }
+
+        let strength = strength_score(password);
+        if strength < self.min_strength {
+            violations.push(violation(
+                "strength",
+                format!("Is too easy to guess (strength {} of 4, at least {} required)", strength, self.min_strength),
+            ));
+        }
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(PasswordPolicyViolations(violations).into())
+        }
+    }
+}
+
+/// Strength score from 0 (too guessable) to 4 (very unguessable), on the same scale as zxcvbn:
+/// the estimated number of guesses is bucketed at 10^3, 10^6, 10^8 and 10^10.
+///
+/// The estimate charges every character by the size of the character classes in use, but
+/// repeats, sequences (`abc`, `321`), years, keyboard runs and common words (also in l33t
+/// spelling) only cost a few bits each, since guessers try those first.
+pub fn strength_score(password: &str) -> u8 {
+    let chars: Vec<char> = password.chars().collect();
+    if chars.is_empty() {
+        return 0;
+    }
+
+    let alphabet_bits = (alphabet_size(&chars) as f64).log2();
+    let normalized: Vec<char> = chars.iter().map(|c| unleet(c.to_ascii_lowercase())).collect();
+    let mut bits = 0.0;
+    let mut index = 0;
+
+    while index < chars.len() {
+        // Dictionary words and keyboard runs are one guess from a short list
+        if let Some(length) = pattern_match_at(&normalized, index) {
+            bits += (COMMON_WORDS.len() as f64).log2() + 1.0;
+            index += length;
+            continue;
+        }
+
+        // Recent years are one guess out of a couple hundred
+        let year = chars
+            .get(index..index + 4)
+            .and_then(|digits| digits.iter().collect::<String>().parse::<u32>().ok());
+        if year.is_some_and(|year| (1900..=2099).contains(&year)) {
+            bits += 200f64.log2();
+            index += 4;
+            continue;
+        }
+
+        let predictable = index > 0 && {
+            let previous = chars[index - 1] as i64;
+            let current = chars[index] as i64;
+            (current - previous).abs() <= 1
+        };
+        bits += if predictable { 1.0 } else { alphabet_bits };
+        index += 1;
+    }
+
+    let log10_guesses = bits * 2f64.log10();
+    match log10_guesses {
+        guesses if guesses < 3.0 => 0,
+        guesses if guesses < 6.0 => 1,
+        guesses if guesses < 8.0 => 2,
+        guesses if guesses < 10.0 => 3,
+        _ => 4,
+    }
+}
+
+// Length of the longest common word or keyboard run (at least four characters) starting at `index`
+fn pattern_match_at(normalized: &[char], index: usize) -> Option<usize> {
+    let rest: String = normalized[index..].iter().collect();
+
+    let word = COMMON_WORDS
+        .iter()
+        .filter(|word| word.len() >= 4 && rest.starts_with(*word))
+        .map(|word| word.len())
+        .max();
+
+    let keyboard_run = KEYBOARD_ROWS
+        .iter()
+        .filter_map(|row| {
+            (4..=row.len())
+                .rev()
+                .find(|&length| (0..=row.len() - length).any(|start| rest.starts_with(&row[start..start + length])))
+        })
+        .max();
+
+    word.max(keyboard_run)
+}
+
+fn alphabet_size(chars: &[char]) -> usize {
+    let mut size = 0;
+    if chars.iter().any(|c| c.is_ascii_lowercase()) {
+        size += 26;
+    }
+    if chars.iter().any(|c| c.is_ascii_uppercase()) {
+        size += 26;
+    }
+    if chars.iter().any(|c| c.is_ascii_digit()) {
+        size += 10;
+    }
+    if chars.iter().any(|c| c.is_ascii_punctuation() || *c == ' ') {
+        size += 33;
+    }
+    if chars.iter().any(|c| !c.is_ascii()) {
+        size += 100;
+    }
+    size
+}
+
+// Undo the usual l33t substitutions so `p@ssw0rd` still matches `password`
+fn unleet(c: char) -> char {
+    match c {
+        '@' | '4' => 'a',
+        '3' => 'e',
+        '1' | '!' => 'i',
+        '0' => 'o',
+        '$' | '5' => 's',
+        '7' => 't',
+        other => other,
+    }
+}
+
+fn violation(rule: &str, message: String) -> PasswordRuleViolation {
+    PasswordRuleViolation {
+        rule: rule.to_string(),
+        message,
+    }
+}
+
+fn env_or(name: &str, default: usize) -> usize {
+    std::env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_policy() -> PasswordPolicy {
+        PasswordPolicy {
+            min_length: DEFAULT_MIN_LENGTH,
+            max_length: DEFAULT_MAX_LENGTH,
+            min_strength: 0,
+            breached_passwords: HashSet::new(),
+        }
+    }
+
+    fn violated_rules(policy: &PasswordPolicy, password: &str, username: &str, email: &str) -> Vec<String> {
+        match policy.check(password, username, email) {
+            Ok(()) => Vec::new(),
+            Err(err) => err
+                .downcast::<PasswordPolicyViolations>()
+                .unwrap()
+                .0
+                .into_iter()
+                .map(|violation| violation.rule)
+                .collect(),
+        }
+    }
+
+    fn normalized(password: &str) -> Vec<char> {
+        password.chars().map(|c| unleet(c.to_ascii_lowercase())).collect()
+    }
+
+    #[test]
+    fn strength_score_buckets() {
+        assert_eq!(strength_score(""), 0);
+        assert_eq!(strength_score("kq7"), 1);
+        assert_eq!(strength_score("kq7x"), 2);
+        assert_eq!(strength_score("kq7xmw"), 3);
+        assert_eq!(strength_score("kq7xmwz3p"), 4);
+    }
+
+    #[test]
+    fn common_words_score_low_even_in_leet() {
+        assert_eq!(strength_score("password"), 0);
+        assert_eq!(strength_score("p@ssw0rd"), 0);
+    }
+
+    #[test]
+    fn repeats_and_sequences_score_low() {
+        assert_eq!(strength_score("aaaaaaaa"), 1);
+        assert_eq!(strength_score("abcdefgh"), 1);
+        assert_eq!(strength_score("87654321"), 1);
+        assert!(strength_score("hcfaedbg") > strength_score("abcdefgh"));
+    }
+
+    #[test]
+    fn years_score_low() {
+        assert!(strength_score("1987") < strength_score("1x8y"));
+    }
+
+    #[test]
+    fn pattern_match_at_finds_keyboard_runs() {
+        assert_eq!(pattern_match_at(&normalized("qwer"), 0), Some(4));
+        assert_eq!(pattern_match_at(&normalized("xasdfg"), 1), Some(5));
+        assert_eq!(pattern_match_at(&normalized("zxcvbnm"), 0), Some(7));
+        assert_eq!(pattern_match_at(&normalized("xasdfg"), 0), None);
+        assert_eq!(pattern_match_at(&normalized("qwe"), 0), None);
+    }
+
+    #[test]
+    fn pattern_match_at_prefers_the_longest_match() {
+        // "qwerty" is both a common word and a keyboard run; "qwertyui" is a longer run
+        assert_eq!(pattern_match_at(&normalized("qwertyui"), 0), Some(8));
+        assert_eq!(pattern_match_at(&normalized("sunshine"), 0), Some(8));
+        assert_eq!(pattern_match_at(&normalized("5un5h1n3"), 0), Some(8));
+    }
+
+    #[test]
+    fn rejects_username_and_email_local_part_regardless_of_case() {
+        let rules = violated_rules(&test_policy(), "xxAliceSmith99yy", "alicesmith", "ALICE.S@example.com");
+        assert_eq!(rules, vec!["contains_username"]);
+
+        let rules = violated_rules(&test_policy(), "my-alice.s-pass", "someone", " ALICE.S@example.com ");
+        assert_eq!(rules, vec!["contains_email"]);
+    }
+
+    #[test]
+    fn ignores_usernames_and_local_parts_shorter_than_three_characters() {
+        assert!(violated_rules(&test_policy(), "jo-password-ab", "jo", "ab@example.com").is_empty());
+    }
+
+    #[test]
+    fn lists_every_broken_rule() {
+        let policy = PasswordPolicy {
+            min_strength: 3,
+            breached_passwords: HashSet::from(["bob1".to_string()]),
+            ..test_policy()
+        };
+        let rules = violated_rules(&policy, "bob1", "bob", "bob@example.com");
+
+        assert_eq!(rules, vec!["min_length", "contains_username", "contains_email", "breached", "strength"]);
+    }
+}